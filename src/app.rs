@@ -1,219 +1,2157 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
 use crossterm::event;
 use crossterm::event::{Event, KeyCode};
 use num_traits::Num;
-use std::collections::HashMap;
 
-use crate::calculator::{Line, MeasurementType, Point};
+use crate::calculator::adc::Adc;
+use crate::calculator::budget::{self, Combined, Contributor, Distribution, Side, Term};
+use crate::calculator::dac::Dac;
+use crate::calculator::drift::{self, Drift};
+use crate::calculator::robust::{self, Robust};
+use crate::calculator::thermistor::Divider;
+use crate::calculator::verify::{Check, Tolerance};
+use crate::calculator::{
+    CalcError, Covariance, FitOptions, LoopScaling, LoopStatus, MeasurementType, Model, ModelKind,
+    PlcScaling, Point, Regression,
+};
+use crate::codegen::{self, Language};
+use crate::history::{self, Entry};
+use crate::lut::{self, LutOptions};
+use crate::metadata::{self, Field, Metadata};
+use crate::plot;
+use crate::report;
+use crate::session;
+use crate::units::{self, Dimension, Quantity, Unit, UnitError};
+
+// How many points the curve is sampled at for the chart when it isn't a straight line
+const CURVE_SAMPLES: usize = 60;
+// Confidence levels the bands cycle through
+const CONFIDENCE_LEVELS: [f64; 3] = [0.90, 0.95, 0.99];
+
+// ADC quantization at the tester's point, in the session units
+pub struct Quantization {
+    // output change for one code
+    pub resolution: f64,
+    // code the ADC reports for the test point and the output band that code stands for
+    pub code: Option<f64>,
+    pub band: Option<(f64, f64)>,
+}
+
+// The DAC code to command for the tester's output in an output calibration
+pub struct DacCommand {
+    // code the calibration gives, before rounding
+    pub ideal: f64,
+    // code rounded to the DAC's resolution and kept in its range
+    pub code: f64,
+    // what that code gives, and how far it is from the output asked for
+    pub output: f64,
+    pub error: f64,
+}
+
+// Confidence bands (for the fitted curve) and prediction bands (for a new reading) around the
+// curve as (input, output) lower and upper edges
+#[derive(Default)]
+pub struct Bands {
+    pub confidence: [Vec<(f64, f64)>; 2],
+    pub prediction: [Vec<(f64, f64)>; 2],
+}
+
+// Uncertainty budget worked out at an input: a term for each contributor, in order, then the fit's
+pub struct Budget {
+    pub at: f64,
+    pub terms: Vec<Term>,
+    pub fit: Option<Term>,
+    pub combined: Combined,
+}
+
+// What a fit learns about the points besides the model, see the fields of the same names on App
+struct FitDetails {
+    weights: Vec<Option<f64>>,
+    covariance: Option<Covariance>,
+    ols_line: Option<(f64, f64)>,
+    outliers: Vec<bool>,
+}
+
+impl FitDetails {
+    fn new(points: usize) -> Self {
+        FitDetails {
+            weights: vec![None; points],
+            covariance: None,
+            ols_line: None,
+            outliers: vec![false; points],
+        }
+    }
+}
+
+// Calibration stages: the sensor as it was found, and as it was left after adjustment
+#[derive(Clone, Copy, PartialEq)]
+pub enum Stage {
+    AsFound,
+    AsLeft,
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::AsFound => "as-found",
+            Stage::AsLeft => "as-left",
+        }
+    }
+}
+
+// The stage being edited, and the other one's points and model kept to compare against
+struct Stages {
+    live: Stage,
+    other_points: Vec<Point>,
+    other_model: Result<Model, CalcError>,
+}
+
+// As-found against as-left, point by point in order. Errors are what each stage's calibration
+// indicates for its own reading less the nominal output
+pub struct StageComparison {
+    // (slope, offset) of each stage when both models are lines
+    pub lines: Option<[(f64, f64); 2]>,
+    // otherwise the slope and output of each at the working point
+    pub at: f64,
+    pub slopes: [Option<f64>; 2],
+    pub outputs: [Option<f64>; 2],
+    pub rows: Vec<StageRow>,
+}
+
+pub struct StageRow {
+    pub nominal: f64,
+    // (reading, error) as-found then as-left
+    pub found: Option<(f64, f64)>,
+    pub left: Option<(f64, f64)>,
+}
+
+impl StageRow {
+    // Change in error from as-found to as-left
+    pub fn delta(&self) -> Option<f64> {
+        Some(self.left?.1 - self.found?.1)
+    }
+}
+
+// When the calibration is expected to drift out of tolerance: the input it goes first at, the
+// allowed error there and the day (since 1970) it is reached
+pub struct DriftForecast {
+    pub at: f64,
+    pub limit: f64,
+    pub day: f64,
+}
+
+// Another calibration loaded to compare this one against, kept whole so any model converts
+struct Compared {
+    label: String,
+    channel: Box<Channel>,
+}
+
+// This calibration against the compared one across the operating range (the inputs of the points)
+pub struct CalibrationDifference {
+    pub label: String,
+    // the compared calibration's curve
+    pub other_plot: Vec<(f64, f64)>,
+    // this one's output less the compared one's
+    pub difference: Vec<(f64, f64)>,
+    // (input, difference) where they are furthest apart
+    pub max: Option<(f64, f64)>,
+}
 
 pub struct Bounds {
     pub labels: Vec<String>,
     pub bounds: (f64, f64),
 }
 
+// A calibration of its own, one of the session's channels
+pub struct Channel {
+    points: Vec<Point>,
+    // index of the highlighted point in the point list
+    selected: usize,
+    model_kind: ModelKind,
+    fit_options: FitOptions,
+    model: Result<Model, CalcError>,
+    // relative weight each point got in the fit (largest is 1), None for unweighted fits
+    weights: Vec<Option<f64>>,
+    // covariance of the fitted (slope, intercept) or curve (a, b)
+    covariance: Option<Covariance>,
+    // confidence level of the bands on the chart
+    confidence: f64,
+    bands: Bands,
+    // ordinary least squares (slope, intercept) to compare an errors-in-variables line against
+    ols_line: Option<(f64, f64)>,
+    // points whose studentized residual marks them as outliers, aligned with the point list
+    outliers: Vec<bool>,
+    pub testing_value: Option<MeasurementType>,
+    test_point: Option<[(f64, f64); 1]>,
+    plot: Vec<(f64, f64)>,
+    input: Quantity,
+    output: Quantity,
+    loop_scaling: Option<LoopScaling>,
+    // raw range the line is shown over as PLC scaling, the points' inputs when not chosen
+    plc_raw: Option<(f64, f64)>,
+    adc: Option<Adc>,
+    // output calibration, the input is the code commanded to this DAC
+    dac: Option<Dac>,
+    divider: Option<Divider>,
+    // reference junction temperature in °C for the thermocouple models
+    cold_junction: f64,
+    // entered lines of the uncertainty budget and the one selected on its screen
+    contributors: Vec<Contributor>,
+    budget_selected: usize,
+    // verification readings as (measured input, nominal output) and the spec they are held to
+    verification: Vec<Point>,
+    verify_selected: usize,
+    tolerance: Option<Tolerance>,
+    // as-found / as-left workflow, None until the as-found calibration is recorded
+    stages: Option<Stages>,
+    // calibration details and the field selected on their form
+    metadata: Metadata,
+    metadata_selected: usize,
+    // the sensor's past calibrations, read from the history by serial number
+    history: Vec<Entry>,
+    compared: Option<Compared>,
+}
+
+// The channels of a rig calibrated in one session and the one being worked on, always at least one
+struct Rig {
+    channels: Vec<Channel>,
+    active: usize,
+}
+
+impl Rig {
+    fn active(&self) -> &Channel {
+        &self.channels[self.active]
+    }
+
+    // Borrows only the channels, so the app's own fields can be used alongside
+    fn active_mut(&mut self) -> &mut Channel {
+        &mut self.channels[self.active]
+    }
+}
+
+/*
+ * What the terminal shows and the settings of the session as a whole. Everything else belongs to
+ * a channel, read and changed through active() and active_mut()
+ */
 pub struct App {
-    p1: Option<Point>,
-    p2: Option<Point>,
-    pub line: Option<Line>,
+    rig: Rig,
     current_screen: ScreenID,
     mode: Mode,
-    pub testing_value: Option<MeasurementType>,
     currently_editing: Option<CurrentlyEditing>,
     temp_point: Option<String>,
-    test_point: Option<[(f64, f64); 1]>,
-    plot: Vec<(f64, f64)>,
+    // file w saves the session to, and what happened the last time
+    session_path: PathBuf,
+    message: Option<String>,
+    // where calibrations are filed by serial number
+    history_dir: PathBuf,
+    // the team's report templates, one per format, instead of the built in ones
+    report_templates: Vec<PathBuf>,
+    // width and height of exported charts in pixels
+    chart_size: (u32, u32),
+    // how lookup tables for firmware are made
+    lut_options: LutOptions,
 }
 
-pub enum CurrentlyEditing {
-    Voltage,
-    Physical,
-}
+#[derive(PartialEq)]
+pub enum CurrentlyEditing {
+    Input,
+    Output,
+    // standard uncertainties of the point's values
+    InputUncertainty,
+    OutputUncertainty,
+    // the tester's cold junction temperature, only with thermocouple models
+    ColdJunction,
+    // a column of the selected uncertainty budget line
+    Budget(BudgetField),
+    // the verification tolerance spec
+    Tolerance,
+    // the selected field of the calibration details
+    Metadata,
+    // path of a session to compare against
+    ComparePath,
+    // PLC raw range, or the whole scaling to take the line from
+    PlcScaling,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum BudgetField {
+    Name,
+    Side,
+    Value,
+    Distribution,
+    Dof,
+}
+
+pub enum Mode {
+    Edit,
+    EditingValue,
+    Select,
+    // D was pressed, y removes the channel and any other key keeps it
+    ConfirmRemove,
+    Quit,
+}
+pub enum ScreenID {
+    Points,
+    Tester,
+    Budget,
+    Verify,
+    Stages,
+    Metadata,
+    History,
+    Compare,
+}
+
+impl Channel {
+    pub fn new(input: Quantity, output: Quantity) -> Self {
+        let mut channel = Channel {
+            points: vec![Point::from((5.0, 100.0)), Point::from((0.0, 0.0))],
+            selected: 0,
+            model_kind: ModelKind::Linear,
+            fit_options: FitOptions::default(),
+            model: Err(CalcError::Singular),
+            weights: Vec::new(),
+            covariance: None,
+            confidence: 0.95,
+            bands: Bands::default(),
+            ols_line: None,
+            outliers: Vec::new(),
+            test_point: None,
+            testing_value: None,
+            plot: Vec::new(),
+            input,
+            output,
+            loop_scaling: None,
+            plc_raw: None,
+            adc: None,
+            dac: None,
+            divider: None,
+            cold_junction: 0.0,
+            // the usual suspects, to be filled in
+            contributors: vec![
+                Contributor::new(
+                    "Reference standard",
+                    Side::Output,
+                    Distribution::Normal(2.0),
+                ),
+                Contributor::new("DMM accuracy", Side::Input, Distribution::Rectangular),
+                Contributor::new("Resolution", Side::Input, Distribution::Rectangular),
+                Contributor::new("Repeatability", Side::Output, Distribution::Normal(1.0)),
+            ],
+            budget_selected: 0,
+            verification: Vec::new(),
+            verify_selected: 0,
+            tolerance: None,
+            stages: None,
+            metadata: Metadata::default(),
+            metadata_selected: 0,
+            history: Vec::new(),
+            compared: None,
+        };
+        // a new calibration is taken now unless told otherwise
+        channel.metadata.set(Field::Date, &metadata::now());
+        channel.update_model();
+        channel
+    }
+
+    // Fits the session's model to the points, converting them to the model's units first
+    pub fn update_model(&mut self) {
+        let mut details = FitDetails::new(self.points.len());
+        self.model = self.fit_model(&self.points, &mut details);
+        self.weights = details.weights;
+        self.covariance = details.covariance;
+        self.ols_line = details.ols_line;
+        self.outliers = details.outliers;
+        // the shelved stage follows the same model and options
+        let other = self.stages.as_ref().map(|s| {
+            let mut details = FitDetails::new(s.other_points.len());
+            self.fit_model(&s.other_points, &mut details)
+        });
+        if let (Some(stages), Some(model)) = (self.stages.as_mut(), other) {
+            stages.other_model = model;
+        }
+        self.update_vector();
+    }
+
+    /*
+     * Points with stated uncertainties are weighted by 1/u^2 of their output error. An input
+     * uncertainty counts through the slope of the model at the point, so the fit is repeated a
+     * few times for the slope to settle. Points without one get the largest stated on that axis.
+     * A robust fit then reweights on top of that, and finally the residuals are checked for outliers
+     */
+    fn fit_model(&self, points: &[Point], details: &mut FitDetails) -> Result<Model, CalcError> {
+        let valid: Vec<usize> = (0..points.len())
+            .filter(|i| points[*i].is_valid() && !points[*i].is_excluded())
+            .collect();
+        let mut pairs = Vec::new();
+        let mut sigmas = Vec::new();
+        for i in &valid {
+            let vals = points[*i].get_val();
+            let (x, y) = (vals["in"], vals["out"]);
+            let pair = (self.to_model_input(x)?, self.to_model_output(y)?);
+            // uncertainties carried over into the model's units
+            let (ux, uy) = points[*i].get_uncertainty();
+            let ux = ux.and_then(|u| Some((self.to_model_input(x + u).ok()? - pair.0).abs()));
+            let uy = uy.and_then(|u| Some((self.to_model_output(y + u).ok()? - pair.1).abs()));
+            pairs.push(pair);
+            sigmas.push((ux, uy));
+        }
+        let largest = |v: Vec<Option<f64>>| v.into_iter().flatten().reduce(f64::max);
+        let max_x = largest(sigmas.iter().map(|s| s.0).collect());
+        let max_y = largest(sigmas.iter().map(|s| s.1).collect());
+
+        let mut model = Model::fit(self.model_kind, &pairs, None, &self.fit_options)?;
+        let mut weights = None;
+        if max_x.is_some() || max_y.is_some() {
+            for _ in 0..3 {
+                let variance: Vec<f64> = pairs
+                    .iter()
+                    .zip(&sigmas)
+                    .map(|((x, _), (ux, uy))| {
+                        let (ux, uy) = (ux.or(max_x).unwrap_or(0.0), uy.or(max_y).unwrap_or(0.0));
+                        let m = model.slope_at(*x).unwrap_or(0.0);
+                        uy * uy + (m * ux).powi(2)
+                    })
+                    .collect();
+                // a point stated as exact can't have an infinite weight
+                let floor = variance
+                    .iter()
+                    .cloned()
+                    .filter(|v| *v > 0.0)
+                    .fold(f64::INFINITY, f64::min);
+                if !floor.is_finite() {
+                    break;
+                }
+                let w: Vec<f64> = variance.iter().map(|v| 1.0 / v.max(floor)).collect();
+                model = Model::fit(self.model_kind, &pairs, Some(&w), &self.fit_options)?;
+                weights = Some(w);
+            }
+        }
+        let stated = weights.is_some();
+        let base = weights.unwrap_or_else(|| vec![1.0; pairs.len()]);
+        let residuals = |model: &Model| -> Vec<f64> {
+            pairs
+                .iter()
+                .map(|(x, y)| {
+                    let f = model.get_corresponding_value(&MeasurementType::Input(*x));
+                    f.map_or(f64::INFINITY, |f| y - f)
+                })
+                .collect()
+        };
+        let mut w = base.clone();
+        let robust = self.fit_options.robust;
+        if robust != Robust::Off {
+            let (kind, options) = (self.model_kind, &self.fit_options);
+            // points the robust fit drops altogether are left out rather than given no weight
+            let fit = |mult: &[f64]| {
+                let (p, w): (Vec<(f64, f64)>, Vec<f64>) = pairs
+                    .iter()
+                    .zip(&base)
+                    .zip(mult)
+                    .filter(|(_, m)| **m > 0.0)
+                    .map(|((p, w), m)| (*p, w * m))
+                    .unzip();
+                Model::fit(kind, &p, Some(&w), options)
+            };
+            let k = self.model_kind.min_points().max(2);
+            let (robust_model, mult) = robust.fit(&base, k, fit, residuals)?;
+            model = robust_model;
+            w = base.iter().zip(mult).map(|(w, m)| w * m).collect();
+        }
+        if stated || robust != Robust::Off {
+            let max = w.iter().cloned().fold(0.0, f64::max);
+            for (i, w) in valid.iter().zip(&w) {
+                details.weights[*i] = Some(w / max);
+            }
+        }
+        // studentized residuals against the points' own uncertainties
+        let inputs: Vec<f64> = pairs.iter().map(|p| p.0).collect();
+        let leverage = robust::leverage(&inputs, &base);
+        let t = robust::studentized(&residuals(&model), &base, &leverage, 2);
+        for (i, t) in valid.iter().zip(t) {
+            details.outliers[*i] = t.abs() > robust::OUTLIER_LIMIT;
+        }
+        // what the robust fit dropped doesn't count in the OLS comparison either
+        let (pairs, w): (Vec<(f64, f64)>, Vec<f64>) = pairs
+            .iter()
+            .zip(&w)
+            .filter(|(_, w)| **w > 0.0)
+            .map(|(p, w)| (*p, *w))
+            .unzip();
+        details.covariance = model.covariance(&pairs, &w, stated);
+        if let (ModelKind::Linear, Regression::Deming | Regression::Orthogonal) =
+            (self.model_kind, self.fit_options.regression)
+        {
+            let ols = FitOptions {
+                regression: Regression::Ordinary,
+                ..self.fit_options
+            };
+            details.ols_line = Model::fit(self.model_kind, &pairs, Some(&w), &ols)
+                .ok()
+                .and_then(|m| m.get_line());
+        }
+        Ok(model)
+    }
+
+    // Function to update the vector we will use to plot
+    pub fn update_vector(&mut self) {
+        // vector contents should look like [(v0,p0),(v1,p1), (v2,p2)....(vn,pn)]
+
+        // Wipe the existing vector
+        self.plot = Vec::new();
+        self.bands = Bands::default();
+
+        // the curve covers the inputs of the points
+        let inputs = self
+            .points
+            .iter()
+            .filter(|p| p.is_valid())
+            .map(|p| p.get_val()["in"]);
+        let (start, end) = inputs.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| {
+            (lo.min(x), hi.max(x))
+        });
+        if start >= end {
+            return;
+        }
+        // a line only needs its ends, curves get sampled
+        let samples = match self.model_kind {
+            ModelKind::Linear => 2,
+            _ => CURVE_SAMPLES,
+        };
+        for i in 0..samples {
+            let x = start + (end - start) * i as f64 / (samples - 1) as f64;
+            if let Ok(y) = self.convert(&MeasurementType::Input(x)) {
+                self.plot.push((x, y));
+            }
+        }
+        // bands curve even around a line so they always get the full sampling. Only the linear
+        // and curve models have them, and those work in the session units
+        if let (Ok(model), Some(cov)) = (self.model.as_ref(), self.covariance.as_ref()) {
+            let k = cov.coverage(self.confidence);
+            for i in 0..CURVE_SAMPLES {
+                let x = start + (end - start) * i as f64 / (CURVE_SAMPLES - 1) as f64;
+                let y = self.convert(&MeasurementType::Input(x));
+                if let (Ok(y), Some((fitted, reading))) = (y, model.band(cov, x)) {
+                    self.bands.confidence[0].push((x, y - k * fitted));
+                    self.bands.confidence[1].push((x, y + k * fitted));
+                    self.bands.prediction[0].push((x, y - k * reading));
+                    self.bands.prediction[1].push((x, y + k * reading));
+                }
+            }
+        }
+    }
+
+    /*
+     * Conversions between the session units and the units the model is fitted in.
+     * The linear model works in the session units; thermistors work in ohms and kelvin and
+     * RTDs in ohms and °C, with the divider turning a measured voltage into ohms.
+     * Thermocouples work in mV against a 0 °C junction so the cold junction's EMF is added on
+     */
+    fn to_model_input(&self, x: f64) -> Result<f64, CalcError> {
+        match (self.model_kind.units(), self.divider.as_ref()) {
+            (None, _) => Ok(x),
+            (Some((Unit::Ohm, _)), Some(divider)) => divider.resistance(
+                self.input
+                    .unit
+                    .convert(x, Unit::Volt)
+                    .map_err(|_| CalcError::OutOfDomain("divider input must be a voltage"))?,
+            ),
+            (Some((unit, _)), _) => {
+                let x = self
+                    .input
+                    .unit
+                    .convert(x, unit)
+                    .map_err(|_| model_input_error(unit))?;
+                Ok(x + self.cold_junction_emf()?)
+            }
+        }
+    }
+
+    fn input_from_model(&self, x: f64) -> Result<f64, CalcError> {
+        match (self.model_kind.units(), self.divider.as_ref()) {
+            (None, _) => Ok(x),
+            (Some((Unit::Ohm, _)), Some(divider)) => Unit::Volt
+                .convert(divider.voltage(x), self.input.unit)
+                .map_err(|_| CalcError::OutOfDomain("divider input must be a voltage")),
+            (Some((unit, _)), _) => unit
+                .convert(x - self.cold_junction_emf()?, self.input.unit)
+                .map_err(|_| model_input_error(unit)),
+        }
+    }
+
+    // EMF of the cold junction relative to 0 °C, nothing for the other models
+    pub fn cold_junction_emf(&self) -> Result<f64, CalcError> {
+        match self.model_kind {
+            ModelKind::Thermocouple(t) => t.emf(self.cold_junction),
+            _ => Ok(0.0),
+        }
+    }
+
+    fn to_model_output(&self, y: f64) -> Result<f64, CalcError> {
+        match self.model_kind.units() {
+            None => Ok(y),
+            Some((_, unit)) => self
+                .output
+                .unit
+                .convert(y, unit)
+                .map_err(|_| CalcError::OutOfDomain("output must be a temperature")),
+        }
+    }
+
+    fn output_from_model(&self, y: f64) -> Result<f64, CalcError> {
+        match self.model_kind.units() {
+            None => Ok(y),
+            Some((_, unit)) => unit
+                .convert(y, self.output.unit)
+                .map_err(|_| CalcError::OutOfDomain("output must be a temperature")),
+        }
+    }
+
+    // Run a value through the calibration in session units: input -> output or output -> input
+    pub fn convert(&self, value: &MeasurementType) -> Result<f64, CalcError> {
+        self.convert_with(&self.model, value)
+    }
+
+    // Same through another fit of the session's model, like the shelved stage's
+    fn convert_with(
+        &self,
+        model: &Result<Model, CalcError>,
+        value: &MeasurementType,
+    ) -> Result<f64, CalcError> {
+        let model = model.as_ref().map_err(|e| e.clone())?;
+        match value {
+            MeasurementType::Input(x) => {
+                let y = model
+                    .get_corresponding_value(&MeasurementType::Input(self.to_model_input(*x)?))?;
+                self.output_from_model(y)
+            }
+            MeasurementType::Output(y) => {
+                let x = model
+                    .get_corresponding_value(&MeasurementType::Output(self.to_model_output(*y)?))?;
+                self.input_from_model(x)
+            }
+        }
+    }
+
+    // Output change per unit of input around x, in session units
+    pub fn slope_at(&self, x: f64) -> Option<f64> {
+        self.slope_with(&self.model, x)
+    }
+
+    fn slope_with(&self, model: &Result<Model, CalcError>, x: f64) -> Option<f64> {
+        if let Ok(Some((m, _))) = model.as_ref().map(|m| m.get_line()) {
+            return Some(m);
+        }
+        let h = (x.abs() * 1e-6).max(1e-9);
+        let hi = self
+            .convert_with(model, &MeasurementType::Input(x + h))
+            .ok()?;
+        let lo = self
+            .convert_with(model, &MeasurementType::Input(x - h))
+            .ok()?;
+        Some((hi - lo) / (2.0 * h))
+    }
+
+    /*
+     -=-=-=-=-=-  Getters and Setters -=-=-=-=-=-=-
+    */
+    // Get vector slice for plot
+    pub fn get_plot_data(&self) -> &Vec<(f64, f64)> {
+        self.plot.as_ref()
+    }
+
+    pub fn get_test_series(&self) -> Option<&[(f64, f64); 1]> {
+        self.test_point.as_ref()
+    }
+
+    // Calibration points as (input, output) for the chart
+    pub fn get_point_series(&self) -> Vec<(f64, f64)> {
+        self.points
+            .iter()
+            .filter(|p| p.is_valid())
+            .map(|p| {
+                let vals = p.get_val();
+                (vals["in"], vals["out"])
+            })
+            .collect()
+    }
+
+    // Function to return bounds structs for the x and y axes, covering the curve, the points and the test point
+    /*
+     * labels: Vec<String>,
+     * bounds: (f64,f64),
+     */
+    pub fn get_bounds(&self) -> (Bounds, Bounds) {
+        let mut all = self.plot.clone();
+        all.extend(self.get_point_series());
+        for edge in &self.bands.prediction {
+            all.extend(edge);
+        }
+        all.extend(self.get_other_stage_plot());
+        if let Some(test) = self.test_point {
+            all.extend(test);
+        }
+        bounds_of(&all)
+    }
+
+    // Bounds of the verification chart, the error bars and the tolerance band
+    pub fn get_error_bounds(&self) -> (Bounds, Bounds) {
+        let mut all: Vec<(f64, f64)> = self
+            .get_error_bars()
+            .into_iter()
+            .flat_map(|(bar, _)| bar)
+            .collect();
+        for edge in self.get_tolerance_envelope() {
+            all.extend(edge);
+        }
+        bounds_of(&all)
+    }
+
+    // Coefficients of the fitted model, or why there aren't any
+    pub fn get_line_val(&self) -> String {
+        match &self.model {
+            Ok(model) => match (model.get_line(), self.covariance.map(|c| c.uncertainties())) {
+                (Some((m, b)), Some([um, ub])) => format!(
+                    "Slope: {:.4} ± {:.4}{}\nIntercept: {:.4} ± {}",
+                    m,
+                    um,
+                    slope_unit(self.input.unit, self.output.unit),
+                    b,
+                    units::format_value(ub, self.output.unit)
+                ),
+                (Some((m, b)), None) => format!(
+                    "Slope: {:.4}{} Intercept: {}",
+                    m,
+                    slope_unit(self.input.unit, self.output.unit),
+                    units::format_value(b, self.output.unit)
+                ),
+                (None, Some([ua, ub])) => {
+                    format!("{}\nu(a): {:.3e} u(b): {:.3e}", model.describe(), ua, ub)
+                }
+                (None, None) => model.describe(),
+            },
+            Err(e) => format!("Unable to calculate {}: {}", self.model_kind.name(), e),
+        }
+    }
+
+    // How an errors-in-variables line compares with ordinary least squares: the OLS line and the
+    // largest difference between the two over the points, in output units
+    pub fn get_ols_comparison(&self) -> Option<String> {
+        let (m, b) = self.ols_line?;
+        let (fm, fb) = self.model.as_ref().ok()?.get_line()?;
+        let diff = self
+            .get_point_series()
+            .iter()
+            .map(|(x, _)| (fm * x + fb) - (m * x + b))
+            .fold(0.0, |acc: f64, d| if d.abs() > acc.abs() { d } else { acc });
+        Some(format!(
+            "OLS: {:.4}{} {:+.4} | max diff {}",
+            m,
+            slope_unit(self.input.unit, self.output.unit),
+            b,
+            units::format_value(diff, self.output.unit)
+        ))
+    }
+
+    // Whether each point looks like an outlier, aligned with the point list
+    pub fn get_outliers(&self) -> &[bool] {
+        &self.outliers
+    }
+
+    // Points flagged as outliers and points left out of the fit, for the chart
+    pub fn get_flagged_series(&self) -> Vec<(f64, f64)> {
+        self.series_where(|i, p| !p.is_excluded() && self.outliers.get(i) == Some(&true))
+    }
+
+    pub fn get_excluded_series(&self) -> Vec<(f64, f64)> {
+        self.series_where(|_, p| p.is_excluded())
+    }
+
+    fn series_where(&self, keep: impl Fn(usize, &Point) -> bool) -> Vec<(f64, f64)> {
+        self.points
+            .iter()
+            .enumerate()
+            .filter(|(i, p)| p.is_valid() && keep(*i, p))
+            .map(|(_, p)| {
+                let vals = p.get_val();
+                (vals["in"], vals["out"])
+            })
+            .collect()
+    }
+
+    pub fn get_bands(&self) -> &Bands {
+        &self.bands
+    }
+
+    pub fn get_confidence(&self) -> f64 {
+        self.confidence
+    }
+
+    pub fn set_confidence(&mut self, level: f64) {
+        self.confidence = level;
+        self.update_vector();
+    }
+
+    pub fn get_contributors(&self) -> &[Contributor] {
+        &self.contributors
+    }
+
+    pub fn get_budget_selected(&self) -> usize {
+        self.budget_selected
+    }
+
+    pub fn set_contributors(&mut self, contributors: Vec<Contributor>) {
+        self.contributors = contributors;
+        self.budget_selected = 0;
+    }
+
+    /*
+     * The uncertainty budget in output units at the working point. Input contributors are carried
+     * through the model's slope there, output ones count as they are. Fits with a covariance add the
+     * standard deviation of a reading about the fit (the prediction band) as the fit residual line
+     */
+    pub fn get_budget(&self) -> Option<Budget> {
+        let at = self.working_point();
+        let slope = self.slope_at(at)?;
+        let terms: Vec<Term> = self
+            .contributors
+            .iter()
+            .map(|c| Term {
+                standard: c.standard(),
+                sensitivity: match c.side {
+                    Side::Input => slope,
+                    Side::Output => 1.0,
+                },
+                dof: c.dof,
+            })
+            .collect();
+        let fit = match (self.model.as_ref(), self.covariance.as_ref()) {
+            (Ok(model), Some(cov)) => model.band(cov, at).map(|(_, reading)| Term {
+                standard: reading,
+                sensitivity: 1.0,
+                dof: cov.dof.map(|dof| dof as f64),
+            }),
+            _ => None,
+        };
+        let mut all = terms.clone();
+        all.extend(fit);
+        Some(Budget {
+            at,
+            terms,
+            fit,
+            combined: budget::combine(&all),
+        })
+    }
+
+    // Add a budget line after the selected one
+    pub fn add_contributor(&mut self) {
+        let at = (self.budget_selected + 1).min(self.contributors.len());
+        self.contributors.insert(
+            at,
+            Contributor::new("New contributor", Side::Output, Distribution::Normal(1.0)),
+        );
+        self.budget_selected = at;
+    }
+
+    pub fn remove_contributor(&mut self) {
+        if self.budget_selected < self.contributors.len() {
+            self.contributors.remove(self.budget_selected);
+            self.budget_selected = self
+                .budget_selected
+                .min(self.contributors.len().saturating_sub(1));
+        }
+    }
+
+    pub fn get_verification_points(&self) -> &[Point] {
+        &self.verification
+    }
+
+    pub fn get_verify_selected(&self) -> usize {
+        self.verify_selected
+    }
+
+    pub fn set_verification_points(&mut self, points: Vec<Point>) {
+        self.verification = points;
+        self.verify_selected = 0;
+    }
+
+    pub fn get_tolerance(&self) -> Option<&Tolerance> {
+        self.tolerance.as_ref()
+    }
+
+    pub fn set_tolerance(&mut self, tolerance: Option<Tolerance>) {
+        self.tolerance = tolerance;
+    }
+
+    // Output span of the calibration, what percentages of full scale are taken of
+    fn full_scale(&self) -> f64 {
+        let outputs = self.get_point_series().into_iter().map(|p| p.1);
+        let (lo, hi) = outputs.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), y| {
+            (lo.min(y), hi.max(y))
+        });
+        if hi >= lo {
+            hi - lo
+        } else {
+            0.0
+        }
+    }
+
+    // Each verification reading run through the calibration and held to the tolerance, None for
+    // readings that are incomplete or that the calibration can't convert
+    pub fn get_verification(&self) -> Vec<Option<Check>> {
+        let full_scale = self.full_scale();
+        self.verification
+            .iter()
+            .map(|p| {
+                if !p.is_valid() {
+                    return None;
+                }
+                let vals = p.get_val();
+                let (measured, nominal) = (vals["in"], vals["out"]);
+                let indicated = self.convert(&MeasurementType::Input(measured)).ok()?;
+                Some(Check {
+                    nominal,
+                    indicated,
+                    limit: self
+                        .tolerance
+                        .map(|t| t.limit(nominal, full_scale))
+                        .unwrap_or(f64::NAN),
+                })
+            })
+            .collect()
+    }
+
+    // Error of each checked reading as a bar up from 0 at its nominal value, and whether it passed
+    pub fn get_error_bars(&self) -> Vec<([(f64, f64); 2], bool)> {
+        self.get_verification()
+            .into_iter()
+            .flatten()
+            .map(|c| ([(c.nominal, 0.0), (c.nominal, c.error())], c.passed()))
+            .collect()
+    }
+
+    // Lower and upper edges of the tolerance band over the nominal values, as (nominal, error)
+    pub fn get_tolerance_envelope(&self) -> [Vec<(f64, f64)>; 2] {
+        let (Some(tolerance), Some((start, end))) = (self.tolerance, self.verification_range())
+        else {
+            return Default::default();
+        };
+        let full_scale = self.full_scale();
+        let mut envelope: [Vec<(f64, f64)>; 2] = Default::default();
+        for i in 0..CURVE_SAMPLES {
+            let x = start + (end - start) * i as f64 / (CURVE_SAMPLES - 1) as f64;
+            let limit = tolerance.limit(x, full_scale);
+            envelope[0].push((x, -limit));
+            envelope[1].push((x, limit));
+        }
+        envelope
+    }
+
+    // Nominal values the verification covers
+    fn verification_range(&self) -> Option<(f64, f64)> {
+        let nominals: Vec<f64> = self
+            .verification
+            .iter()
+            .filter(|p| p.is_valid())
+            .map(|p| p.get_val()["out"])
+            .collect();
+        let lo = nominals.iter().cloned().reduce(f64::min)?;
+        let hi = nominals.iter().cloned().reduce(f64::max)?;
+        Some((lo, hi))
+    }
+
+    // Add a verification reading after the selected one, measured where the calibration expects it
+    pub fn add_verification(&mut self) {
+        // the first starts at the bottom of the calibration
+        let x = if self.verification.is_empty() {
+            self.get_point_series()
+                .iter()
+                .map(|p| p.0)
+                .reduce(f64::min)
+                .unwrap_or(0.0)
+        } else {
+            next_input(&self.verification, self.verify_selected)
+        };
+        let y = self.convert(&MeasurementType::Input(x)).unwrap_or(0.0);
+        let at = (self.verify_selected + 1).min(self.verification.len());
+        self.verification.insert(at, Point::from((x, y)));
+        self.verify_selected = at;
+    }
+
+    pub fn remove_verification(&mut self) {
+        if self.verify_selected < self.verification.len() {
+            self.verification.remove(self.verify_selected);
+            self.verify_selected = self
+                .verify_selected
+                .min(self.verification.len().saturating_sub(1));
+        }
+    }
+
+    pub fn get_stage(&self) -> Option<Stage> {
+        self.stages.as_ref().map(|s| s.live)
+    }
+
+    // Points of the stage that isn't being edited
+    pub fn get_other_stage_points(&self) -> Option<&[Point]> {
+        self.stages.as_ref().map(|s| s.other_points.as_slice())
+    }
+
+    // Replace the points, with the live stage and the shelved stage's points when there are stages
+    pub fn set_points(&mut self, points: Vec<Point>, stages: Option<(Stage, Vec<Point>)>) {
+        self.points = points;
+        self.selected = 0;
+        self.stages = stages.map(|(live, other_points)| Stages {
+            live,
+            other_points,
+            other_model: Err(CalcError::Singular),
+        });
+        // refits the shelved stage as well
+        self.update_model();
+        self.update_test_point();
+    }
+
+    // A channel set up like this one, with a new budget and today's date. Its points are left to fill
+    // in, other than the range values of loop scaling
+    pub fn blank(&self) -> Channel {
+        let mut channel = Channel::new(self.input.clone(), self.output.clone());
+        channel.points = vec![Point::new(), Point::new()];
+        channel.model_kind = self.model_kind;
+        channel.fit_options = self.fit_options;
+        channel.plc_raw = self.plc_raw;
+        channel.adc = self.adc;
+        channel.dac = self.dac;
+        channel.divider = self.divider;
+        channel.cold_junction = self.cold_junction;
+        channel.confidence = self.confidence;
+        channel.tolerance = self.tolerance;
+        // puts the range values in as points and refits
+        channel.set_loop_scaling(self.loop_scaling);
+        channel.update_model();
+        channel
+    }
+
+    /*
+     * Step through the as-found / as-left workflow. The first time the points are recorded as found
+     * and copied to start the as-left stage, after that the two stages swap so either can be edited.
+     * Loop scaling keeps its own two points so it has no stages
+     */
+    pub fn toggle_stage(&mut self) {
+        if self.loop_scaling.is_some() {
+            return;
+        }
+        match self.stages.as_mut() {
+            None => {
+                let mut details = FitDetails::new(self.points.len());
+                self.stages = Some(Stages {
+                    live: Stage::AsLeft,
+                    other_points: self.points.clone(),
+                    other_model: self.fit_model(&self.points, &mut details),
+                });
+            }
+            Some(stages) => {
+                std::mem::swap(&mut stages.other_points, &mut self.points);
+                stages.live = match stages.live {
+                    Stage::AsFound => Stage::AsLeft,
+                    Stage::AsLeft => Stage::AsFound,
+                };
+                self.selected = self.selected.min(self.points.len().saturating_sub(1));
+                self.update_model();
+                self.update_test_point();
+            }
+        }
+    }
+
+    // The shelved stage's curve over the live points' inputs, to draw behind the live one
+    pub fn get_other_stage_plot(&self) -> Vec<(f64, f64)> {
+        let (Some(stages), Some(first), Some(last)) =
+            (self.stages.as_ref(), self.plot.first(), self.plot.last())
+        else {
+            return Vec::new();
+        };
+        (0..CURVE_SAMPLES)
+            .filter_map(|i| {
+                let x = first.0 + (last.0 - first.0) * i as f64 / (CURVE_SAMPLES - 1) as f64;
+                let y = self.convert_with(&stages.other_model, &MeasurementType::Input(x));
+                y.ok().map(|y| (x, y))
+            })
+            .collect()
+    }
+
+    pub fn get_stage_comparison(&self) -> Option<StageComparison> {
+        let stages = self.stages.as_ref()?;
+        let live = (&self.points, &self.model);
+        let other = (&stages.other_points, &stages.other_model);
+        let [found, left] = match stages.live {
+            Stage::AsFound => [live, other],
+            Stage::AsLeft => [other, live],
+        };
+        // each reading through its own stage's calibration
+        let errors = |(points, model): (&Vec<Point>, &Result<Model, CalcError>)| {
+            points
+                .iter()
+                .map(|p| {
+                    if !p.is_valid() {
+                        return None;
+                    }
+                    let vals = p.get_val();
+                    let (x, y) = (vals["in"], vals["out"]);
+                    let indicated = self.convert_with(model, &MeasurementType::Input(x)).ok()?;
+                    Some((x, y, indicated - y))
+                })
+                .collect::<Vec<_>>()
+        };
+        let (found_errors, left_errors) = (errors(found), errors(left));
+        let rows = (0..found_errors.len().max(left_errors.len()))
+            .map(|i| {
+                let f = found_errors.get(i).cloned().flatten();
+                let l = left_errors.get(i).cloned().flatten();
+                StageRow {
+                    nominal: l.or(f).map_or(f64::NAN, |(_, y, _)| y),
+                    found: f.map(|(x, _, e)| (x, e)),
+                    left: l.map(|(x, _, e)| (x, e)),
+                }
+            })
+            .collect();
+        let line = |model: &Result<Model, CalcError>| model.as_ref().ok()?.get_line();
+        let at = self.working_point();
+        let output = |model| self.convert_with(model, &MeasurementType::Input(at)).ok();
+        Some(StageComparison {
+            lines: line(found.1).zip(line(left.1)).map(|(f, l)| [f, l]),
+            at,
+            slopes: [self.slope_with(found.1, at), self.slope_with(left.1, at)],
+            outputs: [output(found.1), output(left.1)],
+            rows,
+        })
+    }
+
+    // Expanded uncertainty (k = 2) of the tester's converted value as a new reading on this
+    // calibration: output units when the tester holds an input, input units the other way round
+    pub fn get_test_uncertainty(&self) -> Option<f64> {
+        let model = self.model.as_ref().ok()?;
+        let [(x, _)] = self.test_point?;
+        let (_, reading) = model.band(self.covariance.as_ref()?, x)?;
+        match self.testing_value.as_ref()? {
+            MeasurementType::Input(_) => Some(2.0 * reading),
+            MeasurementType::Output(_) => Some(2.0 * reading / self.slope_at(x)?.abs()),
+        }
+    }
+
+    // Relative weight of each point in the fit, aligned with the point list
+    pub fn get_weights(&self) -> &[Option<f64>] {
+        &self.weights
+    }
+
+    // Largest difference between a fitted RTD and the IEC 60751 curve of its nominal probe over
+    // the points, as (probe name, deviation in output units)
+    pub fn get_standard_deviation(&self) -> Option<(String, f64)> {
+        let cvd = match self.model.as_ref() {
+            Ok(Model::CallendarVanDusen(cvd)) => cvd,
+            _ => return None,
+        };
+        let standard = cvd.nominal();
+        let to_output = |t: f64| Unit::Celsius.convert(t, self.output.unit).ok();
+        self.points
+            .iter()
+            .filter(|p| p.is_valid() && !p.is_excluded())
+            .filter_map(|p| {
+                let ohms = self.to_model_input(p.get_val()["in"]).ok()?;
+                let t = standard.temperature(ohms).ok()?;
+                let dev = cvd.deviation(&standard, ohms).ok()?;
+                // a difference, so only the scale of the output unit applies
+                Some(to_output(t + dev)? - to_output(t)?)
+            })
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .map(|dev| (format!("PT{}", standard.r0), dev))
+    }
+
+    //get Point values
+    pub fn get_points(&self) -> &[Point] {
+        &self.points
+    }
+
+    pub fn get_selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn get_model_kind(&self) -> ModelKind {
+        self.model_kind
+    }
+
+    // The fitted model in its own units, or why there isn't one
+    pub fn get_model(&self) -> Result<&Model, &CalcError> {
+        self.model.as_ref()
+    }
+
+    pub fn get_fit_options(&self) -> &FitOptions {
+        &self.fit_options
+    }
+
+    pub fn set_fit_options(&mut self, options: FitOptions) {
+        self.fit_options = options;
+        self.update_model();
+        self.update_test_point();
+    }
+
+    pub fn get_divider(&self) -> Option<&Divider> {
+        self.divider.as_ref()
+    }
+
+    pub fn set_divider(&mut self, divider: Option<Divider>) {
+        self.divider = divider;
+        self.update_model();
+    }
+
+    // Pick the model to fit. Thermistor models move the quantities over to resistance (or the
+    // divider voltage) and temperature; the values are kept so they can be fixed up by hand
+    pub fn set_model_kind(&mut self, kind: ModelKind) {
+        if let Some((unit, _)) = kind.units() {
+            self.loop_scaling = None;
+            let input = match (unit, self.divider) {
+                (Unit::Ohm, Some(_)) => Unit::Volt,
+                _ => unit,
+            };
+            if self.input.unit.dimension() != input.dimension() {
+                self.input = Quantity::from_unit(input);
+            }
+            if self.output.unit.dimension() != Dimension::Temperature {
+                self.output = Quantity::from_unit(Unit::Celsius);
+            }
+        }
+        self.model_kind = kind;
+        self.update_model();
+        self.update_test_point();
+    }
+
+    pub fn get_cold_junction(&self) -> Option<f64> {
+        match self.model_kind {
+            ModelKind::Thermocouple(_) => Some(self.cold_junction),
+            _ => None,
+        }
+    }
+
+    // Unit the cold junction is shown and typed in: the output's when that is a temperature
+    pub fn cold_junction_unit(&self) -> Unit {
+        match self.output.unit.dimension() {
+            Dimension::Temperature => self.output.unit,
+            _ => Unit::Celsius,
+        }
+    }
+
+    pub fn set_cold_junction(&mut self, celsius: f64) {
+        self.cold_junction = celsius;
+        self.update_model();
+        self.update_test_point();
+    }
+
+    pub fn get_metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = metadata;
+    }
+
+    pub fn get_metadata_selected(&self) -> usize {
+        self.metadata_selected
+    }
+
+    // The sensor's calibrations oldest first, with this session in place of its saved copy
+    pub fn get_history(&self) -> Vec<Entry> {
+        let date = self.metadata.get(Field::Date);
+        let mut entries: Vec<Entry> = self
+            .history
+            .iter()
+            .filter(|e| e.date != date)
+            .cloned()
+            .collect();
+        let day = metadata::day_of(date);
+        if let (Some((slope, intercept)), Some(day)) = (self.get_line(), day) {
+            entries.push(Entry {
+                date: date.to_owned(),
+                calibration: drift::Calibration {
+                    day,
+                    slope,
+                    intercept,
+                },
+            });
+        }
+        entries.sort_by(|a, b| a.calibration.day.total_cmp(&b.calibration.day));
+        entries
+    }
+
+    pub fn get_drift(&self) -> Option<Drift> {
+        let history: Vec<_> = self.get_history().iter().map(|e| e.calibration).collect();
+        drift::trend(&history)
+    }
+
+    // Load a saved session to compare against, in this session's units
+    pub fn load_comparison(&mut self, path: &Path) -> Result<(), String> {
+        let mut other = session::load(path, &[])
+            .map_err(|e| e.to_string())?
+            .into_channel();
+        let (input, output) = (self.input.unit, self.output.unit);
+        if other.input.unit.dimension() != input.dimension()
+            || other.output.unit.dimension() != output.dimension()
+        {
+            return Err(format!(
+                "{} doesn't convert {} to {}",
+                path.display(),
+                self.input.name,
+                self.output.name
+            ));
+        }
+        other.set_units(input, output);
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let label = match other.metadata.get(Field::Date) {
+            "" => name,
+            date => format!("{} ({})", name, date),
+        };
+        self.compared = Some(Compared {
+            label,
+            channel: Box::new(other),
+        });
+        Ok(())
+    }
+
+    pub fn get_comparison(&self) -> Option<CalibrationDifference> {
+        let compared = self.compared.as_ref()?;
+        let (first, last) = (self.plot.first()?, self.plot.last()?);
+        let mut other_plot = Vec::new();
+        let mut difference = Vec::new();
+        for i in 0..CURVE_SAMPLES {
+            let x = first.0 + (last.0 - first.0) * i as f64 / (CURVE_SAMPLES - 1) as f64;
+            let Ok(before) = compared.channel.convert(&MeasurementType::Input(x)) else {
+                continue;
+            };
+            other_plot.push((x, before));
+            if let Ok(now) = self.convert(&MeasurementType::Input(x)) {
+                difference.push((x, now - before));
+            }
+        }
+        let max = difference
+            .iter()
+            .cloned()
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()));
+        Some(CalibrationDifference {
+            label: compared.label.clone(),
+            other_plot,
+            difference,
+            max,
+        })
+    }
+
+    /*
+     * Carry the drift on from the latest calibration until the output at either end of the
+     * calibrated range has moved by the tolerance there, and take whichever end gets there first
+     */
+    pub fn get_drift_forecast(&self) -> Option<DriftForecast> {
+        let drift = self.get_drift()?;
+        let tolerance = self.tolerance?;
+        let last = self.get_history().last()?.calibration;
+        let full_scale = self.full_scale();
+        let inputs = self.get_point_series().into_iter().map(|p| p.0);
+        let (lo, hi) = inputs.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| {
+            (lo.min(x), hi.max(x))
+        });
+        [lo, hi]
+            .into_iter()
+            .filter(|x| x.is_finite())
+            .filter_map(|x| {
+                let limit = tolerance.limit(last.slope * x + last.intercept, full_scale);
+                drift.days_to(x, limit).map(|days| DriftForecast {
+                    at: x,
+                    limit,
+                    day: last.day + days,
+                })
+            })
+            .min_by(|a, b| a.day.total_cmp(&b.day))
+    }
+
+    pub fn get_input(&self) -> &Quantity {
+        &self.input
+    }
+
+    pub fn get_output(&self) -> &Quantity {
+        &self.output
+    }
+
+    // (slope, intercept) when the model is a line
+    pub fn get_line(&self) -> Option<(f64, f64)> {
+        self.model.as_ref().ok()?.get_line()
+    }
+
+    // The calibration written out with the session's quantity names, e.g. "Pressure = 20.0000 bar/V × Voltage + 0.0000 bar"
+    pub fn get_equation(&self) -> String {
+        let Some(stages) = self.stages.as_ref() else {
+            return self.equation_of(&self.model);
+        };
+        let (found, left) = match stages.live {
+            Stage::AsFound => (&self.model, &stages.other_model),
+            Stage::AsLeft => (&stages.other_model, &self.model),
+        };
+        let mut s = format!(
+            "As-found: {}\nAs-left:  {}",
+            self.equation_of(found),
+            self.equation_of(left)
+        );
+        if let Some([(m0, b0), (m1, b1)]) = self.get_stage_comparison().and_then(|c| c.lines) {
+            s += &format!(
+                "\nChange:   slope {:+.4}{}, offset {}",
+                m1 - m0,
+                slope_unit(self.input.unit, self.output.unit),
+                units::format_value(b1 - b0, self.output.unit)
+            );
+        }
+        s
+    }
+
+    fn equation_of(&self, model: &Result<Model, CalcError>) -> String {
+        match model {
+            Ok(model) => match model.get_line() {
+                Some((m, b)) => format!(
+                    "{} = {:.4}{} × {} + {}",
+                    self.output.name,
+                    m,
+                    slope_unit(self.input.unit, self.output.unit),
+                    self.input.name,
+                    units::format_value(b, self.output.unit)
+                ),
+                None => format!(
+                    "{} = {}({}), {}",
+                    self.output.name,
+                    model.kind().name(),
+                    self.input.name,
+                    model.describe()
+                ),
+            },
+            Err(_) => self.get_line_val(),
+        }
+    }
+
+    // Change what the input measures. Values are kept as they are since there is nothing to convert between
+    pub fn set_input_quantity(&mut self, input: Quantity) {
+        // loop scaling only makes sense for a current input
+        if input.unit.dimension() != Dimension::Current {
+            self.loop_scaling = None;
+        }
+        self.input = input;
+    }
+
+    pub fn get_loop_scaling(&self) -> Option<&LoopScaling> {
+        self.loop_scaling.as_ref()
+    }
+
+    // Turn 4-20 mA loop scaling on or off. While it is on there are just the LRV and URV
+    // points, a linear model, and only the point outputs can be edited
+    pub fn set_loop_scaling(&mut self, scaling: Option<LoopScaling>) {
+        if let Some(ls) = scaling.as_ref() {
+            if self.input.unit.dimension() != Dimension::Current {
+                self.input = Quantity::from_unit(Unit::Milliamp);
+            }
+            let (p1, p2) = ls.points(self.input.unit);
+            self.points = vec![p1, p2];
+            self.selected = self.selected.min(1);
+            self.model_kind = ModelKind::Linear;
+            self.update_model();
+            self.update_test_point();
+        }
+        self.loop_scaling = scaling;
+    }
+
+    // Toggle loop scaling, keeping the current calibration if it already has a current input
+    pub fn toggle_loop_scaling(&mut self) {
+        if self.loop_scaling.is_some() {
+            self.set_loop_scaling(None);
+            return;
+        }
+        let mut scaling = LoopScaling::new(0.0, 100.0);
+        if let Dimension::Current = self.input.unit.dimension() {
+            let at = |ma: f64| {
+                let x = Unit::Milliamp.convert(ma, self.input.unit).unwrap_or(ma);
+                self.convert(&MeasurementType::Input(x))
+            };
+            if let (Ok(lrv), Ok(urv)) = (at(LoopScaling::LOW_MA), at(LoopScaling::HIGH_MA)) {
+                scaling = LoopScaling::new(lrv, urv);
+            }
+        }
+        self.set_loop_scaling(Some(scaling));
+    }
+
+    pub fn get_plc_raw(&self) -> Option<(f64, f64)> {
+        self.plc_raw
+    }
+
+    pub fn set_plc_raw(&mut self, range: Option<(f64, f64)>) {
+        self.plc_raw = range;
+    }
+
+    // The line as a PLC analog input block is set up, over the chosen raw range
+    pub fn get_plc_scaling(&self) -> Option<PlcScaling> {
+        let Ok(Model::Linear(line)) = self.model.as_ref() else {
+            return None;
+        };
+        let range = self.plc_raw.or_else(|| {
+            let inputs = self.get_point_series().into_iter().map(|p| p.0);
+            let lo = inputs.clone().reduce(f64::min)?;
+            let hi = inputs.reduce(f64::max)?;
+            (lo < hi).then_some((lo, hi))
+        })?;
+        PlcScaling::from_line(line, range)
+    }
+
+    // The Results panel's line for the PLC scaling
+    pub fn get_plc_val(&self) -> Option<String> {
+        let s = self.get_plc_scaling()?;
+        Some(format!(
+            "PLC raw {} to {} → eng {} to {}",
+            units::format_value(s.raw_min, self.input.unit),
+            units::format_value(s.raw_max, self.input.unit),
+            units::format_value(s.eng_min, self.output.unit),
+            units::format_value(s.eng_max, self.output.unit)
+        ))
+    }
+
+    // Take the line from a PLC's scaling: its two ends become the points of a linear calibration
+    pub fn import_plc_scaling(&mut self, scaling: PlcScaling) -> Result<(), String> {
+        if scaling.line().get_val().is_none() {
+            return Err("the PLC's raw min and max are the same".to_owned());
+        }
+        let (p1, p2) = scaling.points();
+        self.loop_scaling = None;
+        self.points = vec![p1, p2];
+        self.selected = self.selected.min(1);
+        self.plc_raw = Some((scaling.raw_min, scaling.raw_max));
+        self.model_kind = ModelKind::Linear;
+        self.update_model();
+        self.update_test_point();
+        Ok(())
+    }
+
+    pub fn get_adc(&self) -> Option<&Adc> {
+        self.adc.as_ref()
+    }
+
+    pub fn set_adc(&mut self, adc: Option<Adc>) {
+        self.adc = adc;
+    }
+
+    pub fn get_dac(&self) -> Option<&Dac> {
+        self.dac.as_ref()
+    }
+
+    pub fn set_dac(&mut self, dac: Option<Dac>) {
+        self.dac = dac;
+    }
+
+    // The code to command for the tester's output and what it really gives, in an output calibration
+    pub fn get_dac_command(&self) -> Option<DacCommand> {
+        let dac = self.dac.as_ref()?;
+        let [(ideal, wanted)] = self.test_point?;
+        let code = dac.quantize(ideal);
+        let output = self.convert(&MeasurementType::Input(code)).ok()?;
+        Some(DacCommand {
+            ideal,
+            code,
+            output,
+            error: output - wanted,
+        })
+    }
+
+    // With an ADC configured switch the input between codes and volts, converting the points
+    pub fn toggle_adc_input(&mut self) {
+        if self.adc.is_none() {
+            return;
+        }
+        match self.input.unit.dimension() {
+            Dimension::Counts => self.set_units(Unit::Volt, self.output.unit),
+            Dimension::Voltage => self.set_units(Unit::Count, self.output.unit),
+            _ => {}
+        }
+    }
+
+    // Parse a typed input value. With an ADC, volts can be typed into a counts session and the other way round
+    pub fn parse_input(&self, s: &str) -> Result<f64, UnitError> {
+        match (units::split_value(s)?, self.adc.as_ref()) {
+            ((value, Some(unit)), Some(adc)) => match adc.convert(value, unit, self.input.unit) {
+                Some(converted) => Ok(converted),
+                None => unit.convert(value, self.input.unit),
+            },
+            ((value, Some(unit)), None) => unit.convert(value, self.input.unit),
+            ((value, None), _) => Ok(value),
+        }
+    }
+
+    // Input that curves are linearised at: the test point, or the middle of the points without one
+    fn working_point(&self) -> f64 {
+        match self.test_point {
+            Some([(x, _)]) => x,
+            None => {
+                let series = self.get_point_series();
+                series.iter().map(|p| p.0).sum::<f64>() / series.len().max(1) as f64
+            }
+        }
+    }
+
+    // Resolution per LSB and the quantization band around the tester's conversion
+    pub fn get_quantization(&self) -> Option<Quantization> {
+        let adc = self.adc.as_ref()?;
+        let x = self.working_point();
+        let m = self.slope_at(x)?;
+        // one code expressed in the input unit
+        let lsb = match self.input.unit {
+            Unit::Count => 1.0,
+            unit => Unit::Volt.convert(adc.lsb(), unit).ok()?,
+        };
+        let resolution = (m * lsb).abs();
+        let mut quantization = Quantization {
+            resolution,
+            code: None,
+            band: None,
+        };
+        if let Some([(x, y)]) = self.test_point {
+            let volts = adc.convert(x, self.input.unit, Unit::Volt)?;
+            quantization.code = Some(adc.quantize(volts));
+            quantization.band = Some((y - resolution / 2.0, y + resolution / 2.0));
+        }
+        Some(quantization)
+    }
+
+    // NAMUR NE43 status of the tester's loop current, if we are loop scaling and have one
+    pub fn get_loop_status(&self) -> Option<LoopStatus> {
+        self.loop_scaling.as_ref()?;
+        let (x, _) = self.test_point?[0];
+        let ma = self.input.unit.convert(x, Unit::Milliamp).ok()?;
+        Some(LoopScaling::status(ma))
+    }
+
+    // Switch the session units. Points and the test value are converted so they still describe the same thing
+    pub fn set_units(&mut self, input_unit: Unit, output_unit: Unit) {
+        let (from_in, from_out) = (self.input.unit, self.output.unit);
+        let convert = |v: f64, from: Unit, to: Unit| from.convert(v, to).unwrap_or(v);
+        let adc = self.adc;
+        let convert_in = |v: f64| match adc.and_then(|a| a.convert(v, from_in, input_unit)) {
+            Some(converted) => converted,
+            None => convert(v, from_in, input_unit),
+        };
+        let shelved = self
+            .stages
+            .iter_mut()
+            .flat_map(|s| s.other_points.iter_mut());
+        for p in self
+            .points
+            .iter_mut()
+            .chain(self.verification.iter_mut())
+            .chain(shelved)
+        {
+            if p.is_valid() {
+                let vals = p.get_val();
+                let (x, y) = (vals["in"], vals["out"]);
+                // uncertainties are differences, so they follow the conversion's scale at the point
+                let (ux, uy) = p.get_uncertainty();
+                p.set_input_uncertainty(ux.map(|u| convert_in(x + u) - convert_in(x)));
+                p.set_output_uncertainty(uy.map(|u| {
+                    convert(y + u, from_out, output_unit) - convert(y, from_out, output_unit)
+                }));
+                p.set_point(convert_in(x), convert(y, from_out, output_unit));
+            }
+        }
+        // budget values are differences too, taken at the working point
+        let x = self.working_point();
+        let y = self.convert(&MeasurementType::Input(x)).unwrap_or(0.0);
+        for c in self.contributors.iter_mut() {
+            c.value = match c.side {
+                Side::Input => (convert_in(x + c.value) - convert_in(x)).abs(),
+                Side::Output => (convert(y + c.value, from_out, output_unit)
+                    - convert(y, from_out, output_unit))
+                .abs(),
+            };
+        }
+        if let Some(t) = self.tolerance.as_mut() {
+            t.absolute = (convert(y + t.absolute, from_out, output_unit)
+                - convert(y, from_out, output_unit))
+            .abs();
+        }
+        // the loop's range values are outputs, the PLC's raw range is inputs
+        if let Some(ls) = self.loop_scaling.as_mut() {
+            ls.lrv = convert(ls.lrv, from_out, output_unit);
+            ls.urv = convert(ls.urv, from_out, output_unit);
+        }
+        self.plc_raw = self
+            .plc_raw
+            .map(|(lo, hi)| (convert_in(lo), convert_in(hi)));
+        if let Some(compared) = self.compared.as_mut() {
+            compared.channel.set_units(input_unit, output_unit);
+        }
+        // the history's lines go through their points at inputs 0 and 1
+        let (x0, x1) = (convert_in(0.0), convert_in(1.0));
+        for c in self.history.iter_mut().map(|e| &mut e.calibration) {
+            let y0 = convert(c.intercept, from_out, output_unit);
+            let y1 = convert(c.slope + c.intercept, from_out, output_unit);
+            c.slope = (y1 - y0) / (x1 - x0);
+            c.intercept = y0 - c.slope * x0;
+        }
+        self.testing_value = match self.testing_value.take() {
+            Some(MeasurementType::Input(v)) => Some(MeasurementType::Input(convert_in(v))),
+            Some(MeasurementType::Output(p)) => {
+                Some(MeasurementType::Output(convert(p, from_out, output_unit)))
+            }
+            None => None,
+        };
+        // keep default names in step when the ADC swaps the input between volts and counts
+        if self.input.name == from_in.dimension().default_name() {
+            self.input.name = input_unit.dimension().default_name().to_owned();
+        }
+        self.input.unit = input_unit;
+        self.output.unit = output_unit;
+        self.update_model();
+        self.update_test_point();
+    }
+
+    // Add a point after the selected one, placed on the current curve so the fit doesn't jump
+    pub fn add_point(&mut self) {
+        if self.loop_scaling.is_some() {
+            return;
+        }
+        let x = next_input(&self.points, self.selected);
+        let y = self.convert(&MeasurementType::Input(x)).unwrap_or(0.0);
+        let at = (self.selected + 1).min(self.points.len());
+        self.points.insert(at, Point::from((x, y)));
+        self.selected = at;
+        self.update_model();
+        self.update_test_point();
+    }
+
+    // Remove the selected point, always keeping two
+    pub fn remove_point(&mut self) {
+        if self.loop_scaling.is_some() || self.points.len() <= 2 {
+            return;
+        }
+        self.points.remove(self.selected);
+        self.selected = self.selected.min(self.points.len() - 1);
+        self.update_model();
+        self.update_test_point();
+    }
+
+    // Leave the selected point out of the fit, or put it back. The loop range points always count
+    pub fn toggle_excluded(&mut self) {
+        if self.loop_scaling.is_some() {
+            return;
+        }
+        if let Some(p) = self.points.get_mut(self.selected) {
+            p.set_excluded(!p.is_excluded());
+            self.update_model();
+            self.update_test_point();
+        }
+    }
+
+    // Hold a value in the tester and work out the other side of it
+    pub fn set_testing_value(&mut self, value: MeasurementType) {
+        self.testing_value = Some(value);
+        self.update_test_point();
+    }
+
+    // Recalculate the tester's chart point from the held testing value
+    fn update_test_point(&mut self) {
+        self.test_point = match self.testing_value.as_ref() {
+            Some(value) => match (value, self.convert(value)) {
+                (MeasurementType::Input(v), Ok(p)) => Some([(*v, p)]),
+                (MeasurementType::Output(p), Ok(v)) => Some([(v, *p)]),
+                _ => None,
+            },
+            None => None,
+        };
+    }
+}
+
+impl App {
+    // A session of one channel
+    pub fn new(input: Quantity, output: Quantity) -> Self {
+        App {
+            rig: Rig {
+                channels: vec![Channel::new(input, output)],
+                active: 0,
+            },
+            current_screen: ScreenID::Points,
+            mode: Mode::Select,
+            currently_editing: None,
+            temp_point: None,
+            session_path: PathBuf::from(session::DEFAULT_PATH),
+            message: None,
+            history_dir: PathBuf::from(history::DEFAULT_DIR),
+            report_templates: Vec::new(),
+            chart_size: plot::DEFAULT_SIZE,
+            lut_options: LutOptions::default(),
+        }
+    }
+
+    // The channel being worked on
+    pub fn active(&self) -> &Channel {
+        self.rig.active()
+    }
+
+    pub fn active_mut(&mut self) -> &mut Channel {
+        self.rig.active_mut()
+    }
+
+    // track the current screen
+    pub fn get_current_screen(&self) -> &ScreenID {
+        &self.current_screen
+    }
+
+    pub fn get_temp_point(&self) -> &str {
+        match self.temp_point.as_ref() {
+            Some(x) => x,
+            None => "",
+        }
+    }
+
+    pub fn get_currently_editing(&self) -> &Option<CurrentlyEditing> {
+        &self.currently_editing
+    }
+
+    pub fn set_session_path(&mut self, path: PathBuf) {
+        self.session_path = path;
+    }
+
+    // Result of the last save, shown until the next key
+    pub fn get_message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    // Save the session, and file each channel in its sensor's history when it has a serial number
+    pub fn save_session(&mut self) {
+        let path = self.session_path.display();
+        let saved = session::save(self, &self.session_path).and_then(|()| {
+            let mut filed = Vec::new();
+            for channel in self.get_channels() {
+                filed.extend(history::record(channel, &self.history_dir)?);
+            }
+            Ok(filed)
+        });
+        self.message = Some(match saved {
+            Ok(filed) if filed.is_empty() => format!("Saved to {}", path),
+            Ok(filed) if filed.len() == 1 => {
+                format!("Saved to {} and {}", path, filed[0].display())
+            }
+            Ok(filed) => format!(
+                "Saved to {} and filed {} calibrations in {}",
+                path,
+                filed.len(),
+                self.history_dir.display()
+            ),
+            Err(e) => format!("Couldn't save {}: {}", path, e),
+        });
+        self.refresh_history();
+    }
+
+    pub fn get_report_templates(&self) -> &[PathBuf] {
+        &self.report_templates
+    }
+
+    pub fn set_report_templates(&mut self, templates: Vec<PathBuf>) {
+        self.report_templates = templates;
+    }
+
+    // Write the Markdown and HTML reports next to the session file
+    pub fn write_reports(&mut self) {
+        let paths = ["md", "html"].map(|ext| self.export_path().with_extension(ext));
+        let written = paths.iter().try_for_each(|path| report::write(self, path));
+        self.message = Some(match written {
+            Ok(()) => format!(
+                "Reports written to {} and {}",
+                paths[0].display(),
+                paths[1].display()
+            ),
+            Err(e) => format!("Couldn't write the report: {}", e),
+        });
+    }
+
+    pub fn get_chart_size(&self) -> (u32, u32) {
+        self.chart_size
+    }
+
+    pub fn set_chart_size(&mut self, size: (u32, u32)) {
+        self.chart_size = size;
+    }
+
+    // Export the calibration chart as SVG and PNG next to the session file
+    pub fn export_chart(&mut self) {
+        let paths = ["svg", "png"].map(|ext| self.export_path().with_extension(ext));
+        let written = paths
+            .iter()
+            .try_for_each(|path| plot::write(self.active(), path, self.chart_size));
+        self.message = Some(match written {
+            Ok(()) => format!(
+                "Chart written to {} and {}",
+                paths[0].display(),
+                paths[1].display()
+            ),
+            Err(e) => format!("Couldn't write the chart: {}", e),
+        });
+    }
+
+    pub fn get_session_path(&self) -> &Path {
+        &self.session_path
+    }
+
+    // Write the calibration as code in every language next to the session file
+    pub fn export_code(&mut self) {
+        let path = self.export_path();
+        let written = Language::ALL
+            .iter()
+            .try_for_each(|lang| codegen::write(self.active(), *lang, &lang.path_for(&path)));
+        self.message = Some(match written {
+            Ok(()) => format!(
+                "Code written next to {} in {}",
+                path.display(),
+                Language::ALL.map(|l| l.name()).join(", ")
+            ),
+            Err(e) => format!("Couldn't write the code: {}", e),
+        });
+    }
+
+    pub fn get_lut_options(&self) -> &LutOptions {
+        &self.lut_options
+    }
+
+    pub fn set_lut_options(&mut self, options: LutOptions) {
+        self.lut_options = options;
+    }
+
+    // Write the lookup table as CSV and as a C header next to the session file
+    pub fn export_lut(&mut self) {
+        let paths = ["csv", "h"].map(|ext| lut::path_for(&self.export_path(), ext));
+        let written = lut::write(self.active(), &self.lut_options, &paths[0]).and_then(|table| {
+            lut::write(self.active(), &self.lut_options, &paths[1]).map(|_| table)
+        });
+        self.message = Some(match written {
+            Ok(table) => format!(
+                "{} entry table written to {} and {}, off by at most {} at {}",
+                table.entries.len(),
+                paths[0].display(),
+                paths[1].display(),
+                lut::format_error(table.max_error, self.active().output.unit),
+                units::format_value(table.at, self.active().input.unit)
+            ),
+            Err(e) => format!("Couldn't write the lookup table: {}", e),
+        });
+    }
+
+    pub fn set_history_dir(&mut self, dir: PathBuf) {
+        self.history_dir = dir;
+    }
 
-pub enum Mode {
-    Edit,
-    EditingValue,
-    Select,
-    Quit,
-}
-pub enum ScreenID {
-    P1,
-    P2,
-    Tester,
-}
+    // Every channel of the session in order
+    pub fn get_channels(&self) -> &[Channel] {
+        &self.rig.channels
+    }
 
-impl App {
-    pub fn new() -> Self {
-        let mut app = App {
-            p1: Some(Point::from((5.0, 100.0))),
-            p2: Some(Point::from((0.0, 0.0))),
-            line: None,
-            test_point: None,
-            current_screen: ScreenID::P1,
-            mode: Mode::Select,
-            testing_value: None,
-            currently_editing: None,
-            temp_point: None,
-            plot: Vec::new(),
-        };
-        app.update_line();
-        return app;
+    // Index of the channel being worked on
+    pub fn get_channel(&self) -> usize {
+        self.rig.active
     }
 
-    //calculates the line based on the 2 points if one of the points is NONE then
-    pub fn update_line(&mut self) {
-        if let Some(p1) = &self.p1 {
-            if let Some(p2) = &self.p2 {
-                self.line = Some(Line::from((p1, p2)));
-                self.update_vector();
-            } else {
-                self.line = None;
+    // Names on the channel tabs: the channel detail, or the channel's number when it has none
+    pub fn get_channel_names(&self) -> Vec<String> {
+        self.rig
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(n, channel)| match channel.metadata.get(Field::Channel) {
+                "" => format!("Channel {}", n + 1),
+                name => name.to_owned(),
+            })
+            .collect()
+    }
+
+    // What each channel's files are suffixed with, its name as an identifier and unique
+    pub fn get_channel_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        for (n, name) in self.get_channel_names().iter().enumerate() {
+            let mut file = codegen::identifier(name);
+            if file.is_empty() || files.contains(&file) {
+                file = format!("{}_{}", file, n + 1)
+                    .trim_start_matches('_')
+                    .to_owned();
             }
-        } else {
-            self.line = None;
+            files.push(file);
         }
+        files
     }
 
-    // Function to update the vector we will use to plot
-    pub fn update_vector(&mut self) {
-        // vector contents should look like [(v0,p0),(v1,p1), (v2,p2)....(vn,pn)]
+    // Channels after the first, as loaded from a session
+    pub fn set_channels(&mut self, channels: Vec<Channel>) {
+        self.rig.channels.truncate(1);
+        self.rig.channels.extend(channels);
+    }
 
-        // Wipe the existing vector
-        self.plot = Vec::new();
+    // The channel being worked on, when the rest of the session isn't wanted
+    pub fn into_channel(mut self) -> Channel {
+        self.rig.channels.swap_remove(self.rig.active)
+    }
 
-        // generate new vector
-        // get the highest x and y value
-
-        // get the lowest x and y value
-        let start: MeasurementType;
-        let end: MeasurementType;
-        if let (Some(x1), Some(x2)) = (self.p1.as_ref(), self.p2.as_ref()) {
-            let tmp1 = x1.get_val().get("v").unwrap_or(&0.0).clone();
-            let tmp2 = x2.get_val().get("v").unwrap_or(&0.0).clone();
-            let start_p: f64;
-            let end_p: f64;
-            if tmp1 < tmp2 {
-                start = MeasurementType::Voltage(tmp1.clone());
-                end = MeasurementType::Voltage(tmp2.clone());
-                start_p = tmp1;
-                end_p = tmp2;
-            } else {
-                start = MeasurementType::Voltage(tmp2.clone());
-                end = MeasurementType::Voltage(tmp1.clone());
-                start_p = tmp2;
-                end_p = tmp1;
-            }
-            if let Some(l) = self.line.as_ref() {
-                let _res1 = self
-                    .plot
-                    .push((start_p, l.get_corresponding_value(&start).unwrap()));
-                let _res2 = self
-                    .plot
-                    .push((end_p, l.get_corresponding_value(&end).unwrap()));
-            }
+    // Work on another channel, the screen staying where it is
+    pub fn select_channel(&mut self, n: usize) {
+        if n < self.rig.channels.len() {
+            self.rig.active = n;
+            self.refresh_history();
         }
     }
 
-    /*
-     -=-=-=-=-=-  Getters and Setters -=-=-=-=-=-=-
-    */
-    // Get vector slice for plot
-    pub fn get_plot_data(&self) -> &Vec<(f64, f64)> {
-        self.plot.as_ref()
+    // Tab and shift-tab go round the channels
+    pub fn step_channel(&mut self, forward: bool) {
+        let count = self.rig.channels.len();
+        let n = match forward {
+            true => (self.rig.active + 1) % count,
+            false => (self.rig.active + count - 1) % count,
+        };
+        self.select_channel(n);
     }
 
-    pub fn get_test_series(&self) -> Option<&[(f64, f64); 1]> {
-        self.test_point.as_ref()
+    // A new last channel set up like this one, its points, details and readings left to fill in
+    pub fn add_channel(&mut self) {
+        let channel = self.active().blank();
+        self.rig.channels.push(channel);
+        self.select_channel(self.rig.channels.len() - 1);
+        self.message = Some(format!(
+            "Added {}",
+            self.get_channel_names()[self.rig.active]
+        ));
     }
 
-    // Function to return a bounds struct to be used to set actual bounds and label axes
-    /*
-     * labels: Vec<String>,
-     * boundsounds: (f64,f64),
-     */
-    pub fn get_bounds(&self) -> Bounds {
-        // If the plot vector has points then generate a bounds struct
-        if self.plot.len() > 0 {
-            let mut labels: Vec<String> = Vec::new();
-
-            // structures the bounds
-            let x_min_max = get_min_max(self.plot[0].0.clone(), self.plot[1].0.clone());
-            let y_min_max = get_min_max(self.plot[0].1.clone(), self.plot[1].1.clone());
-
-            // maybe we should find the lowest of the 2
-            let mut min = get_min_max(x_min_max.clone().0, y_min_max.0).clone().0 as i32;
-            let mut max = get_min_max(y_min_max.1, x_min_max.1).1 as i32;
-
-            // get label top
-            max = (max + 5) - (max % 5);
-            min = (min - 5) - (min % 5);
-            let max = max as f64;
-            let min = min as f64;
-            // push labels
-            labels.push(min.to_string());
-
-            let mut i = 1;
-            let segments = 4;
-            let gap = (max - min) / segments as f64;
-            while i < segments {
-                labels.push((min + gap * i as f64).to_string());
-                i = i + 1;
-            }
-
-            labels.push(max.to_string());
-            // place holder
-            Bounds {
-                bounds: (min, max),
-                labels: labels,
-            }
-        } else {
-            // If the thing is empty just use a default bounds struct
-            Bounds {
-                bounds: (0.0, 100.0),
-                labels: vec!["0".to_string(), "10".to_string()],
-            }
+    // Drop this channel for the next one along, a session keeps at least one
+    pub fn remove_channel(&mut self) {
+        if self.rig.channels.len() == 1 {
+            self.message = Some("The only channel can't be removed".to_owned());
+            return;
         }
+        let n = self.rig.active;
+        let name = self.get_channel_names().swap_remove(n);
+        self.rig.channels.remove(n);
+        self.select_channel(n.min(self.rig.channels.len() - 1));
+        self.message = Some(format!("Removed {}", name));
     }
-    // Get tuple with (m,b) from line
-    pub fn get_line_val(&self) -> String {
-        if let Some(line) = &self.line {
-            if let Some(vals) = line.get_val() {
-                format!("Slope: {:.4} Intercept: {:.4}", vals.0, vals.1)
-            } else {
-                "Unable to calculate line".to_owned()
+
+    // Reports, charts, code and lookup tables for every channel next to the session file
+    pub fn export_channels(&mut self) {
+        let files = self.get_channel_files();
+        let names = self.get_channel_names();
+        let active = self.rig.active;
+        let mut failed = Vec::new();
+        for (n, (file, name)) in files.iter().zip(&names).enumerate() {
+            self.rig.active = n;
+            let path = channel_path(&self.session_path, file);
+            if let Err(e) = self.export_to(&path) {
+                failed.push(format!("{}: {}", name, e));
             }
-        } else {
-            "Unable to calculate line".to_owned()
         }
+        self.rig.active = active;
+        self.message = Some(match failed.as_slice() {
+            [] => format!(
+                "Reports, charts, code and tables for {} channels written next to {}",
+                files.len(),
+                self.session_path.display()
+            ),
+            _ => format!("Couldn't export {}", failed.join(", ")),
+        });
     }
-    // track the current screen
-    pub fn get_current_screen(&self) -> &ScreenID {
-        &self.current_screen
-    }
-    pub fn get_temp_point(&self) -> &str {
-        match self.temp_point.as_ref() {
-            Some(x) => x,
-            None => "",
+
+    // Where the single exports of this channel go: beside the session file, as the channel's own
+    // files when there are others
+    fn export_path(&self) -> PathBuf {
+        match self.rig.channels.len() {
+            1 => self.session_path.clone(),
+            _ => channel_path(
+                &self.session_path,
+                &self.get_channel_files()[self.rig.active],
+            ),
         }
     }
-    pub fn get_currently_editing(&self) -> &Option<CurrentlyEditing> {
-        &self.currently_editing
+
+    // Everything the single exports write, for a session at `path`
+    fn export_to(&self, path: &Path) -> io::Result<()> {
+        for ext in ["md", "html"] {
+            report::write(self, &path.with_extension(ext))?;
+        }
+        for ext in ["svg", "png"] {
+            plot::write(self.active(), &path.with_extension(ext), self.chart_size)?;
+        }
+        for lang in Language::ALL {
+            codegen::write(self.active(), lang, &lang.path_for(path))?;
+        }
+        for ext in ["csv", "h"] {
+            lut::write(self.active(), &self.lut_options, &lut::path_for(path, ext))?;
+        }
+        Ok(())
     }
 
-    //get Point values
-    pub fn get_points(&self) -> Option<(HashMap<&str, f64>, HashMap<&str, f64>)> {
-        if let Some(p1) = &self.p1 {
-            if let Some(p2) = &self.p2 {
-                return Some((p1.get_val(), p2.get_val()));
-            } else {
-                return None;
-            }
+    // Read the sensor's past calibrations again, in the session units
+    fn refresh_history(&mut self) {
+        let channel = self.rig.active_mut();
+        let serial = channel.metadata.get(Field::Serial);
+        channel.history = if serial.is_empty() {
+            Vec::new()
         } else {
-            return None;
-        }
+            history::load(
+                &self.history_dir,
+                serial,
+                channel.input.unit,
+                channel.output.unit,
+            )
+        };
     }
+
     pub fn get_mode(&self) -> &Mode {
         &self.mode
     }
 
+    /*
+     * Typed PLC scaling: two numbers pick the raw range, four are raw min, raw max, eng min and
+     * eng max to replace the points with, and nothing goes back to the points' range
+     */
+    fn set_plc_typed(&mut self, typed: &str) {
+        let channel = self.rig.active_mut();
+        if typed.trim().is_empty() {
+            channel.plc_raw = None;
+            return;
+        }
+        let units = [
+            channel.input.unit,
+            channel.input.unit,
+            channel.output.unit,
+            channel.output.unit,
+        ];
+        let values: Option<Vec<f64>> = typed
+            .split(',')
+            .zip(units)
+            .map(|(v, unit)| units::parse_value(v, unit).ok())
+            .collect();
+        match (values.as_deref(), typed.split(',').count()) {
+            (Some(&[lo, hi]), 2) if lo != hi => channel.plc_raw = Some((lo, hi)),
+            (Some(&[raw_min, raw_max, eng_min, eng_max]), 4) => {
+                let scaling = PlcScaling {
+                    raw_min,
+                    raw_max,
+                    eng_min,
+                    eng_max,
+                };
+                if let Err(e) = channel.import_plc_scaling(scaling) {
+                    self.message = Some(e);
+                }
+            }
+            _ => {
+                self.message = Some(format!(
+                    "Expected <raw min>,<raw max>[,<eng min>,<eng max>]: {}",
+                    typed
+                ))
+            }
+        }
+    }
+
     /*
      *  App control functions. The App is drivern by main by calling update_state.
      *  Update state will use different control flow depending on the curren mode
@@ -221,11 +2159,14 @@ impl App {
 
     // Update the status we should call differnt functions based on the modes
     pub fn update_state(&mut self) -> Result<(), ()> {
+        // the screen with the message is already drawn, it goes with the next key
+        self.message = None;
         match self.mode {
             Mode::Edit => self.update_editor_mode()?,
             Mode::Select => self.update_selector_mode()?,
             Mode::Quit => return Err(()),
             Mode::EditingValue => self.edit_value()?,
+            Mode::ConfirmRemove => self.confirm_remove(),
         }
         Ok(())
     }
@@ -254,87 +2195,200 @@ impl App {
                     KeyCode::Char(c) => {
                         s.push(c);
                     }
+                    // these are typed straight from select mode so they go back there
+                    KeyCode::Esc
+                        if matches!(
+                            self.currently_editing,
+                            Some(
+                                CurrentlyEditing::Tolerance
+                                    | CurrentlyEditing::Metadata
+                                    | CurrentlyEditing::PlcScaling
+                                    | CurrentlyEditing::ComparePath
+                            )
+                        ) =>
+                    {
+                        self.temp_point = None;
+                        self.currently_editing = None;
+                        self.mode = Mode::Select;
+                    }
+                    KeyCode::Enter
+                        if self.currently_editing == Some(CurrentlyEditing::Metadata) =>
+                    {
+                        let typed = self.temp_point.take().unwrap_or_default();
+                        let channel = self.rig.active_mut();
+                        if let Some(field) = Field::ALL.get(channel.metadata_selected) {
+                            channel.metadata.set(*field, &typed);
+                        }
+                        self.currently_editing = None;
+                        self.mode = Mode::Select;
+                    }
+                    KeyCode::Enter
+                        if self.currently_editing == Some(CurrentlyEditing::PlcScaling) =>
+                    {
+                        let typed = self.temp_point.take().unwrap_or_default();
+                        self.set_plc_typed(&typed);
+                        self.currently_editing = None;
+                        self.mode = Mode::Select;
+                    }
+                    KeyCode::Enter
+                        if self.currently_editing == Some(CurrentlyEditing::ComparePath) =>
+                    {
+                        let typed = self.temp_point.take().unwrap_or_default();
+                        if let Err(e) = self.active_mut().load_comparison(Path::new(typed.trim())) {
+                            self.message = Some(e);
+                        }
+                        self.currently_editing = None;
+                        self.mode = Mode::Select;
+                    }
+                    KeyCode::Enter
+                        if self.currently_editing == Some(CurrentlyEditing::Tolerance) =>
+                    {
+                        let typed = self.temp_point.take().unwrap_or_default();
+                        // blank clears it, anything that doesn't parse leaves it alone
+                        let channel = self.rig.active_mut();
+                        if typed.trim().is_empty() {
+                            channel.tolerance = None;
+                        } else if let Some(t) = Tolerance::parse(&typed, channel.output.unit) {
+                            channel.tolerance = Some(t);
+                        }
+                        self.currently_editing = None;
+                        self.mode = Mode::Select;
+                    }
                     KeyCode::Esc => {
                         // escape will clear the string and switch mode back to editing
                         self.temp_point = None;
                         self.mode = Mode::Edit;
                     }
+                    // uncertainties have their own parsing since they are differences
+                    KeyCode::Enter
+                        if matches!(
+                            self.currently_editing,
+                            Some(
+                                CurrentlyEditing::InputUncertainty
+                                    | CurrentlyEditing::OutputUncertainty
+                            )
+                        ) =>
+                    {
+                        let typed = self.temp_point.take().unwrap_or_default();
+                        self.set_uncertainty(&typed);
+                        self.mode = Mode::Edit;
+                    }
+                    // and budget lines are mostly not numbers
+                    KeyCode::Enter
+                        if matches!(self.currently_editing, Some(CurrentlyEditing::Budget(_))) =>
+                    {
+                        let typed = self.temp_point.take().unwrap_or_default();
+                        self.set_contributor_field(&typed);
+                        self.mode = Mode::Edit;
+                    }
                     KeyCode::Enter => {
                         // Enter will attempt to push the value back into the point. If it fails to parse the value change nothing
                         // Recalculate line if that succeeeds
 
+                        // Parse first, the point we are editing is borrowed mutably below
+                        // Values may be typed with a unit ("14.7 psi"), convert them into the session units
+                        let channel = self.rig.active_mut();
+                        let parsed =
+                            match (self.currently_editing.as_ref(), self.temp_point.as_ref()) {
+                                (Some(CurrentlyEditing::Input), Some(val)) => {
+                                    Some(channel.parse_input(val))
+                                }
+                                (Some(CurrentlyEditing::Output), Some(val)) => {
+                                    Some(units::parse_value(val, channel.output.unit))
+                                }
+                                (Some(CurrentlyEditing::ColdJunction), Some(val)) => Some(
+                                    units::parse_value(val, channel.cold_junction_unit()).and_then(
+                                        |t| channel.cold_junction_unit().convert(t, Unit::Celsius),
+                                    ),
+                                ),
+                                _ => None,
+                            };
+
                         // I need to know: which point and which value of that point
                         // Which point were editing
                         let mut point_ref = None;
                         match self.current_screen {
-                            ScreenID::P1 => {
-                                point_ref = self.p1.as_mut();
+                            ScreenID::Points => {
+                                point_ref = channel.points.get_mut(channel.selected);
                             }
-                            ScreenID::P2 => {
-                                point_ref = self.p2.as_mut();
+                            ScreenID::Verify => {
+                                point_ref = channel.verification.get_mut(channel.verify_selected);
                             }
-
-                            ScreenID::Tester => {} // TODO: Impliment tester
+                            ScreenID::Tester
+                            | ScreenID::Budget
+                            | ScreenID::Stages
+                            | ScreenID::Metadata
+                            | ScreenID::History
+                            | ScreenID::Compare => {}
                         }
                         // Which value of the point
                         if let Some(ce) = self.currently_editing.as_ref() {
-                            // Try to parse the string to f64. If it's succesful update the point
-                            if let Some(val) = self.temp_point.as_ref() {
-                                if let Ok(parsed) = val.parse::<f64>() {
-                                    match ce {
-                                        CurrentlyEditing::Physical => {
-                                            match self.current_screen {
-                                                ScreenID::Tester => {
-                                                    self.testing_value =
-                                                        Some(MeasurementType::Physical(parsed));
-                                                    // populate test_point
-                                                    if let Some(l) = self.line.as_ref() {
-                                                        self.test_point = Some([(
-                                                            l.get_corresponding_value(
-                                                                &MeasurementType::Physical(parsed),
-                                                            )
-                                                            .unwrap(),
-                                                            parsed,
-                                                        )])
-                                                    }
+                            // If the string parsed update the point
+                            if let Some(Ok(parsed)) = parsed {
+                                match ce {
+                                    CurrentlyEditing::ColdJunction => {
+                                        channel.set_cold_junction(parsed);
+                                    }
+                                    // handled before parsing
+                                    CurrentlyEditing::InputUncertainty
+                                    | CurrentlyEditing::OutputUncertainty
+                                    | CurrentlyEditing::Budget(_)
+                                    | CurrentlyEditing::Tolerance
+                                    | CurrentlyEditing::Metadata
+                                    | CurrentlyEditing::ComparePath
+                                    | CurrentlyEditing::PlcScaling => {}
+                                    CurrentlyEditing::Output => {
+                                        match self.current_screen {
+                                            ScreenID::Tester => {
+                                                channel.set_testing_value(MeasurementType::Output(
+                                                    parsed,
+                                                ));
+                                            }
+                                            // verification readings don't touch the fit
+                                            ScreenID::Verify => {
+                                                if let Some(p) = point_ref {
+                                                    p.set_output(parsed);
                                                 }
-                                                _ => {
-                                                    if let Some(p) = point_ref {
-                                                        p.set_physical(parsed);
-                                                        // Recalculate the line
-                                                        self.update_line();
-                                                        self.test_point = None;
-                                                        self.testing_value = None;
+                                            }
+                                            _ => {
+                                                // the outputs of the loop points are the range values
+                                                if let Some(ls) = channel.loop_scaling.as_mut() {
+                                                    match channel.selected {
+                                                        0 => ls.lrv = parsed,
+                                                        _ => ls.urv = parsed,
                                                     }
                                                 }
+                                                if let Some(p) = point_ref {
+                                                    p.set_output(parsed);
+                                                    // Recalculate the model
+                                                    channel.update_model();
+                                                    channel.test_point = None;
+                                                    channel.testing_value = None;
+                                                }
                                             }
                                         }
-                                        CurrentlyEditing::Voltage => {
-                                            match self.current_screen {
-                                                ScreenID::Tester => {
-                                                    // add testing value
-                                                    self.testing_value =
-                                                        Some(MeasurementType::Voltage(parsed));
-
-                                                    // populate test_point
-                                                    if let Some(l) = self.line.as_ref() {
-                                                        self.test_point = Some([(
-                                                            parsed,
-                                                            l.get_corresponding_value(
-                                                                &MeasurementType::Voltage(parsed),
-                                                            )
-                                                            .unwrap(),
-                                                        )])
-                                                    }
+                                    }
+                                    CurrentlyEditing::Input => {
+                                        match self.current_screen {
+                                            ScreenID::Tester => {
+                                                channel.set_testing_value(MeasurementType::Input(
+                                                    parsed,
+                                                ));
+                                            }
+                                            ScreenID::Verify => {
+                                                if let Some(p) = point_ref {
+                                                    p.set_input(parsed);
                                                 }
-                                                _ => {
-                                                    if let Some(p) = point_ref {
-                                                        p.set_voltage(parsed);
-                                                        // Recalculate the line
-                                                        self.update_line();
-                                                        self.test_point = None;
-                                                        self.testing_value = None;
-                                                    }
+                                            }
+                                            // loop points are pinned to 4 and 20 mA
+                                            _ if channel.loop_scaling.is_some() => {}
+                                            _ => {
+                                                if let Some(p) = point_ref {
+                                                    p.set_input(parsed);
+                                                    // Recalculate the model
+                                                    channel.update_model();
+                                                    channel.test_point = None;
+                                                    channel.testing_value = None;
                                                 }
                                             }
                                         }
@@ -363,7 +2417,7 @@ impl App {
      * It needs to:
      *  - If escape is pressed it needs to: { turn currently_editing back to None, switch mode to select}
      *  - set currently_editing enum to Some(CurrentlyEditing)
-     *  - Toggle which value, physical or voltage, is being currently edited as arrow keys are pressed
+     *  - Toggle which value, input or output, is being currently edited as arrow keys are pressed
      *  - enter EditingValue mode if enter is pressed
      */
     fn update_editor_mode(&mut self) -> Result<(), ()> {
@@ -376,48 +2430,173 @@ impl App {
                         self.mode = Mode::Select;
                     }
                     KeyCode::Enter => {
-                        match self.current_screen {
-                            ScreenID::Tester => {
-                                self.testing_value = None;
-                                self.test_point = None;
-                            }
-                            _ => {}
+                        if let ScreenID::Tester = self.current_screen {
+                            let channel = self.rig.active_mut();
+                            channel.testing_value = None;
+                            channel.test_point = None;
                         }
                         self.mode = Mode::EditingValue;
                     }
-                    KeyCode::Down => match self.current_screen {
-                        ScreenID::P1 | ScreenID::P2 => {
-                            self.currently_editing = Some(CurrentlyEditing::Physical);
-                        }
-                        _ => {}
-                    },
-                    KeyCode::Up => match self.current_screen {
-                        ScreenID::P1 | ScreenID::P2 => {
-                            self.currently_editing = Some(CurrentlyEditing::Voltage);
-                        }
-                        _ => {}
-                    },
-                    KeyCode::Left => match self.current_screen {
-                        ScreenID::Tester => {
-                            self.currently_editing = Some(CurrentlyEditing::Voltage);
-                        }
-                        _ => {}
-                    },
-                    KeyCode::Right => match self.current_screen {
-                        ScreenID::Tester => {
-                            self.currently_editing = Some(CurrentlyEditing::Physical)
-                        }
-                        _ => {}
-                    },
+                    // up and down walk the point list keeping the same column
+                    KeyCode::Down => self.step_row(true),
+                    KeyCode::Up => self.step_row(false),
+                    KeyCode::Left => self.step_column(false),
+                    KeyCode::Right => self.step_column(true),
                     _ => {}
                 }
             }
         } else {
-            self.currently_editing = Some(CurrentlyEditing::Voltage);
+            self.currently_editing = Some(self.first_editable());
         }
         Ok(())
     }
 
+    // The value edit mode starts on
+    fn first_editable(&self) -> CurrentlyEditing {
+        self.editable_columns().remove(0)
+    }
+
+    // Values that can be edited on the current screen, left to right
+    fn editable_columns(&self) -> Vec<CurrentlyEditing> {
+        use CurrentlyEditing::*;
+        match self.current_screen {
+            // the inputs of loop points are fixed
+            ScreenID::Points if self.active().loop_scaling.is_some() => {
+                vec![Output, OutputUncertainty]
+            }
+            ScreenID::Points => vec![Input, Output, InputUncertainty, OutputUncertainty],
+            // thermocouple testers have the cold junction to the right of the output
+            ScreenID::Tester if self.active().get_cold_junction().is_some() => {
+                vec![Input, Output, ColdJunction]
+            }
+            ScreenID::Tester => vec![Input, Output],
+            ScreenID::Budget => [
+                BudgetField::Name,
+                BudgetField::Side,
+                BudgetField::Value,
+                BudgetField::Distribution,
+                BudgetField::Dof,
+            ]
+            .into_iter()
+            .map(Budget)
+            .collect(),
+            ScreenID::Verify => vec![Input, Output],
+            // the comparison is only looked at, and the details are typed straight from select mode
+            ScreenID::Stages | ScreenID::Metadata | ScreenID::History | ScreenID::Compare => {
+                Vec::new()
+            }
+        }
+    }
+
+    // Move the selection a row down or up the point list or the budget
+    fn step_row(&mut self, down: bool) {
+        let channel = self.rig.active_mut();
+        let (selected, len) = match self.current_screen {
+            ScreenID::Points => (&mut channel.selected, channel.points.len()),
+            ScreenID::Budget => (&mut channel.budget_selected, channel.contributors.len()),
+            ScreenID::Verify => (&mut channel.verify_selected, channel.verification.len()),
+            ScreenID::Metadata => (&mut channel.metadata_selected, Field::ALL.len()),
+            ScreenID::Tester | ScreenID::Stages | ScreenID::History | ScreenID::Compare => return,
+        };
+        if !down {
+            *selected = selected.saturating_sub(1);
+        } else if *selected + 1 < len {
+            *selected += 1;
+        }
+    }
+
+    // Move the edited value one column left or right, stopping at the ends
+    fn step_column(&mut self, right: bool) {
+        let mut columns = self.editable_columns();
+        let at = columns
+            .iter()
+            .position(|c| Some(c) == self.currently_editing.as_ref());
+        let next = match (at, right) {
+            (Some(i), true) => (i + 1).min(columns.len() - 1),
+            (Some(i), false) => i.saturating_sub(1),
+            (None, _) => 0,
+        };
+        self.currently_editing = Some(columns.swap_remove(next));
+    }
+
+    // Set the selected point's uncertainty in the column being edited, a blank entry clears it.
+    // Typed units only scale the value since an uncertainty is a difference
+    fn set_uncertainty(&mut self, typed: &str) {
+        let unit = match self.currently_editing {
+            Some(CurrentlyEditing::InputUncertainty) => self.active().input.unit,
+            Some(CurrentlyEditing::OutputUncertainty) => self.active().output.unit,
+            _ => return,
+        };
+        // a zero, negative or infinite one would leave the fit without sensible weights
+        let u = match units::parse_uncertainty(typed, unit) {
+            Ok(u) if u.is_none_or(|u| u > 0.0 && u.is_finite()) => u,
+            Ok(_) => {
+                self.message = Some(format!("An uncertainty is a positive number: {}", typed));
+                return;
+            }
+            Err(_) => return,
+        };
+        let channel = self.rig.active_mut();
+        if let Some(p) = channel.points.get_mut(channel.selected) {
+            match self.currently_editing {
+                Some(CurrentlyEditing::InputUncertainty) => p.set_input_uncertainty(u),
+                _ => p.set_output_uncertainty(u),
+            }
+            channel.update_model();
+            channel.update_test_point();
+        }
+    }
+
+    // Set a column of the selected budget line from what was typed, leaving it alone if that doesn't
+    // parse. The value takes units like an uncertainty, and a blank degrees of freedom is infinite
+    fn set_contributor_field(&mut self, typed: &str) {
+        let Some(CurrentlyEditing::Budget(field)) = self.currently_editing else {
+            return;
+        };
+        let channel = self.rig.active_mut();
+        let (input_unit, output_unit) = (channel.input.unit, channel.output.unit);
+        let Some(c) = channel.contributors.get_mut(channel.budget_selected) else {
+            return;
+        };
+        match field {
+            BudgetField::Name => c.name = typed.trim().to_owned(),
+            BudgetField::Side => c.side = Side::parse(typed).unwrap_or(c.side),
+            BudgetField::Value => {
+                let unit = match c.side {
+                    Side::Input => input_unit,
+                    Side::Output => output_unit,
+                };
+                if let Ok(v) = units::parse_uncertainty(typed, unit) {
+                    c.value = v.unwrap_or(0.0).abs();
+                }
+            }
+            BudgetField::Distribution => {
+                c.distribution = Distribution::parse(typed).unwrap_or(c.distribution)
+            }
+            BudgetField::Dof => match typed.trim() {
+                "" | "inf" => c.dof = None,
+                t => {
+                    if let Some(dof) = t.parse::<f64>().ok().filter(|d| *d > 0.0) {
+                        c.dof = Some(dof);
+                    }
+                }
+            },
+        }
+    }
+
+    /*
+     * MODE = ConfirmRemove
+     * Only y removes the channel, anything else goes back to selecting with it kept
+     */
+    fn confirm_remove(&mut self) {
+        if let Some(key) = get_key_press() {
+            self.mode = Mode::Select;
+            if key == KeyCode::Char('y') {
+                self.remove_channel();
+            }
+        }
+    }
+
     /*
      * MODE = Select
      * This mode is just responsible for switching the screen, quitting, and entering Edit mode
@@ -430,34 +2609,175 @@ impl App {
                     self.mode = Mode::Quit;
                 }
                 KeyCode::Left => match screen {
-                    ScreenID::Tester => {
-                        self.current_screen = ScreenID::P1;
-                    }
-                    _ => {}
+                    ScreenID::Tester => self.current_screen = ScreenID::Points,
+                    ScreenID::Budget => self.current_screen = ScreenID::Tester,
+                    ScreenID::Verify => self.current_screen = ScreenID::Budget,
+                    ScreenID::Stages => self.current_screen = ScreenID::Verify,
+                    ScreenID::Metadata => self.current_screen = ScreenID::Stages,
+                    ScreenID::History => self.current_screen = ScreenID::Metadata,
+                    ScreenID::Compare => self.current_screen = ScreenID::History,
+                    ScreenID::Points => {}
                 },
                 KeyCode::Right => match screen {
-                    ScreenID::P1 | ScreenID::P2 => {
-                        self.current_screen = ScreenID::Tester;
+                    ScreenID::Points => self.current_screen = ScreenID::Tester,
+                    ScreenID::Tester => self.current_screen = ScreenID::Budget,
+                    ScreenID::Budget => self.current_screen = ScreenID::Verify,
+                    ScreenID::Verify => self.current_screen = ScreenID::Stages,
+                    ScreenID::Stages => self.current_screen = ScreenID::Metadata,
+                    // the serial may have just been typed
+                    ScreenID::Metadata => {
+                        self.refresh_history();
+                        self.current_screen = ScreenID::History;
                     }
-                    _ => {}
+                    ScreenID::History => self.current_screen = ScreenID::Compare,
+                    ScreenID::Compare => {}
                 },
-                KeyCode::Up => match screen {
-                    ScreenID::P2 => {
-                        self.current_screen = ScreenID::P1;
-                    }
-                    _ => {}
+                KeyCode::Up => self.step_row(false),
+                KeyCode::Down => self.step_row(true),
+                // add and remove points, or budget lines on the budget screen
+                KeyCode::Char('n') => match screen {
+                    ScreenID::Budget => self.active_mut().add_contributor(),
+                    ScreenID::Verify => self.active_mut().add_verification(),
+                    _ => self.active_mut().add_point(),
                 },
-                KeyCode::Down => match screen {
-                    ScreenID::P1 => {
-                        self.current_screen = ScreenID::P2;
-                    }
-                    _ => {}
+                KeyCode::Char('x') | KeyCode::Delete => match screen {
+                    ScreenID::Budget => self.active_mut().remove_contributor(),
+                    ScreenID::Verify => self.active_mut().remove_verification(),
+                    _ => self.active_mut().remove_point(),
                 },
+                // type the verification tolerance
+                KeyCode::Char('t') if matches!(screen, ScreenID::Verify) => {
+                    self.currently_editing = Some(CurrentlyEditing::Tolerance);
+                    self.temp_point = Some(String::new());
+                    self.mode = Mode::EditingValue;
+                }
+                KeyCode::Char('m') => {
+                    let next = self.active().model_kind.next();
+                    self.active_mut().set_model_kind(next);
+                }
+                // switch nonlinear refinement of the linearized fits on and off
+                KeyCode::Char('r') => {
+                    let options = self.active().fit_options;
+                    self.active_mut().set_fit_options(FitOptions {
+                        refine: !options.refine,
+                        ..options
+                    });
+                }
+                // confidence level of the bands
+                KeyCode::Char('c') => {
+                    let next = CONFIDENCE_LEVELS
+                        .iter()
+                        .find(|l| **l > self.active().confidence + 1e-9)
+                        .unwrap_or(&CONFIDENCE_LEVELS[0]);
+                    self.active_mut().set_confidence(*next);
+                }
+                // robust fitting: off, Huber, Tukey, RANSAC
+                KeyCode::Char('b') => {
+                    let options = self.active().fit_options;
+                    self.active_mut().set_fit_options(FitOptions {
+                        robust: options.robust.next(),
+                        ..options
+                    });
+                }
+                KeyCode::Char('e') => {
+                    self.active_mut().toggle_excluded();
+                }
+                // cycle the line fit between OLS, Deming and orthogonal regression
+                KeyCode::Char('g') => {
+                    let options = self.active().fit_options;
+                    self.active_mut().set_fit_options(FitOptions {
+                        regression: options.regression.next(),
+                        ..options
+                    });
+                }
+                // an empty budget has nothing to edit
+                KeyCode::Enter
+                    if matches!(screen, ScreenID::Budget)
+                        && self.active().contributors.is_empty() => {}
+                KeyCode::Enter
+                    if matches!(screen, ScreenID::Verify)
+                        && self.active().verification.is_empty() => {}
+                KeyCode::Enter if matches!(screen, ScreenID::Stages | ScreenID::History) => {}
+                // type the path of the session to compare against
+                KeyCode::Enter if matches!(screen, ScreenID::Compare) => {
+                    self.temp_point = Some(String::new());
+                    self.currently_editing = Some(CurrentlyEditing::ComparePath);
+                    self.mode = Mode::EditingValue;
+                }
+                // type the selected detail, starting from what it says now
+                KeyCode::Enter if matches!(screen, ScreenID::Metadata) => {
+                    let field = Field::ALL[self.active().metadata_selected];
+                    self.temp_point = Some(self.active().metadata.get(field).to_owned());
+                    self.currently_editing = Some(CurrentlyEditing::Metadata);
+                    self.mode = Mode::EditingValue;
+                }
+                KeyCode::Char('w') => {
+                    self.save_session();
+                }
+                KeyCode::Char('p') => {
+                    self.write_reports();
+                }
+                KeyCode::Char('s') => {
+                    self.export_chart();
+                }
+                KeyCode::Char('k') => {
+                    self.export_code();
+                }
+                KeyCode::Char('u') => {
+                    self.export_lut();
+                }
+                // channels: go round them, add one set up like this, drop this one, export them all
+                KeyCode::Tab => self.step_channel(true),
+                KeyCode::BackTab => self.step_channel(false),
+                KeyCode::Char('d') => self.add_channel(),
+                // a channel goes with its points and details, so that is asked about first
+                KeyCode::Char('D') if self.rig.channels.len() > 1 => {
+                    self.mode = Mode::ConfirmRemove;
+                }
+                KeyCode::Char('D') => self.remove_channel(),
+                KeyCode::Char('E') => self.export_channels(),
+                // type the PLC raw range or a whole scaling, linear models only
+                KeyCode::Char('z') if self.active().model_kind == ModelKind::Linear => {
+                    self.temp_point = Some(String::new());
+                    self.currently_editing = Some(CurrentlyEditing::PlcScaling);
+                    self.mode = Mode::EditingValue;
+                }
+                // record the as-found calibration, then switch between the stages
+                KeyCode::Char('f') => {
+                    self.active_mut().toggle_stage();
+                }
                 KeyCode::Enter => {
                     // change to editor mode
-                    self.currently_editing = Some(CurrentlyEditing::Voltage);
+                    self.currently_editing = Some(self.first_editable());
                     self.mode = Mode::Edit;
                 }
+                // cycle through compatible units, converting everything we hold
+                KeyCode::Char('i') => {
+                    let channel = self.active_mut();
+                    channel.set_units(channel.input.unit.next_compatible(), channel.output.unit);
+                }
+                KeyCode::Char('o') => {
+                    let channel = self.active_mut();
+                    channel.set_units(channel.input.unit, channel.output.unit.next_compatible());
+                }
+                // switch what the input is (voltage, current, counts, resistance)
+                KeyCode::Char('q') => {
+                    let next = self
+                        .active()
+                        .input
+                        .unit
+                        .dimension()
+                        .next_input()
+                        .default_unit();
+                    self.active_mut()
+                        .set_input_quantity(Quantity::from_unit(next));
+                }
+                KeyCode::Char('l') => {
+                    self.active_mut().toggle_loop_scaling();
+                }
+                KeyCode::Char('a') => {
+                    self.active_mut().toggle_adc_input();
+                }
                 // Every other one is useless
                 _ => {}
             }
@@ -467,13 +2787,42 @@ impl App {
     }
 }
 
+// Where a channel's files go, e.g. calibration_tank_3.lsb for its reports calibration_tank_3.md
+pub fn channel_path(session: &Path, file: &str) -> PathBuf {
+    let stem = session
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("calibration");
+    let ext = session
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("lsb");
+    session.with_file_name(format!("{}_{}.{}", stem, file, ext))
+}
+
+// Input for a point added after `selected`: halfway to the next one, or a step on from the last
+fn next_input(points: &[Point], selected: usize) -> f64 {
+    let input_at = |i: usize| {
+        points
+            .get(i)
+            .filter(|p| p.is_valid())
+            .map(|p| p.get_val()["in"])
+    };
+    let prev = selected.checked_sub(1).and_then(input_at);
+    match (input_at(selected), input_at(selected + 1), prev) {
+        (Some(x), Some(next), _) => (x + next) / 2.0,
+        (Some(x), None, Some(prev)) => x + (x - prev),
+        (Some(x), None, None) => x + 1.0,
+        _ => 0.0,
+    }
+}
+
 // Function to get key press. We will just sit in the loop until we get a keypress
 fn get_key_press() -> Option<KeyCode> {
     loop {
         // Read a key
-        let e = event::read();
-        if e.is_ok() {
-            if let Event::Key(key) = e.unwrap() {
+        if let Ok(e) = event::read() {
+            if let Event::Key(key) = e {
                 if key.kind == event::KeyEventKind::Release {
                     // Skip events that are not KeyEventKind::Press
                     continue;
@@ -487,6 +2836,13 @@ fn get_key_press() -> Option<KeyCode> {
     }
 }
 
+fn model_input_error(unit: Unit) -> CalcError {
+    match unit.dimension() {
+        Dimension::Resistance => CalcError::OutOfDomain("input must be a resistance"),
+        _ => CalcError::OutOfDomain("input must be a voltage"),
+    }
+}
+
 // get max
 pub fn get_min_max<T>(a: T, b: T) -> (T, T)
 where
@@ -498,3 +2854,312 @@ where
         (a, b)
     }
 }
+
+// Bounds around everything that is drawn on a chart
+pub fn bounds_of(all: &[(f64, f64)]) -> (Bounds, Bounds) {
+    // If the thing is empty just use a default bounds struct
+    if all.is_empty() {
+        return (make_bounds(0.0, 100.0), make_bounds(0.0, 100.0));
+    }
+    let x_min_max = all
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |acc, p| {
+            (acc.0.min(p.0), acc.1.max(p.0))
+        });
+    let y_min_max = all
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |acc, p| {
+            (acc.0.min(p.1), acc.1.max(p.1))
+        });
+    (
+        make_bounds(x_min_max.0, x_min_max.1),
+        make_bounds(y_min_max.0, y_min_max.1),
+    )
+}
+
+// Bounds padded out to round numbers, labelled every 1, 2 or 5 times a power of ten
+pub fn make_bounds(min: f64, max: f64) -> Bounds {
+    let (mut min, mut max) = get_min_max(min, max);
+    if max - min < 1e-12 {
+        // a single value still needs some room around it
+        let pad = if min == 0.0 { 1.0 } else { min.abs() * 0.1 };
+        min -= pad;
+        max += pad;
+    }
+    // aim for about 4 segments
+    let raw = (max - min) / 4.0;
+    let mag = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|f| f * mag)
+        .find(|s| *s >= raw)
+        .unwrap_or(10.0 * mag);
+    let lo = (min / step).floor() * step;
+    let hi = (max / step).ceil() * step;
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let segments = ((hi - lo) / step).round() as usize;
+    let labels = (0..=segments)
+        .map(|i| format!("{:.*}", decimals, lo + step * i as f64))
+        .collect();
+    Bounds {
+        bounds: (lo, hi),
+        labels,
+    }
+}
+
+// Unit of the slope (output per input), with a leading space so it can follow the number
+pub fn slope_unit(input: Unit, output: Unit) -> String {
+    match (input, output) {
+        (Unit::Unitless, Unit::Unitless) => String::new(),
+        (Unit::Unitless, o) => format!(" {}", o),
+        (i, Unit::Unitless) => format!(" 1/{}", i),
+        (i, o) => format!(" {}/{}", o, i),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{App, Channel, Stage};
+    use crate::calculator::verify::Tolerance;
+    use crate::calculator::{LoopScaling, MeasurementType, Point};
+    use crate::metadata::Field;
+    use crate::session;
+    use crate::units::{Quantity, Unit};
+    use std::path::PathBuf;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn points(values: &[(f64, f64)]) -> Vec<Point> {
+        values.iter().map(|p| Point::from(*p)).collect()
+    }
+
+    fn inputs(channel: &Channel) -> Vec<f64> {
+        channel
+            .get_points()
+            .iter()
+            .map(|p| p.get_val()["in"])
+            .collect()
+    }
+
+    #[test]
+    fn as_found_as_left() {
+        let mut channel = Channel::new(
+            Quantity::new("Sensor", Unit::Volt),
+            Quantity::new("Level", Unit::Percent),
+        );
+        let found = [(0.0, 1.0), (10.0, 102.0)];
+        channel.set_points(points(&found), None);
+        // the first toggle records the points as found and carries them on as left
+        channel.toggle_stage();
+        assert!(channel.get_stage() == Some(Stage::AsLeft));
+        assert_eq!(vec![0.0, 10.0], inputs(&channel));
+
+        // adjusted, least squares gives a slope of 10 and an offset of 1/3
+        let left = [(0.0, 0.0), (5.0, 51.0), (10.0, 100.0)];
+        channel.set_points(points(&left), Some((Stage::AsLeft, points(&found))));
+        channel.toggle_stage();
+        assert!(channel.get_stage() == Some(Stage::AsFound));
+        assert_eq!(vec![0.0, 10.0], inputs(&channel));
+        channel.toggle_stage();
+        assert!(channel.get_stage() == Some(Stage::AsLeft));
+        assert_eq!(vec![0.0, 5.0, 10.0], inputs(&channel));
+
+        let comparison = channel.get_stage_comparison().unwrap();
+        let [(m0, b0), (m1, b1)] = comparison.lines.unwrap();
+        assert!(close(m0, 10.1) && close(b0, 1.0));
+        assert!(close(m1, 10.0) && close(b1, 1.0 / 3.0));
+        assert!(close(m1 - m0, -0.1) && close(b1 - b0, -2.0 / 3.0));
+
+        // rows line up by position, each reading through its own stage's line
+        assert_eq!(3, comparison.rows.len());
+        let left_errors = [1.0 / 3.0, -2.0 / 3.0, 1.0 / 3.0];
+        for ((row, (x, y)), e) in comparison.rows.iter().zip(left).zip(left_errors) {
+            assert_eq!(y, row.nominal);
+            let (reading, error) = row.left.unwrap();
+            assert_eq!(x, reading);
+            assert!(close(error, e));
+        }
+        // the found line goes through both its points, the second beside the left stage's second
+        let (reading, error) = comparison.rows[1].found.unwrap();
+        assert!(reading == 10.0 && close(error, 0.0));
+        assert!(close(comparison.rows[1].delta().unwrap(), -2.0 / 3.0));
+        assert!(comparison.rows[2].found.is_none());
+        assert!(comparison.rows[2].delta().is_none());
+        assert!(close(
+            channel.convert(&MeasurementType::Input(0.0)).unwrap(),
+            1.0 / 3.0
+        ));
+    }
+
+    #[test]
+    fn blank_channel() {
+        let mut channel = Channel::new(
+            Quantity::from_unit(Unit::Milliamp),
+            Quantity::new("Tank level", Unit::Percent),
+        );
+        channel.set_loop_scaling(Some(LoopScaling::new(0.0, 250.0)));
+        channel.set_confidence(0.99);
+        channel.set_tolerance(Tolerance::parse("0.5% FS", Unit::Percent));
+        channel.set_contributors(Vec::new());
+        let mut metadata = channel.get_metadata().clone();
+        metadata.set(Field::Serial, "LT-101");
+        channel.set_metadata(metadata);
+
+        // the settings come across, the details and budget start afresh
+        let blank = channel.blank();
+        assert_eq!("Tank level", blank.get_output().name);
+        assert_eq!(0.99, blank.get_confidence());
+        assert_eq!(channel.get_tolerance(), blank.get_tolerance());
+        assert_eq!(250.0, blank.get_loop_scaling().unwrap().urv);
+        assert_eq!(4, blank.get_contributors().len());
+        assert!(blank.get_metadata().get(Field::Serial).is_empty());
+        assert!(!blank.get_metadata().get(Field::Date).is_empty());
+        assert!(blank.get_model().is_ok());
+
+        // without loop scaling the points are empty ones to fill in
+        channel.set_loop_scaling(None);
+        let blank = channel.blank();
+        assert_eq!(2, blank.get_points().len());
+        assert!(blank.get_points().iter().all(|p| !p.is_valid()));
+    }
+
+    // A session of channels with these names, each with its own output unit
+    fn rig(names: &[&str]) -> App {
+        let mut app = App::new(
+            Quantity::from_unit(Unit::Milliamp),
+            Quantity::new("Tank level", Unit::Percent),
+        );
+        for (n, name) in names.iter().enumerate() {
+            if n > 0 {
+                app.add_channel();
+            }
+            let channel = app.active_mut();
+            let mut metadata = channel.get_metadata().clone();
+            metadata.set(Field::Channel, name);
+            channel.set_metadata(metadata);
+            channel.set_points(points(&[(4.0, 0.0), (20.0, 100.0 * (n + 1) as f64)]), None);
+        }
+        app
+    }
+
+    #[test]
+    fn step_channels() {
+        let mut app = rig(&["A", "B", "C"]);
+        assert_eq!(2, app.get_channel());
+        // going round wraps at both ends
+        app.step_channel(true);
+        assert_eq!(0, app.get_channel());
+        app.step_channel(false);
+        assert_eq!(2, app.get_channel());
+        app.step_channel(false);
+        assert_eq!(1, app.get_channel());
+        assert_eq!("B", app.active().get_metadata().get(Field::Channel));
+    }
+
+    #[test]
+    fn keep_last_channel() {
+        let mut app = rig(&["A"]);
+        app.remove_channel();
+        assert_eq!(vec!["A"], app.get_channel_names());
+        assert_eq!(vec![4.0, 20.0], inputs(app.active()));
+        assert_eq!(Some("The only channel can't be removed"), app.get_message());
+        // with another there is one to fall back on
+        app.add_channel();
+        app.remove_channel();
+        assert_eq!(vec!["A"], app.get_channel_names());
+    }
+
+    #[test]
+    fn unique_channel_files() {
+        let app = rig(&["Tank 3", "tank 3", "Tank-3", "!!"]);
+        assert_eq!(
+            vec!["tank_3", "tank_3_2", "tank_3_3", "4"],
+            app.get_channel_files()
+        );
+    }
+
+    #[test]
+    fn channels_round_trip() {
+        let mut app = rig(&["Tank 1", "Tank 2"]);
+        app.active_mut().set_units(Unit::Ampere, Unit::Percent);
+        let saved = session::to_string(&app);
+        let back = session::from_str(&saved, &[]).unwrap();
+        assert_eq!(saved, session::to_string(&back));
+        // it opens on the first channel, each with its own units and points
+        assert_eq!(0, back.get_channel());
+        assert_eq!(vec!["Tank 1", "Tank 2"], back.get_channel_names());
+        let [first, second] = back.get_channels() else {
+            panic!("expected two channels");
+        };
+        assert_eq!(Unit::Milliamp, first.get_input().unit);
+        assert_eq!(Unit::Ampere, second.get_input().unit);
+        assert_eq!(vec![4.0, 20.0], inputs(first));
+        assert!(close(0.02, inputs(second)[1]));
+        assert_eq!(100.0, first.get_points()[1].get_val()["out"]);
+        assert_eq!(200.0, second.get_points()[1].get_val()["out"]);
+    }
+
+    #[test]
+    fn export_paths() {
+        let mut app = App::new(
+            Quantity::from_unit(Unit::Milliamp),
+            Quantity::new("Tank level", Unit::Percent),
+        );
+        app.set_session_path(PathBuf::from("rig.lsb"));
+        assert_eq!(PathBuf::from("rig.lsb"), app.export_path());
+        // with a second channel each exports to its own files
+        app.add_channel();
+        assert_eq!(PathBuf::from("rig_channel_2.lsb"), app.export_path());
+        app.step_channel(true);
+        assert_eq!(PathBuf::from("rig_channel_1.lsb"), app.export_path());
+    }
+
+    #[test]
+    fn compare_sessions() {
+        let dir = std::env::temp_dir().join(format!("lsb-compare-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let session = |input: Unit, output: Unit, values: &[(f64, f64)], file: &str| {
+            let mut app = App::new(
+                Quantity::new("Sensor", input),
+                Quantity::new("Level", output),
+            );
+            app.active_mut().set_points(points(values), None);
+            let path = dir.join(file);
+            session::save(&app, &path).unwrap();
+            path
+        };
+        // 9.7 %/V + 2 % saved in millivolts, and a session that reads out in bar
+        let before = session(
+            Unit::Millivolt,
+            Unit::Percent,
+            &[(0.0, 2.0), (10_000.0, 99.0)],
+            "before.lsb",
+        );
+        let pressure = session(Unit::Volt, Unit::Bar, &[(0.0, 0.0), (10.0, 1.0)], "bar.lsb");
+
+        let mut channel = Channel::new(
+            Quantity::new("Sensor", Unit::Volt),
+            Quantity::new("Level", Unit::Percent),
+        );
+        channel.set_points(points(&[(0.0, 0.0), (10.0, 100.0)]), None);
+        assert!(channel.load_comparison(&pressure).is_err());
+        channel.load_comparison(&before).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // 10 x less 9.7 x + 2 is 0.3 x - 2: +1 at 10 V, but -2 at 0 V is further out
+        let comparison = channel.get_comparison().unwrap();
+        assert!(comparison.label.starts_with("before.lsb"));
+        assert!(!comparison.difference.is_empty());
+        for ((x, d), (other_x, other)) in comparison.difference.iter().zip(&comparison.other_plot) {
+            assert_eq!(x, other_x);
+            assert!(close(*other, 9.7 * x + 2.0));
+            assert!(close(*d, 0.3 * x - 2.0));
+        }
+        let (x, d) = *comparison.difference.last().unwrap();
+        assert!(close(x, 10.0) && close(d, 1.0));
+        let (at, max) = comparison.max.unwrap();
+        assert!(close(at, 0.0) && close(max, -2.0));
+    }
+}
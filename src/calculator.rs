@@ -1,49 +1,202 @@
 use std::collections::HashMap;
+use std::fmt;
+
+use crate::units::Unit;
+
+pub mod adc;
+pub mod budget;
+pub mod curve;
+pub mod dac;
+pub mod drift;
+pub mod linalg;
+pub mod robust;
+pub mod rtd;
+pub mod stats;
+pub mod thermistor;
+pub mod thermocouple;
+pub mod verify;
+
+use curve::{Curve, CurveShape};
+use robust::Robust;
+use rtd::CallendarVanDusen;
+use thermistor::{Beta, SteinhartHart};
+use thermocouple::{Thermocouple, ThermocoupleType};
+
 pub struct Line {
     slope: Option<f64>,
     intercept: Option<f64>,
 }
+#[derive(Clone)]
 pub struct Point {
-    voltage: Option<f64>,
-    physical: Option<f64>,
+    input: Option<f64>,
+    output: Option<f64>,
+    // standard uncertainties of the reference values, used to weight the fit
+    input_uncertainty: Option<f64>,
+    output_uncertainty: Option<f64>,
+    // left out of the fit but kept in the list
+    excluded: bool,
 }
 
 //use this to pass a value into the equation
 pub enum MeasurementType {
-    Voltage(f64),
-    Physical(f64),
+    Input(f64),
+    Output(f64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CalcError {
+    NotEnoughPoints { needed: usize, got: usize },
+    // the points don't pin the model down, e.g. two points with the same input
+    Singular,
+    OutOfDomain(&'static str),
+}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalcError::NotEnoughPoints { needed, got } => {
+                write!(f, "needs {} points, have {}", needed, got)
+            }
+            CalcError::Singular => write!(f, "points don't define a curve"),
+            CalcError::OutOfDomain(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+// The calibration models a session can fit
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ModelKind {
+    Linear,
+    Curve(CurveShape),
+    SteinhartHart,
+    Beta,
+    CallendarVanDusen,
+    Thermocouple(ThermocoupleType),
+}
+
+// How a model is fitted beyond picking the model itself
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FitOptions {
+    // carry linearized fits on with a nonlinear least squares on the output error
+    pub refine: bool,
+    // how the linear model treats errors in the inputs
+    pub regression: Regression,
+    // output error variance over input error variance for Deming regression, in the session units
+    pub variance_ratio: f64,
+    // keep bad readings from dragging the fit
+    pub robust: Robust,
+}
+
+impl Default for FitOptions {
+    fn default() -> Self {
+        FitOptions {
+            refine: false,
+            regression: Regression::Ordinary,
+            variance_ratio: 1.0,
+            robust: Robust::Off,
+        }
+    }
+}
+
+// Line fitting methods. Ordinary least squares takes the inputs as exact, the errors-in-variables
+// fits allow for noise on both axes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Regression {
+    Ordinary,
+    // errors on both axes in a known ratio of variances
+    Deming,
+    // Deming with equal variances, i.e. perpendicular distances to the line
+    Orthogonal,
+}
+
+// Parameter covariance of a two parameter fit
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Covariance {
+    pub params: [[f64; 2]; 2],
+    // variance of a single new reading about the fit
+    pub observation: f64,
+    // degrees of freedom of the residual scatter, None when stated uncertainties set the scale
+    pub dof: Option<usize>,
+}
+
+impl Covariance {
+    // Standard uncertainties of the two parameters
+    pub fn uncertainties(&self) -> [f64; 2] {
+        [self.params[0][0].sqrt(), self.params[1][1].sqrt()]
+    }
+
+    // Coverage factor for an interval holding the value with probability `level`: Student's t
+    // on the scatter's degrees of freedom, or the normal distribution for stated uncertainties
+    pub fn coverage(&self, level: f64) -> f64 {
+        let p = (1.0 + level) / 2.0;
+        match self.dof {
+            Some(dof) => stats::t_quantile(p, dof as f64),
+            None => stats::normal_quantile(p),
+        }
+    }
+}
+
+// A fitted model. Thermistor models take ohms and give kelvin, RTDs ohms and °C and thermocouples mV and °C,
+// see ModelKind::units
+pub enum Model {
+    Linear(Line),
+    Curve(Curve),
+    SteinhartHart(SteinhartHart),
+    Beta(Beta),
+    CallendarVanDusen(CallendarVanDusen),
+    Thermocouple(Thermocouple),
 }
 
 impl Point {
     pub fn new() -> Self {
         Point {
-            voltage: None,
-            physical: None,
+            input: None,
+            output: None,
+            input_uncertainty: None,
+            output_uncertainty: None,
+            excluded: false,
         }
     }
     pub fn is_valid(&self) -> bool {
-        if self.voltage.is_some() && self.physical.is_some() {
-            true
-        } else {
-            false
-        }
+        self.input.is_some() && self.output.is_some()
     }
     pub fn set_point(&mut self, x: f64, y: f64) {
-        self.voltage = Some(x);
-        self.physical = Some(y);
+        self.input = Some(x);
+        self.output = Some(y);
     }
-    pub fn set_voltage(&mut self, v: f64) {
-        self.voltage = Some(v);
+    pub fn set_input(&mut self, v: f64) {
+        self.input = Some(v);
     }
 
-    pub fn set_physical(&mut self, p: f64) {
-        self.physical = Some(p);
+    pub fn set_output(&mut self, p: f64) {
+        self.output = Some(p);
+    }
+
+    // (input, output) standard uncertainties
+    pub fn get_uncertainty(&self) -> (Option<f64>, Option<f64>) {
+        (self.input_uncertainty, self.output_uncertainty)
+    }
+
+    pub fn set_input_uncertainty(&mut self, u: Option<f64>) {
+        self.input_uncertainty = u.map(f64::abs);
+    }
+
+    pub fn set_output_uncertainty(&mut self, u: Option<f64>) {
+        self.output_uncertainty = u.map(f64::abs);
+    }
+
+    pub fn is_excluded(&self) -> bool {
+        self.excluded
+    }
+
+    pub fn set_excluded(&mut self, excluded: bool) {
+        self.excluded = excluded;
     }
 
     pub fn get_val(&self) -> HashMap<&str, f64> {
         let mut vals = HashMap::new();
-        vals.insert("v", self.voltage.clone().unwrap());
-        vals.insert("p", self.physical.clone().unwrap());
+        vals.insert("in", self.input.unwrap());
+        vals.insert("out", self.output.unwrap());
         vals
     }
 }
@@ -59,13 +212,13 @@ impl Line {
     // updates slop and intercept given 2 points
     pub fn calc(&mut self, p1: &Point, p2: &Point) {
         if p1.is_valid() && p2.is_valid() {
-            if p1.voltage == p2.voltage {
+            if p1.input == p2.input {
                 self.slope = None;
                 self.intercept = None;
             } else {
-                let m = (&p1.physical.unwrap() - &p2.physical.unwrap())
-                    / (&p1.voltage.unwrap() - &p2.voltage.unwrap());
-                let b = -(&m * p1.voltage.unwrap()) + p1.physical.unwrap();
+                let m = (p1.output.unwrap() - p2.output.unwrap())
+                    / (p1.input.unwrap() - p2.input.unwrap());
+                let b = -(m * p1.input.unwrap()) + p1.output.unwrap();
                 self.slope = Some(m);
                 self.intercept = Some(b);
             }
@@ -75,16 +228,61 @@ impl Line {
         }
     }
 
+    // Weighted least squares line through (input, output) pairs. Two points give the exact line
+    pub fn fit(points: &[(f64, f64)], w: &[f64]) -> Self {
+        let mut line = Line::new();
+        if let [p1, p2] = points {
+            line.calc(&Point::from(*p1), &Point::from(*p2));
+            return line;
+        }
+        let rows: Vec<Vec<f64>> = points.iter().map(|(x, _)| vec![*x, 1.0]).collect();
+        let y: Vec<f64> = points.iter().map(|(_, y)| *y).collect();
+        if let Some(x) = linalg::weighted_least_squares(&rows, &y, w) {
+            line.slope = Some(x[0]);
+            line.intercept = Some(x[1]);
+        }
+        line
+    }
+
+    /*
+     * Deming regression: minimise the weighted distances to the line with the variance of the
+     * output errors d = `ratio` times that of the input errors. A big ratio tends to ordinary
+     * least squares, 1 is orthogonal regression. Written as 2 d Sxy / (d Sxx - Syy + sqrt(...))
+     * so a flat line still works
+     */
+    pub fn fit_deming(points: &[(f64, f64)], w: &[f64], ratio: f64) -> Self {
+        let mut line = Line::new();
+        let sw: f64 = w.iter().take(points.len()).sum();
+        if points.len() < 2 || sw <= 0.0 || ratio <= 0.0 {
+            return line;
+        }
+        let mean = |f: fn(&(f64, f64)) -> f64| {
+            points.iter().zip(w).map(|(p, w)| w * f(p)).sum::<f64>() / sw
+        };
+        let (mx, my) = (mean(|p| p.0), mean(|p| p.1));
+        let (mut sxx, mut syy, mut sxy) = (0.0, 0.0, 0.0);
+        for ((x, y), w) in points.iter().zip(w) {
+            sxx += w * (x - mx).powi(2);
+            syy += w * (y - my).powi(2);
+            sxy += w * (x - mx) * (y - my);
+        }
+        let d = ratio * sxx - syy;
+        let denominator = d + (d * d + 4.0 * ratio * sxy * sxy).sqrt();
+        if denominator.abs() < 1e-300 {
+            return line;
+        }
+        let m = 2.0 * ratio * sxy / denominator;
+        line.slope = Some(m);
+        line.intercept = Some(my - m * mx);
+        line
+    }
+
     // Get values in uhh hashmap i guess
     pub fn get_val(&self) -> Option<(f64, f64)> {
         if let Some(m) = self.slope {
-            if let Some(b) = self.intercept {
-                return Some((m, b));
-            } else {
-                return None;
-            }
+            self.intercept.map(|b| (m, b))
         } else {
-            return None;
+            None
         }
     }
 
@@ -92,14 +290,320 @@ impl Line {
     pub fn get_corresponding_value(&self, value: &MeasurementType) -> Result<f64, ()> {
         if let (Some(m), Some(b)) = (self.slope.as_ref(), self.intercept.as_ref()) {
             match value {
-                MeasurementType::Physical(y) => return Ok((y - b) / m),
-                MeasurementType::Voltage(x) => return Ok(m * x + b),
+                MeasurementType::Output(y) => return Ok((y - b) / m),
+                MeasurementType::Input(x) => return Ok(m * x + b),
             }
         }
         Err(())
     }
 }
 
+impl Regression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Regression::Ordinary => "OLS",
+            Regression::Deming => "Deming",
+            Regression::Orthogonal => "orthogonal",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Regression> {
+        match s.to_ascii_lowercase().as_str() {
+            "ols" | "ordinary" => Some(Regression::Ordinary),
+            "deming" => Some(Regression::Deming),
+            "orthogonal" | "tls" | "total" => Some(Regression::Orthogonal),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Regression {
+        match self {
+            Regression::Ordinary => Regression::Deming,
+            Regression::Deming => Regression::Orthogonal,
+            Regression::Orthogonal => Regression::Ordinary,
+        }
+    }
+}
+
+impl ModelKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModelKind::Linear => "Linear",
+            ModelKind::Curve(shape) => shape.name(),
+            ModelKind::SteinhartHart => "Steinhart-Hart",
+            ModelKind::Beta => "Beta",
+            ModelKind::CallendarVanDusen => "Callendar-Van Dusen",
+            ModelKind::Thermocouple(t) => t.label(),
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<ModelKind> {
+        match s.to_ascii_lowercase().as_str() {
+            "linear" | "line" => Some(ModelKind::Linear),
+            "exponential" | "exp" => Some(ModelKind::Curve(CurveShape::Exponential)),
+            "logarithmic" | "log" => Some(ModelKind::Curve(CurveShape::Logarithmic)),
+            "power" | "power-law" => Some(ModelKind::Curve(CurveShape::Power)),
+            "steinhart-hart" | "steinhart" | "sh" => Some(ModelKind::SteinhartHart),
+            "beta" => Some(ModelKind::Beta),
+            "callendar-van-dusen" | "cvd" | "rtd" => Some(ModelKind::CallendarVanDusen),
+            // "type-k" or "tc-k"
+            s => s
+                .strip_prefix("type-")
+                .or_else(|| s.strip_prefix("tc-"))
+                .and_then(ThermocoupleType::parse)
+                .map(ModelKind::Thermocouple),
+        }
+    }
+
+    // What --model takes for this kind, parse() reads it back
+    pub fn key(&self) -> String {
+        match self {
+            ModelKind::Linear => "linear".to_owned(),
+            ModelKind::Curve(CurveShape::Exponential) => "exp".to_owned(),
+            ModelKind::Curve(CurveShape::Logarithmic) => "log".to_owned(),
+            ModelKind::Curve(CurveShape::Power) => "power".to_owned(),
+            ModelKind::SteinhartHart => "steinhart-hart".to_owned(),
+            ModelKind::Beta => "beta".to_owned(),
+            ModelKind::CallendarVanDusen => "cvd".to_owned(),
+            ModelKind::Thermocouple(t) => format!("type-{}", t.name().to_ascii_lowercase()),
+        }
+    }
+
+    pub fn next(&self) -> ModelKind {
+        match self {
+            ModelKind::Linear => ModelKind::Curve(CurveShape::Exponential),
+            ModelKind::Curve(CurveShape::Exponential) => ModelKind::Curve(CurveShape::Logarithmic),
+            ModelKind::Curve(CurveShape::Logarithmic) => ModelKind::Curve(CurveShape::Power),
+            ModelKind::Curve(CurveShape::Power) => ModelKind::SteinhartHart,
+            ModelKind::SteinhartHart => ModelKind::Beta,
+            ModelKind::Beta => ModelKind::CallendarVanDusen,
+            ModelKind::CallendarVanDusen => ModelKind::Thermocouple(ThermocoupleType::K),
+            ModelKind::Thermocouple(t) => match t.next() {
+                Some(t) => ModelKind::Thermocouple(t),
+                None => ModelKind::Linear,
+            },
+        }
+    }
+
+    // Fewest points the model can be fitted to
+    pub fn min_points(&self) -> usize {
+        match self {
+            ModelKind::Linear | ModelKind::Curve(_) | ModelKind::Beta => 2,
+            ModelKind::SteinhartHart => 3,
+            ModelKind::CallendarVanDusen => 1,
+            // no points leaves the reference table as it is
+            ModelKind::Thermocouple(_) => 0,
+        }
+    }
+
+    // The (input, output) units the model is fitted in. None means it works in the session units
+    pub fn units(&self) -> Option<(Unit, Unit)> {
+        match self {
+            ModelKind::Linear | ModelKind::Curve(_) => None,
+            ModelKind::SteinhartHart | ModelKind::Beta => Some((Unit::Ohm, Unit::Kelvin)),
+            ModelKind::CallendarVanDusen => Some((Unit::Ohm, Unit::Celsius)),
+            ModelKind::Thermocouple(_) => Some((Unit::Millivolt, Unit::Celsius)),
+        }
+    }
+}
+
+impl Model {
+    // Fit a model to (input, output) pairs given in the model's units. `weights` are 1/u^2 of the
+    // output errors, None fits every point the same
+    pub fn fit(
+        kind: ModelKind,
+        points: &[(f64, f64)],
+        weights: Option<&[f64]>,
+        options: &FitOptions,
+    ) -> Result<Model, CalcError> {
+        let ones = vec![1.0; points.len()];
+        let w = weights.unwrap_or(&ones);
+        match kind {
+            ModelKind::Linear => {
+                if points.len() < 2 {
+                    return Err(CalcError::NotEnoughPoints {
+                        needed: 2,
+                        got: points.len(),
+                    });
+                }
+                let line = match options.regression {
+                    Regression::Ordinary => Line::fit(points, w),
+                    Regression::Deming => Line::fit_deming(points, w, options.variance_ratio),
+                    Regression::Orthogonal => Line::fit_deming(points, w, 1.0),
+                };
+                match line.get_val() {
+                    Some(_) => Ok(Model::Linear(line)),
+                    None => Err(CalcError::Singular),
+                }
+            }
+            ModelKind::Curve(shape) => {
+                Ok(Model::Curve(Curve::fit(shape, points, w, options.refine)?))
+            }
+            ModelKind::SteinhartHart => Ok(Model::SteinhartHart(SteinhartHart::fit(points, w)?)),
+            ModelKind::Beta => Ok(Model::Beta(Beta::fit(points, w)?)),
+            ModelKind::CallendarVanDusen => {
+                Ok(Model::CallendarVanDusen(CallendarVanDusen::fit(points, w)?))
+            }
+            ModelKind::Thermocouple(t) => Ok(Model::Thermocouple(Thermocouple::fit(t, points, w)?)),
+        }
+    }
+
+    pub fn kind(&self) -> ModelKind {
+        match self {
+            Model::Linear(_) => ModelKind::Linear,
+            Model::Curve(c) => ModelKind::Curve(c.shape),
+            Model::SteinhartHart(_) => ModelKind::SteinhartHart,
+            Model::Beta(_) => ModelKind::Beta,
+            Model::CallendarVanDusen(_) => ModelKind::CallendarVanDusen,
+            Model::Thermocouple(tc) => ModelKind::Thermocouple(tc.kind),
+        }
+    }
+
+    // Same idea as Line::get_corresponding_value: give an input get the output and the other way round
+    pub fn get_corresponding_value(&self, value: &MeasurementType) -> Result<f64, CalcError> {
+        match (self, value) {
+            (Model::Linear(l), v) => l
+                .get_corresponding_value(v)
+                .map_err(|_| CalcError::Singular),
+            (Model::Curve(c), MeasurementType::Input(x)) => c.output(*x),
+            (Model::Curve(c), MeasurementType::Output(y)) => c.input(*y),
+            (Model::SteinhartHart(sh), MeasurementType::Input(r)) => sh.temperature(*r),
+            (Model::SteinhartHart(sh), MeasurementType::Output(t)) => sh.resistance(*t),
+            (Model::Beta(b), MeasurementType::Input(r)) => b.temperature(*r),
+            (Model::Beta(b), MeasurementType::Output(t)) => b.resistance(*t),
+            (Model::CallendarVanDusen(cvd), MeasurementType::Input(r)) => cvd.temperature(*r),
+            (Model::CallendarVanDusen(cvd), MeasurementType::Output(t)) => cvd.resistance(*t),
+            (Model::Thermocouple(tc), MeasurementType::Input(e)) => tc.temperature(*e),
+            (Model::Thermocouple(tc), MeasurementType::Output(t)) => tc.emf(*t),
+        }
+    }
+
+    // dy/dx at x in the model's units
+    pub fn slope_at(&self, x: f64) -> Option<f64> {
+        if let Some((m, _)) = self.get_line() {
+            return Some(m);
+        }
+        let h = (x.abs() * 1e-6).max(1e-9);
+        let hi = self
+            .get_corresponding_value(&MeasurementType::Input(x + h))
+            .ok()?;
+        let lo = self
+            .get_corresponding_value(&MeasurementType::Input(x - h))
+            .ok()?;
+        Some((hi - lo) / (2.0 * h))
+    }
+
+    /*
+     * Covariance of the two parameters of a line (slope, intercept) or curve (a, b), (J^T W J)^-1.
+     * Weights from `stated` uncertainties are taken as they are, otherwise they are only relative
+     * and the scatter of the residuals sets the scale, which needs more than two points with any
+     * weight
+     */
+    pub fn covariance(&self, points: &[(f64, f64)], w: &[f64], stated: bool) -> Option<Covariance> {
+        let mut jtj = vec![vec![0.0; 2]; 2];
+        for ((x, _), w) in points.iter().zip(w) {
+            let g = self.gradient(*x)?;
+            for i in 0..2 {
+                for j in 0..2 {
+                    jtj[i][j] += w * g[i] * g[j];
+                }
+            }
+        }
+        let cov = linalg::invert(jtj)?;
+        let n = w.iter().filter(|w| **w > 0.0).count();
+        let (scale, dof) = match stated {
+            true => (1.0, None),
+            false if n > 2 => {
+                let ssr: f64 = points
+                    .iter()
+                    .zip(w)
+                    .map(|((x, y), w)| {
+                        let f = self.get_corresponding_value(&MeasurementType::Input(*x));
+                        w * (y - f.unwrap_or(f64::NAN)).powi(2)
+                    })
+                    .sum();
+                (ssr / (n - 2) as f64, Some(n - 2))
+            }
+            false => return None,
+        };
+        // a new reading is taken to be as good as the average point
+        let mean_w = w.iter().filter(|w| **w > 0.0).sum::<f64>() / n.max(1) as f64;
+        let covariance = Covariance {
+            params: [
+                [cov[0][0] * scale, cov[0][1] * scale],
+                [cov[1][0] * scale, cov[1][1] * scale],
+            ],
+            observation: scale / mean_w,
+            dof,
+        };
+        if covariance.uncertainties().iter().all(|u| u.is_finite()) {
+            Some(covariance)
+        } else {
+            None
+        }
+    }
+
+    // Standard uncertainties at x of the fitted curve and of a new reading there, in output units
+    pub fn band(&self, cov: &Covariance, x: f64) -> Option<(f64, f64)> {
+        let g = self.gradient(x)?;
+        let p = cov.params;
+        let fitted = g[0] * g[0] * p[0][0] + 2.0 * g[0] * g[1] * p[0][1] + g[1] * g[1] * p[1][1];
+        Some((fitted.sqrt(), (fitted + cov.observation).sqrt()))
+    }
+
+    // Derivatives of the output with respect to the two parameters, for the models that have two
+    fn gradient(&self, x: f64) -> Option<[f64; 2]> {
+        match self {
+            Model::Linear(_) => Some([x, 1.0]),
+            Model::Curve(c) => Some(c.gradient(x)),
+            _ => None,
+        }
+    }
+
+    // The linear model's (slope, intercept)
+    pub fn get_line(&self) -> Option<(f64, f64)> {
+        match self {
+            Model::Linear(l) => l.get_val(),
+            _ => None,
+        }
+    }
+
+    // Coefficients for the Results panel. Linear ones need the session units so they are formatted by the App
+    pub fn describe(&self) -> String {
+        match self {
+            Model::Linear(l) => match l.get_val() {
+                Some((m, b)) => format!("Slope: {:.4} Intercept: {:.4}", m, b),
+                None => "Unable to calculate line".to_owned(),
+            },
+            Model::Curve(c) => match c.shape {
+                CurveShape::Exponential => format!("y = {:.6e} e^({:.6e} x)", c.a, c.b),
+                CurveShape::Logarithmic => format!("y = {:.6e} ln(x) {:+.6e}", c.a, c.b),
+                CurveShape::Power => format!("y = {:.6e} x^{:.6}", c.a, c.b),
+            },
+            Model::SteinhartHart(sh) => {
+                format!("A: {:.6e} B: {:.6e} C: {:.6e}", sh.a, sh.b, sh.c)
+            }
+            Model::Beta(b) => format!(
+                "Beta: {:.1} K R0: {:.1} ohm @ {:.2} °C",
+                b.beta,
+                b.r0,
+                b.t0 - 273.15
+            ),
+            Model::CallendarVanDusen(cvd) => format!(
+                "R0: {:.4} ohm A: {:.5e} B: {:.4e} C: {:.4e}",
+                cvd.r0, cvd.a, cvd.b, cvd.c
+            ),
+            Model::Thermocouple(tc) => format!(
+                "{} + {:.4} mV {:+.3e} mV/°C {:+.3e} mV/°C²",
+                tc.kind.label(),
+                tc.deviation[0],
+                tc.deviation[1],
+                tc.deviation[2]
+            ),
+        }
+    }
+}
+
 impl From<(&Point, &Point)> for Line {
     fn from(points: (&Point, &Point)) -> Self {
         let mut line = Line::new();
@@ -115,10 +619,106 @@ impl From<(f64, f64)> for Point {
         point
     }
 }
+// 4-20 mA transmitter scaling. The lower range value is output at 4 mA and the upper at 20 mA
+#[derive(Clone, Copy)]
+pub struct LoopScaling {
+    pub lrv: f64,
+    pub urv: f64,
+}
+
+// Where a loop current sits according to NAMUR NE43
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LoopStatus {
+    Normal,
+    UnderRange,
+    OverRange,
+    FaultLow,
+    FaultHigh,
+}
+
+impl LoopScaling {
+    pub const LOW_MA: f64 = 4.0;
+    pub const HIGH_MA: f64 = 20.0;
+    // NE43 says anything at or beyond these is a failure signal rather than a measurement
+    pub const FAULT_LOW_MA: f64 = 3.6;
+    pub const FAULT_HIGH_MA: f64 = 21.0;
+
+    pub fn new(lrv: f64, urv: f64) -> Self {
+        LoopScaling { lrv, urv }
+    }
+
+    // The two calibration points with the input expressed in `unit` (mA, A or uA)
+    pub fn points(&self, unit: Unit) -> (Point, Point) {
+        let to_unit = |ma: f64| Unit::Milliamp.convert(ma, unit).unwrap_or(ma);
+        (
+            Point::from((to_unit(Self::LOW_MA), self.lrv)),
+            Point::from((to_unit(Self::HIGH_MA), self.urv)),
+        )
+    }
+
+    // Classify a loop current in mA
+    pub fn status(ma: f64) -> LoopStatus {
+        if ma <= Self::FAULT_LOW_MA {
+            LoopStatus::FaultLow
+        } else if ma < Self::LOW_MA {
+            LoopStatus::UnderRange
+        } else if ma <= Self::HIGH_MA {
+            LoopStatus::Normal
+        } else if ma < Self::FAULT_HIGH_MA {
+            LoopStatus::OverRange
+        } else {
+            LoopStatus::FaultHigh
+        }
+    }
+}
+
+/*
+ * A PLC analog input block's scaling: the raw range of the card maps linearly onto the
+ * engineering range. The same line as slope and intercept, given by its ends instead
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlcScaling {
+    pub raw_min: f64,
+    pub raw_max: f64,
+    pub eng_min: f64,
+    pub eng_max: f64,
+}
+
+impl PlcScaling {
+    // The line's ends over a raw range
+    pub fn from_line(line: &Line, (raw_min, raw_max): (f64, f64)) -> Option<Self> {
+        let (m, b) = line.get_val()?;
+        Some(PlcScaling {
+            raw_min,
+            raw_max,
+            eng_min: m * raw_min + b,
+            eng_max: m * raw_max + b,
+        })
+    }
+
+    // The ends as calibration points, input raw and output engineering
+    pub fn points(&self) -> (Point, Point) {
+        (
+            Point::from((self.raw_min, self.eng_min)),
+            Point::from((self.raw_max, self.eng_max)),
+        )
+    }
+
+    // Back to slope and intercept, which has neither when the raw range is empty
+    pub fn line(&self) -> Line {
+        let (p1, p2) = self.points();
+        Line::from((&p1, &p2))
+    }
+}
+
 // -------TESTS --------
 #[cfg(test)]
 mod tests {
-    use super::{Line, Point};
+    use super::{
+        CalcError, FitOptions, Line, LoopScaling, LoopStatus, MeasurementType, Model, ModelKind,
+        PlcScaling, Point, Regression,
+    };
+    use crate::units::Unit;
 
     #[test]
     fn create_points() {
@@ -163,8 +763,201 @@ mod tests {
         assert_eq!(0.0, line.intercept.unwrap());
         assert_eq!(
             5.0,
-            line.get_corresponding_value(&crate::calculator::MeasurementType::Physical(5.0))
+            line.get_corresponding_value(&crate::calculator::MeasurementType::Output(5.0))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn loop_scaling() {
+        let (p1, p2) = LoopScaling::new(0.0, 10.0).points(Unit::Milliamp);
+        let line = Line::from((&p1, &p2));
+        assert_eq!(0.625, line.slope.unwrap());
+        assert_eq!(-2.5, line.intercept.unwrap());
+        assert_eq!(
+            12.0,
+            line.get_corresponding_value(&MeasurementType::Output(5.0))
                 .unwrap()
         );
+        // reversed ranges are allowed
+        let (p1, p2) = LoopScaling::new(100.0, 0.0).points(Unit::Milliamp);
+        let line = Line::from((&p1, &p2));
+        assert_eq!(-6.25, line.slope.unwrap());
+        // the inputs follow the session unit
+        let (p1, _) = LoopScaling::new(0.0, 10.0).points(Unit::Ampere);
+        assert_eq!(0.004, p1.get_val()["in"]);
+    }
+
+    #[test]
+    fn plc_scaling() {
+        // a 0-10 bar transmitter on a card reading 4-20 mA as 0-27648
+        let line = Line::fit(&[(0.0, 0.0), (27648.0, 10.0)], &[1.0; 2]);
+        let scaling = PlcScaling::from_line(&line, (0.0, 27648.0)).unwrap();
+        assert_eq!((0.0, 10.0), (scaling.eng_min, scaling.eng_max));
+        // the same line over another raw range
+        let scaling = PlcScaling::from_line(&line, (-4864.0, 32511.0)).unwrap();
+        assert!((scaling.eng_min + 1.759259).abs() < 1e-6);
+        assert!((scaling.eng_max - 11.758898).abs() < 1e-6);
+        let (m, b) = scaling.line().get_val().unwrap();
+        assert!((m - 10.0 / 27648.0).abs() < 1e-15);
+        assert!(b.abs() < 1e-12);
+
+        let reversed = PlcScaling {
+            raw_min: 0.0,
+            raw_max: 100.0,
+            eng_min: 50.0,
+            eng_max: -50.0,
+        };
+        assert_eq!(Some((-1.0, 50.0)), reversed.line().get_val());
+        let empty = PlcScaling {
+            raw_max: 0.0,
+            ..reversed
+        };
+        assert_eq!(None, empty.line().get_val());
+        assert_eq!(None, PlcScaling::from_line(&Line::new(), (0.0, 1.0)));
+    }
+
+    #[test]
+    fn loop_status() {
+        assert_eq!(LoopStatus::FaultLow, LoopScaling::status(3.6));
+        assert_eq!(LoopStatus::UnderRange, LoopScaling::status(3.8));
+        assert_eq!(LoopStatus::Normal, LoopScaling::status(4.0));
+        assert_eq!(LoopStatus::Normal, LoopScaling::status(20.0));
+        assert_eq!(LoopStatus::OverRange, LoopScaling::status(20.5));
+        assert_eq!(LoopStatus::FaultHigh, LoopScaling::status(21.0));
+    }
+
+    #[test]
+    fn line_least_squares() {
+        let line = Line::fit(&[(0.0, 0.1), (1.0, 0.9), (2.0, 2.1), (3.0, 2.9)], &[1.0; 4]);
+        let (m, b) = line.get_val().unwrap();
+        assert!((m - 0.96).abs() < 1e-9);
+        assert!((b - 0.06).abs() < 1e-9);
+        // all the same input can't make a line
+        assert_eq!(
+            None,
+            Line::fit(&[(1.0, 0.0), (1.0, 1.0), (1.0, 2.0)], &[1.0; 3]).get_val()
+        );
+    }
+
+    #[test]
+    fn model_keys() {
+        // every kind comes back from its key, going round the whole cycle
+        let mut kind = ModelKind::Linear;
+        loop {
+            assert_eq!(Some(kind), ModelKind::parse(&kind.key()));
+            kind = kind.next();
+            if kind == ModelKind::Linear {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn fit_models() {
+        assert_eq!(
+            Err(CalcError::Singular),
+            Model::fit(
+                ModelKind::Linear,
+                &[(1.0, 0.0), (1.0, 5.0)],
+                None,
+                &FitOptions::default()
+            )
+            .map(|m| m.kind())
+        );
+        let model = Model::fit(
+            ModelKind::SteinhartHart,
+            &[(27950.0, 273.15), (9877.0, 298.15), (4714.0, 318.15)],
+            None,
+            &FitOptions::default(),
+        )
+        .unwrap();
+        let t = model
+            .get_corresponding_value(&MeasurementType::Input(9877.0))
+            .unwrap();
+        assert!((t - 298.15).abs() < 1e-6);
+        assert_eq!(
+            Err(CalcError::NotEnoughPoints { needed: 3, got: 2 }),
+            Model::fit(
+                ModelKind::SteinhartHart,
+                &[(1.0, 1.0), (2.0, 2.0)],
+                None,
+                &FitOptions::default()
+            )
+            .map(|m| m.kind())
+        );
+    }
+
+    #[test]
+    fn weighted_fit() {
+        // the last point is off, a big uncertainty on it keeps it from dragging the line
+        let points = [(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (3.0, 4.0)];
+        let weights = [1.0, 1.0, 1.0, 1e-6];
+        let opts = FitOptions::default();
+        let model = Model::fit(ModelKind::Linear, &points, Some(&weights), &opts).unwrap();
+        let (m, b) = model.get_line().unwrap();
+        assert!((m - 1.0).abs() < 1e-5);
+        assert!(b.abs() < 1e-5);
+        // u = 0.1 on every point: u(m) = 0.1 / sqrt(sum (x - mean)^2) = 0.1 / sqrt(5)
+        let [um, ub] = model
+            .covariance(&points, &[100.0; 4], true)
+            .unwrap()
+            .uncertainties();
+        assert!((um - 0.1 / 5f64.sqrt()).abs() < 1e-12);
+        assert!((ub - 0.1 * (14.0 / 20.0f64).sqrt()).abs() < 1e-12);
+        // two points unweighted leave nothing to estimate the scatter from
+        let two = Model::fit(ModelKind::Linear, &points[..2], None, &opts).unwrap();
+        assert_eq!(None, two.covariance(&points[..2], &[1.0; 2], false));
+    }
+
+    #[test]
+    fn deming_regression() {
+        let points = [(0.0, 0.2), (1.0, 0.8), (2.0, 2.3), (3.0, 2.9), (4.0, 4.1)];
+        let w = [1.0; 5];
+        let (ols, _) = Line::fit(&points, &w).get_val().unwrap();
+        // regressing x on y gives the other extreme
+        let swapped: Vec<(f64, f64)> = points.iter().map(|(x, y)| (*y, *x)).collect();
+        let (inverse, _) = Line::fit(&swapped, &w).get_val().unwrap();
+        let deming = |ratio: f64| Line::fit_deming(&points, &w, ratio).get_val().unwrap();
+        // a huge ratio means exact inputs, a tiny one exact outputs
+        assert!((deming(1e9).0 - ols).abs() < 1e-6);
+        assert!((deming(1e-9).0 - 1.0 / inverse).abs() < 1e-6);
+        let (m, b) = deming(1.0);
+        assert!(m > ols && m < 1.0 / inverse);
+        // the line goes through the centroid
+        assert!((2.0 * m + b - 2.06).abs() < 1e-12);
+        // and the orthogonal fit is Deming with equal variances
+        let options = FitOptions {
+            regression: Regression::Orthogonal,
+            ..FitOptions::default()
+        };
+        let model = Model::fit(ModelKind::Linear, &points, None, &options).unwrap();
+        assert_eq!(Some((m, b)), model.get_line());
+        // a flat line is fine, a vertical one isn't
+        let flat = Line::fit_deming(&[(0.0, 1.0), (1.0, 1.0), (2.0, 1.0)], &[1.0; 3], 1.0);
+        assert_eq!(Some((0.0, 1.0)), flat.get_val());
+        let vertical = Line::fit_deming(&[(1.0, 0.0), (1.0, 1.0), (1.0, 2.0)], &[1.0; 3], 1.0);
+        assert_eq!(None, vertical.get_val());
+    }
+
+    #[test]
+    fn bands() {
+        let points = [(0.0, 0.1), (1.0, 0.9), (2.0, 2.1), (3.0, 2.9)];
+        let w = [1.0; 4];
+        let opts = FitOptions::default();
+        let model = Model::fit(ModelKind::Linear, &points, None, &opts).unwrap();
+        let cov = model.covariance(&points, &w, false).unwrap();
+        // residuals are +-0.04 and +-0.12 about the line, so s^2 = 0.032 / 2
+        let s2: f64 = 0.016;
+        assert!((cov.observation - s2).abs() < 1e-12);
+        assert_eq!(Some(2), cov.dof);
+        // the textbook band for a line: s sqrt(1/n + (x - mean)^2 / Sxx), and 1 more for a reading
+        for x in [-1.0, 1.5, 4.0] {
+            let (fitted, reading) = model.band(&cov, x).unwrap();
+            let lever: f64 = 0.25 + (x - 1.5f64).powi(2) / 5.0;
+            assert!((fitted - (s2 * lever).sqrt()).abs() < 1e-12);
+            assert!((reading - (s2 * (1.0 + lever)).sqrt()).abs() < 1e-12);
+        }
+        assert!((cov.coverage(0.95) - 4.3027).abs() < 1e-4);
     }
 }
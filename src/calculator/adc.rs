@@ -0,0 +1,120 @@
+use crate::units::Unit;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Polarity {
+    // codes 0..2^n-1 cover 0..Vref
+    Unipolar,
+    // two's complement codes -2^(n-1)..2^(n-1)-1 cover -Vref..Vref
+    Bipolar,
+}
+
+// An ADC front end so the input can be handled as codes or as volts
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Adc {
+    pub bits: u32,
+    pub vref: f64,
+    pub polarity: Polarity,
+    // code the converter reads at 0 V, i.e. its offset error in counts
+    pub offset: f64,
+}
+
+impl Adc {
+    pub fn new(bits: u32, vref: f64) -> Self {
+        Adc {
+            bits,
+            vref,
+            polarity: Polarity::Unipolar,
+            offset: 0.0,
+        }
+    }
+
+    // Volts per code
+    pub fn lsb(&self) -> f64 {
+        let levels = 2f64.powi(self.bits as i32);
+        match self.polarity {
+            Polarity::Unipolar => self.vref / levels,
+            Polarity::Bipolar => 2.0 * self.vref / levels,
+        }
+    }
+
+    // Lowest and highest code the converter can output
+    pub fn code_range(&self) -> (f64, f64) {
+        code_range(self.bits, self.polarity)
+    }
+
+    pub fn code_to_volts(&self, code: f64) -> f64 {
+        (code - self.offset) * self.lsb()
+    }
+
+    // Ideal (unrounded) code for a voltage
+    pub fn volts_to_code(&self, volts: f64) -> f64 {
+        volts / self.lsb() + self.offset
+    }
+
+    // The code the converter would actually report, rounded and clamped to its range
+    pub fn quantize(&self, volts: f64) -> f64 {
+        let (min, max) = self.code_range();
+        self.volts_to_code(volts).round().clamp(min, max)
+    }
+
+    // Convert between a voltage unit and counts through the converter. None if neither side is counts
+    pub fn convert(&self, value: f64, from: Unit, to: Unit) -> Option<f64> {
+        match (from, to) {
+            (Unit::Count, Unit::Count) => Some(value),
+            (Unit::Count, _) => from_volts(self.code_to_volts(value), to),
+            (_, Unit::Count) => Some(self.volts_to_code(from.convert(value, Unit::Volt).ok()?)),
+            _ => None,
+        }
+    }
+}
+
+// Codes an n bit converter covers, for DACs as well
+pub fn code_range(bits: u32, polarity: Polarity) -> (f64, f64) {
+    let levels = 2f64.powi(bits as i32);
+    match polarity {
+        Polarity::Unipolar => (0.0, levels - 1.0),
+        Polarity::Bipolar => (-levels / 2.0, levels / 2.0 - 1.0),
+    }
+}
+
+fn from_volts(volts: f64, to: Unit) -> Option<f64> {
+    Unit::Volt.convert(volts, to).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Adc, Polarity};
+    use crate::units::Unit;
+
+    #[test]
+    fn unipolar() {
+        let adc = Adc::new(12, 4.096);
+        assert_eq!(0.001, adc.lsb());
+        assert_eq!((0.0, 4095.0), adc.code_range());
+        assert_eq!(1.5, adc.code_to_volts(1500.0));
+        assert_eq!(4095.0, adc.quantize(5.0));
+        assert_eq!(1235.0, adc.quantize(1.2345));
+    }
+
+    #[test]
+    fn bipolar_with_offset() {
+        let mut adc = Adc::new(16, 2.5);
+        adc.polarity = Polarity::Bipolar;
+        adc.offset = 3.0;
+        assert_eq!((-32768.0, 32767.0), adc.code_range());
+        assert_eq!(3.0, adc.volts_to_code(0.0));
+        assert_eq!(-32768.0, adc.quantize(-3.0));
+        assert!((adc.code_to_volts(13110.0) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn convert_units() {
+        let adc = Adc::new(10, 1.024);
+        assert_eq!(Some(500.0), adc.convert(0.5, Unit::Volt, Unit::Count));
+        assert_eq!(
+            Some(250.0),
+            adc.convert(250.0, Unit::Count, Unit::Millivolt)
+        );
+        assert_eq!(None, adc.convert(1.0, Unit::Volt, Unit::Millivolt));
+    }
+}
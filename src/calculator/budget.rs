@@ -0,0 +1,229 @@
+use super::stats::{normal_quantile, t_quantile};
+
+// Coverage probability of the expanded uncertainty, k = 2 for a normal distribution
+pub const COVERAGE: f64 = 0.9545;
+
+// How a contributor's value is spread, which sets what it is divided by to get a standard uncertainty
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Distribution {
+    // quoted as an expanded uncertainty with this coverage factor, e.g. a certificate's k = 2
+    Normal(f64),
+    // the value is the half width of the limits, e.g. a meter's accuracy spec or half a digit
+    Rectangular,
+    Triangular,
+}
+
+impl Distribution {
+    pub fn divisor(&self) -> f64 {
+        match self {
+            Distribution::Normal(k) => *k,
+            Distribution::Rectangular => 3f64.sqrt(),
+            Distribution::Triangular => 6f64.sqrt(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Distribution::Normal(k) => format!("normal (k={})", k),
+            Distribution::Rectangular => "rectangular".to_owned(),
+            Distribution::Triangular => "triangular".to_owned(),
+        }
+    }
+
+    // "rect", "triangular", "normal" (k = 1), "normal 2", "normal,k=2" or just "k=2"
+    pub fn parse(s: &str) -> Option<Distribution> {
+        let s = s.trim().to_ascii_lowercase();
+        let (name, k) = match s.split_once(|c: char| c == ',' || c.is_whitespace()) {
+            Some((name, k)) => (name, Some(k.trim())),
+            None => (s.as_str(), None),
+        };
+        let coverage = |k: &str| {
+            k.trim_start_matches("k")
+                .trim_start_matches('=')
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|k| *k > 0.0)
+        };
+        match (name, k) {
+            ("normal" | "gaussian" | "n", None) => Some(Distribution::Normal(1.0)),
+            ("normal" | "gaussian" | "n", Some(k)) => coverage(k).map(Distribution::Normal),
+            ("rectangular" | "rect" | "uniform" | "r", None) => Some(Distribution::Rectangular),
+            ("triangular" | "tri" | "t", None) => Some(Distribution::Triangular),
+            (k, None) if k.starts_with('k') => coverage(k).map(Distribution::Normal),
+            _ => None,
+        }
+    }
+}
+
+// Which reading a contributor disturbs. Input contributors reach the output through the model's slope
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Side {
+    Input,
+    Output,
+}
+
+impl Side {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Side::Input => "in",
+            Side::Output => "out",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Side> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "in" | "input" | "i" | "x" => Some(Side::Input),
+            "out" | "output" | "o" | "y" => Some(Side::Output),
+            _ => None,
+        }
+    }
+}
+
+// One line of an uncertainty budget as it was entered. The value is in the session unit of its side
+#[derive(Clone, PartialEq, Debug)]
+pub struct Contributor {
+    pub name: String,
+    pub side: Side,
+    pub value: f64,
+    pub distribution: Distribution,
+    // degrees of freedom, None for infinite (most type B estimates)
+    pub dof: Option<f64>,
+}
+
+impl Contributor {
+    pub fn new(name: &str, side: Side, distribution: Distribution) -> Self {
+        Contributor {
+            name: name.to_owned(),
+            side,
+            value: 0.0,
+            distribution,
+            dof: None,
+        }
+    }
+
+    pub fn standard(&self) -> f64 {
+        self.value / self.distribution.divisor()
+    }
+}
+
+// A standard uncertainty and how strongly it reaches the result
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Term {
+    pub standard: f64,
+    pub sensitivity: f64,
+    pub dof: Option<f64>,
+}
+
+impl Term {
+    pub fn contribution(&self) -> f64 {
+        (self.sensitivity * self.standard).abs()
+    }
+}
+
+// Combined standard uncertainty with its effective degrees of freedom and coverage factor
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Combined {
+    pub standard: f64,
+    pub dof: Option<f64>,
+    pub k: f64,
+}
+
+impl Combined {
+    pub fn expanded(&self) -> f64 {
+        self.k * self.standard
+    }
+}
+
+/*
+ * Combine uncorrelated terms the GUM way: the root sum of squares of the contributions, effective
+ * degrees of freedom from the Welch-Satterthwaite formula and the coverage factor from Student's t
+ * at COVERAGE (the normal distribution when every term has infinite degrees of freedom)
+ */
+pub fn combine(terms: &[Term]) -> Combined {
+    let variance: f64 = terms.iter().map(|t| t.contribution().powi(2)).sum();
+    let finite: Vec<(f64, f64)> = terms
+        .iter()
+        .filter_map(|t| t.dof.map(|dof| (t.contribution(), dof)))
+        .filter(|(c, dof)| *c > 0.0 && *dof > 0.0)
+        .collect();
+    let dof = if finite.is_empty() {
+        None
+    } else {
+        Some(variance.powi(2) / finite.iter().map(|(c, dof)| c.powi(4) / dof).sum::<f64>())
+    };
+    let p = (1.0 + COVERAGE) / 2.0;
+    let k = match dof {
+        Some(dof) => t_quantile(p, dof),
+        None => normal_quantile(p),
+    };
+    Combined {
+        standard: variance.sqrt(),
+        dof,
+        k,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{combine, Contributor, Distribution, Side, Term};
+
+    #[test]
+    fn distributions() {
+        assert_eq!(
+            Some(Distribution::Normal(2.0)),
+            Distribution::parse("normal, k=2")
+        );
+        assert_eq!(Some(Distribution::Normal(2.0)), Distribution::parse("k=2"));
+        assert_eq!(
+            Some(Distribution::Normal(1.0)),
+            Distribution::parse("Normal")
+        );
+        assert_eq!(Some(Distribution::Rectangular), Distribution::parse("rect"));
+        assert_eq!(Some(Distribution::Triangular), Distribution::parse("tri"));
+        assert_eq!(None, Distribution::parse("normal,0"));
+        assert_eq!(None, Distribution::parse("lognormal"));
+        // a ±0.05 spec read as rectangular
+        let mut dmm = Contributor::new("DMM", Side::Input, Distribution::Rectangular);
+        dmm.value = 0.05;
+        assert!((dmm.standard() - 0.028868).abs() < 1e-6);
+    }
+
+    #[test]
+    fn gum_combination() {
+        // everything type B: plain root sum of squares and k = 2
+        let b = combine(&[
+            Term {
+                standard: 0.3,
+                sensitivity: 1.0,
+                dof: None,
+            },
+            Term {
+                standard: 0.2,
+                sensitivity: -2.0,
+                dof: None,
+            },
+        ]);
+        assert!((b.standard - 0.5).abs() < 1e-12);
+        assert_eq!(None, b.dof);
+        assert!((b.k - 2.0).abs() < 1e-3);
+        // a repeatability of 5 readings brings the degrees of freedom down
+        let a = combine(&[
+            Term {
+                standard: 0.3,
+                sensitivity: 1.0,
+                dof: None,
+            },
+            Term {
+                standard: 0.4,
+                sensitivity: 1.0,
+                dof: Some(4.0),
+            },
+        ]);
+        // 0.5^4 / (0.4^4 / 4) = 9.77
+        let dof = a.dof.unwrap();
+        assert!((dof - 9.765_625).abs() < 1e-9);
+        assert!(a.k > 2.2 && a.k < 2.35);
+        assert!((a.expanded() - a.k * 0.5).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,279 @@
+use super::linalg;
+use super::CalcError;
+
+// Two parameter curves for sensors that aren't straight lines, fitted in the session units
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CurveShape {
+    // y = a e^(b x)
+    Exponential,
+    // y = a ln(x) + b
+    Logarithmic,
+    // y = a x^b
+    Power,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Curve {
+    pub shape: CurveShape,
+    pub a: f64,
+    pub b: f64,
+}
+
+impl CurveShape {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CurveShape::Exponential => "Exponential",
+            CurveShape::Logarithmic => "Logarithmic",
+            CurveShape::Power => "Power law",
+        }
+    }
+}
+
+impl Curve {
+    /*
+     * Fit by least squares on the linearized form (ln y against x for the exponential, y against
+     * ln x for the log and ln y against ln x for the power law). That minimises the error in the
+     * transformed space, `refine` carries on with Levenberg-Marquardt on the error in y itself.
+     * `w` weights the errors in y, which are scaled into ln y for the linearized fit
+     */
+    pub fn fit(
+        shape: CurveShape,
+        points: &[(f64, f64)],
+        w: &[f64],
+        refine: bool,
+    ) -> Result<Self, CalcError> {
+        if points.len() < 2 {
+            return Err(CalcError::NotEnoughPoints {
+                needed: 2,
+                got: points.len(),
+            });
+        }
+        let (log_x, log_y) = match shape {
+            CurveShape::Exponential => (false, true),
+            CurveShape::Logarithmic => (true, false),
+            CurveShape::Power => (true, true),
+        };
+        if log_x && points.iter().any(|(x, _)| *x <= 0.0) {
+            return Err(CalcError::OutOfDomain(
+                "inputs must be positive to take their log",
+            ));
+        }
+        if log_y && points.iter().any(|(_, y)| *y <= 0.0) {
+            return Err(CalcError::OutOfDomain(
+                "outputs must be positive to take their log",
+            ));
+        }
+        let rows: Vec<Vec<f64>> = points
+            .iter()
+            .map(|(x, _)| vec![if log_x { x.ln() } else { *x }, 1.0])
+            .collect();
+        let y: Vec<f64> = points
+            .iter()
+            .map(|(_, y)| if log_y { y.ln() } else { *y })
+            .collect();
+        // an error of u in y is u / y in ln(y)
+        let w_lin: Vec<f64> = points
+            .iter()
+            .zip(w)
+            .map(|((_, y), w)| if log_y { w * y * y } else { *w })
+            .collect();
+        let k = linalg::weighted_least_squares(&rows, &y, &w_lin).ok_or(CalcError::Singular)?;
+        let mut curve = match shape {
+            // ln y = ln a + b x
+            CurveShape::Exponential => Curve {
+                shape,
+                a: k[1].exp(),
+                b: k[0],
+            },
+            CurveShape::Logarithmic => Curve {
+                shape,
+                a: k[0],
+                b: k[1],
+            },
+            // ln y = ln a + b ln x
+            CurveShape::Power => Curve {
+                shape,
+                a: k[1].exp(),
+                b: k[0],
+            },
+        };
+        if refine {
+            curve.refine(points, w);
+        }
+        Ok(curve)
+    }
+
+    // Levenberg-Marquardt on the sum of squared output errors, starting from the linearized fit
+    fn refine(&mut self, points: &[(f64, f64)], w: &[f64]) {
+        let mut cost = self.cost(points, w);
+        let mut lambda = 1e-3;
+        for _ in 0..200 {
+            let mut jtj = vec![vec![0.0; 2]; 2];
+            let mut jtr = vec![0.0; 2];
+            for ((x, y), w) in points.iter().zip(w) {
+                let (f, grad) = self.eval_with_grad(*x);
+                let r = y - f;
+                for i in 0..2 {
+                    jtr[i] += w * grad[i] * r;
+                    for j in 0..2 {
+                        jtj[i][j] += w * grad[i] * grad[j];
+                    }
+                }
+            }
+            // try smaller and smaller steps until one improves things
+            let improved = loop {
+                let mut damped = jtj.clone();
+                for (i, row) in damped.iter_mut().enumerate() {
+                    row[i] += lambda * jtj[i][i].max(1e-300);
+                }
+                let step = linalg::solve(damped, jtr.clone());
+                let candidate = step.map(|d| Curve {
+                    a: self.a + d[0],
+                    b: self.b + d[1],
+                    ..*self
+                });
+                match candidate.map(|c| (c, c.cost(points, w))) {
+                    Some((c, new_cost)) if new_cost.is_finite() && new_cost <= cost => {
+                        lambda = (lambda / 10.0).max(1e-12);
+                        *self = c;
+                        let gain = cost - new_cost;
+                        cost = new_cost;
+                        break gain > 1e-15 * cost.max(1e-300);
+                    }
+                    _ if lambda < 1e12 => lambda *= 10.0,
+                    _ => break false,
+                }
+            };
+            if !improved {
+                return;
+            }
+        }
+    }
+
+    // Weighted sum of squared output errors
+    fn cost(&self, points: &[(f64, f64)], w: &[f64]) -> f64 {
+        points
+            .iter()
+            .zip(w)
+            .map(|((x, y), w)| w * (y - self.eval_with_grad(*x).0).powi(2))
+            .sum()
+    }
+
+    // Derivatives of the output with respect to a and b
+    pub fn gradient(&self, x: f64) -> [f64; 2] {
+        self.eval_with_grad(x).1
+    }
+
+    // The value and its derivatives with respect to a and b
+    fn eval_with_grad(&self, x: f64) -> (f64, [f64; 2]) {
+        match self.shape {
+            CurveShape::Exponential => {
+                let e = (self.b * x).exp();
+                (self.a * e, [e, self.a * x * e])
+            }
+            CurveShape::Logarithmic => (self.a * x.ln() + self.b, [x.ln(), 1.0]),
+            CurveShape::Power => {
+                let p = x.powf(self.b);
+                (self.a * p, [p, self.a * p * x.ln()])
+            }
+        }
+    }
+
+    pub fn output(&self, x: f64) -> Result<f64, CalcError> {
+        if self.shape != CurveShape::Exponential && x <= 0.0 {
+            return Err(CalcError::OutOfDomain("input must be positive"));
+        }
+        Ok(self.eval_with_grad(x).0)
+    }
+
+    pub fn input(&self, y: f64) -> Result<f64, CalcError> {
+        let x = match self.shape {
+            CurveShape::Exponential => (y / self.a).ln() / self.b,
+            CurveShape::Logarithmic => ((y - self.b) / self.a).exp(),
+            CurveShape::Power => (y / self.a).powf(1.0 / self.b),
+        };
+        if x.is_finite() {
+            Ok(x)
+        } else {
+            Err(CalcError::OutOfDomain("output is outside the curve"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Curve, CurveShape};
+    use crate::calculator::CalcError;
+
+    #[test]
+    fn exact_curves() {
+        for (shape, f) in [
+            (
+                CurveShape::Exponential,
+                (|x: f64| 2.0 * (0.5 * x).exp()) as fn(f64) -> f64,
+            ),
+            (CurveShape::Logarithmic, |x: f64| 3.0 * x.ln() - 1.0),
+            (CurveShape::Power, |x: f64| 0.5 * x.powf(1.5)),
+        ] {
+            let points: Vec<(f64, f64)> =
+                [1.0, 2.0, 4.0, 8.0].iter().map(|x| (*x, f(*x))).collect();
+            for refine in [false, true] {
+                let curve = Curve::fit(shape, &points, &[1.0; 4], refine).unwrap();
+                for (x, y) in &points {
+                    assert!((curve.output(*x).unwrap() - y).abs() < 1e-9 * y.abs().max(1.0));
+                    assert!((curve.input(*y).unwrap() - x).abs() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn refinement_lowers_output_error() {
+        // a photodiode-ish power law with noise on the outputs
+        let points = [
+            (1.0, 2.1),
+            (2.0, 7.6),
+            (3.0, 16.9),
+            (4.0, 31.0),
+            (5.0, 52.0),
+        ];
+        let w = [1.0; 5];
+        let linearized = Curve::fit(CurveShape::Power, &points, &w, false).unwrap();
+        let refined = Curve::fit(CurveShape::Power, &points, &w, true).unwrap();
+        // weighting ln y by y^2 gets the linearized fit close, refining finishes the job
+        assert!(refined.cost(&points, &w) < linearized.cost(&points, &w));
+        // still a square law, the big readings just count for more
+        assert!(refined.b > 2.0 && refined.b < 2.3);
+    }
+
+    #[test]
+    fn domain_checks() {
+        assert_eq!(
+            Err(CalcError::OutOfDomain(
+                "inputs must be positive to take their log"
+            )),
+            Curve::fit(
+                CurveShape::Logarithmic,
+                &[(0.0, 1.0), (1.0, 2.0)],
+                &[1.0; 2],
+                false
+            )
+        );
+        assert!(Curve::fit(
+            CurveShape::Exponential,
+            &[(0.0, -1.0), (1.0, 2.0)],
+            &[1.0; 2],
+            false
+        )
+        .is_err());
+        let power = Curve::fit(
+            CurveShape::Power,
+            &[(1.0, 1.0), (2.0, 4.0)],
+            &[1.0; 2],
+            false,
+        )
+        .unwrap();
+        assert!(power.output(-1.0).is_err());
+        assert!(power.input(-4.0).is_err());
+    }
+}
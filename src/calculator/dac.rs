@@ -0,0 +1,50 @@
+use super::adc::{self, Polarity};
+
+/*
+ * A DAC the calibration drives, for output calibrations: the points are the code commanded and
+ * the physical value measured, and what is wanted is the code that gives a value
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Dac {
+    pub bits: u32,
+    pub polarity: Polarity,
+}
+
+impl Dac {
+    pub fn new(bits: u32) -> Self {
+        Dac {
+            bits,
+            polarity: Polarity::Unipolar,
+        }
+    }
+
+    // Lowest and highest code the converter takes
+    pub fn code_range(&self) -> (f64, f64) {
+        adc::code_range(self.bits, self.polarity)
+    }
+
+    // The code to command for an ideal one, to the nearest step and kept in range
+    pub fn quantize(&self, code: f64) -> f64 {
+        let (min, max) = self.code_range();
+        code.round().clamp(min, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dac, Polarity};
+
+    #[test]
+    fn codes() {
+        let mut dac = Dac::new(12);
+        assert_eq!((0.0, 4095.0), dac.code_range());
+        assert_eq!(2048.0, dac.quantize(2047.5));
+        assert_eq!(2047.0, dac.quantize(2047.49));
+        assert_eq!(0.0, dac.quantize(-3.2));
+        assert_eq!(4095.0, dac.quantize(5000.0));
+        dac.polarity = Polarity::Bipolar;
+        assert_eq!((-2048.0, 2047.0), dac.code_range());
+        assert_eq!(-13.0, dac.quantize(-12.5));
+        assert_eq!(-2048.0, dac.quantize(-3000.0));
+    }
+}
@@ -0,0 +1,83 @@
+use super::Line;
+
+// Days in a year, drift rates are quoted per year
+pub const YEAR: f64 = 365.25;
+
+// One calibration of a sensor: when, in days since 1970, and the line it was left with
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Calibration {
+    pub day: f64,
+    pub slope: f64,
+    pub intercept: f64,
+}
+
+// How fast the slope and intercept move, per day, from straight lines through the history
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Drift {
+    pub slope: f64,
+    pub intercept: f64,
+}
+
+impl Drift {
+    // Change per day of the output read at input x
+    pub fn output_rate(&self, x: f64) -> f64 {
+        self.slope * x + self.intercept
+    }
+
+    // Days until the output at x has moved by `limit`, None when it isn't moving
+    pub fn days_to(&self, x: f64, limit: f64) -> Option<f64> {
+        let rate = self.output_rate(x).abs();
+        if rate > 0.0 && limit.is_finite() {
+            Some(limit / rate)
+        } else {
+            None
+        }
+    }
+}
+
+/*
+ * Least squares trend of the slope and of the intercept over time. Needs calibrations on at least
+ * two different days; two give the straight change between them
+ */
+pub fn trend(history: &[Calibration]) -> Option<Drift> {
+    let w = vec![1.0; history.len()];
+    let fit = |value: fn(&Calibration) -> f64| {
+        let pairs: Vec<(f64, f64)> = history.iter().map(|c| (c.day, value(c))).collect();
+        Line::fit(&pairs, &w).slope
+    };
+    Some(Drift {
+        slope: fit(|c| c.slope)?,
+        intercept: fit(|c| c.intercept)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{trend, Calibration, YEAR};
+
+    #[test]
+    fn drift_rates() {
+        let at = |day: f64, slope: f64, intercept: f64| Calibration {
+            day,
+            slope,
+            intercept,
+        };
+        assert_eq!(None, trend(&[at(100.0, 2.0, 0.1)]));
+        // the same day twice says nothing about time
+        assert_eq!(None, trend(&[at(100.0, 2.0, 0.1), at(100.0, 2.1, 0.1)]));
+        // the slope gains about 0.01 a year and the intercept loses 0.2, with a bit of scatter
+        let history = [
+            at(0.0, 2.000, 0.5),
+            at(YEAR / 2.0, 2.006, 0.4),
+            at(YEAR, 2.009, 0.3),
+            at(1.5 * YEAR, 2.015, 0.2),
+        ];
+        let drift = trend(&history).unwrap();
+        assert!((drift.slope * YEAR - 0.0096).abs() < 1e-9);
+        assert!((drift.intercept * YEAR + 0.2).abs() < 1e-9);
+        // at an input of 10 the output moves 0.096 - 0.2 = -0.104 a year, so 0.052 takes half a year
+        assert!((drift.output_rate(10.0) * YEAR + 0.104).abs() < 1e-9);
+        assert!((drift.days_to(10.0, 0.052).unwrap() - YEAR / 2.0).abs() < 1e-6);
+        assert_eq!(None, drift.days_to(10.0, f64::NAN));
+    }
+}
@@ -0,0 +1,110 @@
+// Small dense solvers for the fits. Matrices are row major Vec<Vec<f64>>
+
+// Solve A x = b with Gaussian elimination and partial pivoting. None if A is singular
+pub fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        // bring the largest pivot up to keep things stable
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-300 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let f = a[row][col] / pivot_row[col];
+            for (x, p) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= f * p;
+            }
+            b[row] -= f * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let s: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - s) / a[row][row];
+    }
+    if x.iter().all(|v| v.is_finite()) {
+        Some(x)
+    } else {
+        None
+    }
+}
+
+// Weighted linear least squares: minimise sum w_i (y_i - rows_i . x)^2 through the normal equations
+pub fn weighted_least_squares(rows: &[Vec<f64>], y: &[f64], w: &[f64]) -> Option<Vec<f64>> {
+    let n = rows.first()?.len();
+    if rows.len() < n {
+        return None;
+    }
+    let mut ata = vec![vec![0.0; n]; n];
+    let mut aty = vec![0.0; n];
+    for ((row, yi), wi) in rows.iter().zip(y).zip(w) {
+        for i in 0..n {
+            aty[i] += wi * row[i] * yi;
+            for j in 0..n {
+                ata[i][j] += wi * row[i] * row[j];
+            }
+        }
+    }
+    solve(ata, aty)
+}
+
+// Inverse of a square matrix, a column at a time. None if it is singular
+pub fn invert(a: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let n = a.len();
+    let mut inv = vec![vec![0.0; n]; n];
+    for col in 0..n {
+        let mut e = vec![0.0; n];
+        e[col] = 1.0;
+        let x = solve(a.clone(), e)?;
+        for (row, v) in inv.iter_mut().zip(x) {
+            row[col] = v;
+        }
+    }
+    Some(inv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{invert, solve, weighted_least_squares};
+
+    #[test]
+    fn solve_3x3() {
+        let a = vec![
+            vec![2.0, 1.0, -1.0],
+            vec![-3.0, -1.0, 2.0],
+            vec![-2.0, 1.0, 2.0],
+        ];
+        let x = solve(a, vec![8.0, -11.0, -3.0]).unwrap();
+        assert!((x[0] - 2.0).abs() < 1e-12);
+        assert!((x[1] - 3.0).abs() < 1e-12);
+        assert!((x[2] + 1.0).abs() < 1e-12);
+        assert_eq!(
+            None,
+            solve(vec![vec![1.0, 2.0], vec![2.0, 4.0]], vec![1.0, 2.0])
+        );
+    }
+
+    #[test]
+    fn fit_line() {
+        // y = 2x + 1 with a bit of noise
+        let rows: Vec<Vec<f64>> = (0..4).map(|x| vec![x as f64, 1.0]).collect();
+        let y = [1.1, 2.9, 5.1, 6.9];
+        let x = weighted_least_squares(&rows, &y, &[1.0; 4]).unwrap();
+        assert!((x[0] - 1.96).abs() < 1e-9);
+        assert!((x[1] - 1.06).abs() < 1e-9);
+    }
+
+    #[test]
+    fn invert_2x2() {
+        let inv = invert(vec![vec![4.0, 7.0], vec![2.0, 6.0]]).unwrap();
+        let expected = [[0.6, -0.7], [-0.2, 0.4]];
+        for (row, e) in inv.iter().zip(expected) {
+            for (v, e) in row.iter().zip(e) {
+                assert!((v - e).abs() < 1e-12);
+            }
+        }
+    }
+}
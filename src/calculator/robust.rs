@@ -0,0 +1,336 @@
+use super::CalcError;
+
+// Studentized residuals beyond this flag a point as an outlier
+pub const OUTLIER_LIMIT: f64 = 3.0;
+// RANSAC tries every subset of the points up to this many, then samples this many
+const MAX_CANDIDATES: usize = 5000;
+
+// Fits that limit how far a bad reading can drag the model
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Robust {
+    Off,
+    // M-estimators, reweighting the points by their residuals until the fit settles
+    Huber,
+    Tukey,
+    // fit minimal subsets and keep the one most of the points agree with
+    Ransac,
+}
+
+impl Robust {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Robust::Off => "off",
+            Robust::Huber => "Huber",
+            Robust::Tukey => "Tukey",
+            Robust::Ransac => "RANSAC",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Robust> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "none" => Some(Robust::Off),
+            "huber" => Some(Robust::Huber),
+            "tukey" | "bisquare" => Some(Robust::Tukey),
+            "ransac" => Some(Robust::Ransac),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Robust {
+        match self {
+            Robust::Off => Robust::Huber,
+            Robust::Huber => Robust::Tukey,
+            Robust::Tukey => Robust::Ransac,
+            Robust::Ransac => Robust::Off,
+        }
+    }
+
+    /*
+     * Fit with the points reweighted by this method. `fit` takes a multiplier for each point's weight
+     * (0 leaves the point out) and `residuals` gives the output errors of a fitted model.
+     * `w` are the points' own weights and `k` the smallest number of points the model can be
+     * fitted to. Returns the model and the multipliers it ended up with
+     */
+    pub fn fit<M>(
+        &self,
+        w: &[f64],
+        k: usize,
+        fit: impl Fn(&[f64]) -> Result<M, CalcError>,
+        residuals: impl Fn(&M) -> Vec<f64>,
+    ) -> Result<(M, Vec<f64>), CalcError> {
+        let ones = vec![1.0; w.len()];
+        // with no spare points there is nothing to vote against a bad one
+        if *self == Robust::Off || w.len() <= k {
+            return Ok((fit(&ones)?, ones));
+        }
+        match self {
+            Robust::Ransac => ransac(w, k, fit, residuals),
+            _ => self.reweight(w, fit, residuals),
+        }
+    }
+
+    // Iteratively reweighted least squares, the scale comes from the median absolute residual
+    fn reweight<M>(
+        &self,
+        w: &[f64],
+        fit: impl Fn(&[f64]) -> Result<M, CalcError>,
+        residuals: impl Fn(&M) -> Vec<f64>,
+    ) -> Result<(M, Vec<f64>), CalcError> {
+        let mut mult = vec![1.0; w.len()];
+        let mut model = fit(&mult)?;
+        for _ in 0..50 {
+            let u = standardize(&residuals(&model), w);
+            let s = 1.4826 * median(u.iter().map(|u| u.abs()).collect());
+            if s == 0.0 {
+                break;
+            }
+            let next: Vec<f64> = u.iter().map(|u| self.weight(u / s)).collect();
+            let change = next
+                .iter()
+                .zip(&mult)
+                .fold(0.0, |acc: f64, (a, b)| acc.max((a - b).abs()));
+            // Tukey can throw away too much to fit, keep the last model that worked
+            match fit(&next) {
+                Ok(m) => {
+                    model = m;
+                    mult = next;
+                }
+                Err(_) => break,
+            }
+            if change < 1e-6 {
+                break;
+            }
+        }
+        Ok((model, mult))
+    }
+
+    // Weight for a residual of u scale units, with the usual 95% efficiency tuning constants
+    fn weight(&self, u: f64) -> f64 {
+        match self {
+            Robust::Huber if u.abs() > 1.345 => 1.345 / u.abs(),
+            Robust::Tukey if u.abs() >= 4.685 => 0.0,
+            Robust::Tukey => (1.0 - (u / 4.685).powi(2)).powi(2),
+            _ => 1.0,
+        }
+    }
+}
+
+/*
+ * RANSAC with the least median of squares choosing the candidate, so there is no threshold to
+ * set: the median residual of the best candidate gives the scale, points within 2.5 of it are
+ * inliers and the model is refitted to those
+ */
+fn ransac<M>(
+    w: &[f64],
+    k: usize,
+    fit: impl Fn(&[f64]) -> Result<M, CalcError>,
+    residuals: impl Fn(&M) -> Vec<f64>,
+) -> Result<(M, Vec<f64>), CalcError> {
+    let n = w.len();
+    let mut best: Option<(f64, Vec<f64>)> = None;
+    for subset in subsets(n, k) {
+        let mut mult = vec![0.0; n];
+        for i in subset {
+            mult[i] = 1.0;
+        }
+        let model = match fit(&mult) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        let u = standardize(&residuals(&model), w);
+        let med = median(u.iter().map(|u| u * u).collect());
+        if med.is_finite() && best.as_ref().is_none_or(|(b, _)| med < *b) {
+            best = Some((med, u));
+        }
+    }
+    let (med, u) = best.ok_or(CalcError::Singular)?;
+    // small sample correction from Rousseeuw and Leroy
+    let s = 1.4826 * (1.0 + 5.0 / (n - k) as f64) * med.sqrt();
+    let inliers: Vec<f64> = u
+        .iter()
+        .map(|u| if u.abs() <= 2.5 * s { 1.0 } else { 0.0 })
+        .collect();
+    Ok((fit(&inliers)?, inliers))
+}
+
+// Residuals in units of each point's own uncertainty
+fn standardize(e: &[f64], w: &[f64]) -> Vec<f64> {
+    e.iter().zip(w).map(|(e, w)| e * w.sqrt()).collect()
+}
+
+fn median(mut v: Vec<f64>) -> f64 {
+    if v.is_empty() {
+        return f64::NAN;
+    }
+    v.sort_by(|a, b| a.total_cmp(b));
+    let mid = v.len() / 2;
+    if v.len().is_multiple_of(2) {
+        (v[mid - 1] + v[mid]) / 2.0
+    } else {
+        v[mid]
+    }
+}
+
+// Every k of n indices while there aren't too many, otherwise a fixed pseudo-random sample of them
+fn subsets(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let mut all = Vec::new();
+    let mut combo: Vec<usize> = (0..k).collect();
+    while all.len() < MAX_CANDIDATES {
+        all.push(combo.clone());
+        // step to the next combination in lexicographic order
+        let Some(i) = (0..k).rev().find(|i| combo[*i] < n - k + i) else {
+            return all;
+        };
+        combo[i] += 1;
+        for j in i + 1..k {
+            combo[j] = combo[j - 1] + 1;
+        }
+    }
+    // too many to try them all, an LCG keeps the sample the same from run to run
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = |bound: usize| {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 33) as usize % bound
+    };
+    (0..MAX_CANDIDATES)
+        .map(|_| {
+            let mut pick: Vec<usize> = Vec::with_capacity(k);
+            while pick.len() < k {
+                let i = next(n);
+                if !pick.contains(&i) {
+                    pick.push(i);
+                }
+            }
+            pick
+        })
+        .collect()
+}
+
+/*
+ * Externally studentized residuals: each residual over the scatter of the others, allowing for
+ * its leverage. `leverage` comes from the point's input so curves are treated as lines locally.
+ * Needs two more points than parameters, otherwise everything is 0
+ */
+pub fn studentized(e: &[f64], w: &[f64], leverage: &[f64], params: usize) -> Vec<f64> {
+    let n = e.len();
+    if n < params + 2 {
+        return vec![0.0; n];
+    }
+    let dof = (n - params) as f64;
+    let ssr: f64 = e.iter().zip(w).map(|(e, w)| w * e * e).sum();
+    let s = (ssr / dof).sqrt();
+    e.iter()
+        .zip(w)
+        .zip(leverage)
+        .map(|((e, w), h)| {
+            if s == 0.0 {
+                return 0.0;
+            }
+            let r = e * w.sqrt() / (s * (1.0 - h).max(1e-12).sqrt());
+            let rest = dof - r * r;
+            if rest <= 0.0 {
+                f64::INFINITY.copysign(r)
+            } else {
+                r * ((dof - 1.0) / rest).sqrt()
+            }
+        })
+        .collect()
+}
+
+// Leverage of each point in a weighted straight line fit through the inputs
+pub fn leverage(x: &[f64], w: &[f64]) -> Vec<f64> {
+    let sw: f64 = w.iter().sum();
+    let mean = x.iter().zip(w).map(|(x, w)| w * x).sum::<f64>() / sw;
+    let sxx: f64 = x.iter().zip(w).map(|(x, w)| w * (x - mean).powi(2)).sum();
+    x.iter()
+        .zip(w)
+        .map(|(x, w)| w * (1.0 / sw + (x - mean).powi(2) / sxx))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{leverage, studentized, subsets, Robust, OUTLIER_LIMIT};
+    use crate::calculator::{CalcError, Line};
+
+    // y = 2x + 1 with a little noise and one fat-fingered reading
+    const POINTS: [(f64, f64); 7] = [
+        (0.0, 1.02),
+        (1.0, 2.97),
+        (2.0, 5.01),
+        (3.0, 7.0),
+        (4.0, 90.0),
+        (5.0, 10.98),
+        (6.0, 13.03),
+    ];
+
+    fn fit_line(mult: &[f64]) -> Result<Line, CalcError> {
+        let (p, w): (Vec<(f64, f64)>, Vec<f64>) = POINTS
+            .iter()
+            .zip(mult)
+            .filter(|(_, m)| **m > 0.0)
+            .map(|(p, m)| (*p, *m))
+            .unzip();
+        let line = Line::fit(&p, &w);
+        match line.get_val() {
+            Some(_) => Ok(line),
+            None => Err(CalcError::Singular),
+        }
+    }
+
+    fn residuals(line: &Line) -> Vec<f64> {
+        let (m, b) = line.get_val().unwrap();
+        POINTS.iter().map(|(x, y)| y - (m * x + b)).collect()
+    }
+
+    #[test]
+    fn robust_fits_ignore_the_bad_reading() {
+        let w = [1.0; 7];
+        for robust in [Robust::Huber, Robust::Tukey, Robust::Ransac] {
+            let (line, mult) = robust.fit(&w, 2, fit_line, residuals).unwrap();
+            let (m, b) = line.get_val().unwrap();
+            // Huber only limits the outlier's pull, the others drop it
+            let tol = if robust == Robust::Huber { 0.2 } else { 0.05 };
+            assert!((m - 2.0).abs() < tol, "{:?} slope {}", robust, m);
+            assert!((b - 1.0).abs() < 2.0 * tol, "{:?} intercept {}", robust, b);
+            assert!(
+                mult[4] < 0.1,
+                "{:?} kept the outlier at {}",
+                robust,
+                mult[4]
+            );
+        }
+        let (line, _) = Robust::Off.fit(&w, 2, fit_line, residuals).unwrap();
+        assert!(line.get_val().unwrap().0 > 3.0);
+    }
+
+    #[test]
+    fn studentized_residuals_flag_the_outlier() {
+        let w = [1.0; 7];
+        let (line, _) = Robust::Off.fit(&w, 2, fit_line, residuals).unwrap();
+        let x: Vec<f64> = POINTS.iter().map(|p| p.0).collect();
+        let t = studentized(&residuals(&line), &w, &leverage(&x, &w), 2);
+        let flagged: Vec<usize> = (0..7).filter(|i| t[*i].abs() > OUTLIER_LIMIT).collect();
+        assert_eq!(vec![4], flagged);
+        // too few points to say anything
+        assert_eq!(
+            vec![0.0; 3],
+            studentized(&[1.0, -1.0, 5.0], &[1.0; 3], &[0.5; 3], 2)
+        );
+    }
+
+    #[test]
+    fn every_subset() {
+        let all = subsets(5, 2);
+        assert_eq!(10, all.len());
+        assert_eq!(vec![3, 4], all[9]);
+        // big problems are sampled instead
+        let sampled = subsets(40, 4);
+        assert_eq!(5000, sampled.len());
+        assert!(sampled
+            .iter()
+            .all(|s| s.len() == 4 && s.iter().all(|i| *i < 40)));
+    }
+}
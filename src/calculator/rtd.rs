@@ -0,0 +1,228 @@
+use super::linalg;
+use super::CalcError;
+
+// Callendar-Van Dusen works in ohms and °C
+// R(T) = R0 (1 + A T + B T^2 + C (T - 100) T^3), the C term only applies below 0 °C
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CallendarVanDusen {
+    pub r0: f64,
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+}
+
+// Standard probes to compare a fitted one against
+const NOMINAL_R0: [f64; 3] = [100.0, 500.0, 1000.0];
+
+impl CallendarVanDusen {
+    // IEC 60751 coefficients
+    pub const IEC_A: f64 = 3.9083e-3;
+    pub const IEC_B: f64 = -5.775e-7;
+    pub const IEC_C: f64 = -4.183e-12;
+
+    pub fn iec(r0: f64) -> Self {
+        CallendarVanDusen {
+            r0,
+            a: Self::IEC_A,
+            b: Self::IEC_B,
+            c: Self::IEC_C,
+        }
+    }
+
+    // The standard curve of the PT100, PT500 or PT1000 this probe is closest to
+    pub fn nominal(&self) -> Self {
+        let r0 = NOMINAL_R0
+            .iter()
+            .cloned()
+            .min_by(|x, y| {
+                (x.ln() - self.r0.ln())
+                    .abs()
+                    .total_cmp(&(y.ln() - self.r0.ln()).abs())
+            })
+            .unwrap_or(100.0);
+        Self::iec(r0)
+    }
+
+    /*
+     * Fit to (ohm, °C) points. Coefficients are fitted as far as the points allow and the rest
+     * keep their IEC 60751 values: one point gives R0, two add A, three add B and C needs a
+     * fourth point with at least one of them below 0 °C.
+     * Temperatures are scaled by 100 °C so the normal equations stay well conditioned.
+     * `w` weights the temperature errors, the fit itself is on resistance
+     */
+    pub fn fit(points: &[(f64, f64)], w: &[f64]) -> Result<Self, CalcError> {
+        if points.is_empty() {
+            return Err(CalcError::NotEnoughPoints { needed: 1, got: 0 });
+        }
+        if points.iter().any(|(r, t)| *r <= 0.0 || *t <= -273.15) {
+            return Err(CalcError::OutOfDomain(
+                "resistance must be positive and temperature above absolute zero",
+            ));
+        }
+        let max_free = if points.iter().any(|(_, t)| *t < 0.0) {
+            4
+        } else {
+            3
+        };
+        let free = points.len().min(max_free);
+        // IEC coefficients relative to R0 in the scaled temperature s = T / 100
+        let iec = [1.0, Self::IEC_A * 1e2, Self::IEC_B * 1e4, Self::IEC_C * 1e8];
+        let rows: Vec<Vec<f64>> = points
+            .iter()
+            .map(|(_, t)| {
+                let basis = scaled_basis(*t);
+                let mut row = basis[..free].to_vec();
+                // fixed coefficients ride along with R0
+                row[0] += (free..4).map(|j| iec[j] * basis[j]).sum::<f64>();
+                row
+            })
+            .collect();
+        let y: Vec<f64> = points.iter().map(|(r, _)| *r).collect();
+        // an error of u in T is about u dR/dT in R, the IEC curve is close enough for the slope
+        let w: Vec<f64> = points
+            .iter()
+            .zip(w)
+            .map(|((r, t), w)| {
+                let slope = r * (Self::IEC_A + 2.0 * Self::IEC_B * t)
+                    / (1.0 + Self::IEC_A * t + Self::IEC_B * t * t);
+                w / (slope * slope)
+            })
+            .collect();
+        let x = linalg::weighted_least_squares(&rows, &y, &w).ok_or(CalcError::Singular)?;
+        let r0 = x[0];
+        if r0 <= 0.0 {
+            return Err(CalcError::OutOfDomain("fitted R0 is not positive"));
+        }
+        let coef = |j: usize| if j < free { x[j] / r0 } else { iec[j] };
+        Ok(CallendarVanDusen {
+            r0,
+            a: coef(1) / 1e2,
+            b: coef(2) / 1e4,
+            c: coef(3) / 1e8,
+        })
+    }
+
+    pub fn resistance(&self, celsius: f64) -> Result<f64, CalcError> {
+        let mut ratio = 1.0 + self.a * celsius + self.b * celsius * celsius;
+        if celsius < 0.0 {
+            ratio += self.c * (celsius - 100.0) * celsius.powi(3);
+        }
+        if ratio <= 0.0 {
+            return Err(CalcError::OutOfDomain(
+                "temperature is outside the RTD curve",
+            ));
+        }
+        Ok(self.r0 * ratio)
+    }
+
+    // Above R0 the quadratic is solved directly, below it Newton takes over from that guess
+    pub fn temperature(&self, ohms: f64) -> Result<f64, CalcError> {
+        if ohms <= 0.0 {
+            return Err(CalcError::OutOfDomain("resistance must be positive"));
+        }
+        let ratio = ohms / self.r0;
+        let mut t = if self.b == 0.0 {
+            (ratio - 1.0) / self.a
+        } else {
+            let disc = self.a * self.a - 4.0 * self.b * (1.0 - ratio);
+            if disc < 0.0 {
+                return Err(CalcError::OutOfDomain("resistance is beyond the RTD curve"));
+            }
+            (-self.a + disc.sqrt()) / (2.0 * self.b)
+        };
+        if ratio >= 1.0 || self.c == 0.0 {
+            return Ok(t);
+        }
+        for _ in 0..50 {
+            let f = 1.0 + self.a * t + self.b * t * t + self.c * (t - 100.0) * t.powi(3) - ratio;
+            let df = self.a + 2.0 * self.b * t + self.c * (4.0 * t.powi(3) - 300.0 * t * t);
+            let step = f / df;
+            t -= step;
+            if step.abs() < 1e-10 {
+                return Ok(t);
+            }
+        }
+        Err(CalcError::OutOfDomain("resistance is beyond the RTD curve"))
+    }
+
+    // How far this probe reads from another (usually the standard curve) at a resistance, in °C
+    pub fn deviation(&self, other: &CallendarVanDusen, ohms: f64) -> Result<f64, CalcError> {
+        Ok(self.temperature(ohms)? - other.temperature(ohms)?)
+    }
+}
+
+// [1, s, s^2, (s - 1) s^3] with s = T / 100, the last term only below 0 °C
+fn scaled_basis(celsius: f64) -> [f64; 4] {
+    let s = celsius / 100.0;
+    let c = if celsius < 0.0 {
+        (s - 1.0) * s.powi(3)
+    } else {
+        0.0
+    };
+    [1.0, s, s * s, c]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CallendarVanDusen;
+
+    #[test]
+    fn iec_table() {
+        let pt100 = CallendarVanDusen::iec(100.0);
+        // values from the IEC 60751 table
+        for (t, r) in [
+            (-100.0, 60.2558),
+            (0.0, 100.0),
+            (100.0, 138.5055),
+            (400.0, 247.0920),
+        ] {
+            assert!((pt100.resistance(t).unwrap() - r).abs() < 1e-3);
+            assert!((pt100.temperature(r).unwrap() - t).abs() < 1e-2);
+        }
+        let pt1000 = CallendarVanDusen::iec(1010.0).nominal();
+        assert_eq!(1000.0, pt1000.r0);
+    }
+
+    #[test]
+    fn fit_all_coefficients() {
+        let probe = CallendarVanDusen {
+            r0: 100.04,
+            a: 3.91e-3,
+            b: -5.8e-7,
+            c: -4.0e-12,
+        };
+        let points: Vec<(f64, f64)> = [-150.0, -50.0, 0.0, 150.0, 300.0]
+            .iter()
+            .map(|t| (probe.resistance(*t).unwrap(), *t))
+            .collect();
+        let fitted = CallendarVanDusen::fit(&points, &[1.0; 5]).unwrap();
+        assert!((fitted.r0 - probe.r0).abs() < 1e-9);
+        assert!((fitted.a - probe.a).abs() < 1e-12);
+        assert!((fitted.b - probe.b).abs() < 1e-15);
+        assert!((fitted.c - probe.c).abs() < 1e-18);
+        let r = probe.resistance(-120.0).unwrap();
+        assert!((fitted.temperature(r).unwrap() + 120.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fit_keeps_iec_defaults() {
+        // ice point and boiling point only pin R0 and A
+        let probe = CallendarVanDusen {
+            r0: 99.98,
+            a: 3.92e-3,
+            ..CallendarVanDusen::iec(100.0)
+        };
+        let points = [
+            (probe.resistance(0.0).unwrap(), 0.0),
+            (probe.resistance(100.0).unwrap(), 100.0),
+        ];
+        let fitted = CallendarVanDusen::fit(&points, &[1.0; 2]).unwrap();
+        assert!((fitted.r0 - probe.r0).abs() < 1e-9);
+        assert!((fitted.a - probe.a).abs() < 1e-12);
+        assert_eq!(CallendarVanDusen::IEC_B, fitted.b);
+        assert_eq!(CallendarVanDusen::IEC_C, fitted.c);
+        // 0.02 ohm low at the ice point reads about 0.05 °C high against the standard
+        let dev = fitted.deviation(&fitted.nominal(), 100.0).unwrap();
+        assert!((dev - 0.0512).abs() < 1e-3);
+    }
+}
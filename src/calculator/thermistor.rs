@@ -0,0 +1,259 @@
+use super::linalg;
+use super::CalcError;
+
+// Thermistor models work in ohms and kelvin, the App converts to and from the session units
+
+// 1/T = a + b ln(R) + c ln(R)^3
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SteinhartHart {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+}
+
+// 1/T = 1/T0 + ln(R/R0) / beta
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Beta {
+    pub beta: f64,
+    pub r0: f64,
+    pub t0: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DividerPosition {
+    // thermistor between the measured node and ground, fixed resistor to the supply
+    Low,
+    // thermistor between the supply and the measured node
+    High,
+}
+
+// Voltage divider in front of the thermistor so a measured voltage can be turned into ohms
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Divider {
+    pub supply: f64,
+    pub fixed: f64,
+    pub position: DividerPosition,
+}
+
+// Both models are fitted on 1/T, an error of u in T is u / T^2 there
+fn inverse_weights(points: &[(f64, f64)], w: &[f64]) -> Vec<f64> {
+    points
+        .iter()
+        .zip(w)
+        .map(|((_, t), w)| w * t.powi(4))
+        .collect()
+}
+
+fn check_points(points: &[(f64, f64)], needed: usize) -> Result<(), CalcError> {
+    if points.len() < needed {
+        return Err(CalcError::NotEnoughPoints {
+            needed,
+            got: points.len(),
+        });
+    }
+    if points.iter().any(|(r, t)| *r <= 0.0 || *t <= 0.0) {
+        return Err(CalcError::OutOfDomain(
+            "resistance and absolute temperature must be positive",
+        ));
+    }
+    Ok(())
+}
+
+impl SteinhartHart {
+    // Solve exactly through three (ohm, kelvin) points
+    pub fn from_three(points: &[(f64, f64); 3]) -> Result<Self, CalcError> {
+        check_points(points, 3)?;
+        let rows: Vec<Vec<f64>> = points
+            .iter()
+            .map(|(r, _)| vec![1.0, r.ln(), r.ln().powi(3)])
+            .collect();
+        let y = points.iter().map(|(_, t)| 1.0 / t).collect();
+        let x = linalg::solve(rows, y).ok_or(CalcError::Singular)?;
+        Ok(SteinhartHart {
+            a: x[0],
+            b: x[1],
+            c: x[2],
+        })
+    }
+
+    // Least squares through three or more (ohm, kelvin) points, `w` weighting the temperature errors
+    pub fn fit(points: &[(f64, f64)], w: &[f64]) -> Result<Self, CalcError> {
+        check_points(points, 3)?;
+        if let [p1, p2, p3] = points {
+            return Self::from_three(&[*p1, *p2, *p3]);
+        }
+        let rows: Vec<Vec<f64>> = points
+            .iter()
+            .map(|(r, _)| vec![1.0, r.ln(), r.ln().powi(3)])
+            .collect();
+        let y: Vec<f64> = points.iter().map(|(_, t)| 1.0 / t).collect();
+        let x = linalg::weighted_least_squares(&rows, &y, &inverse_weights(points, w))
+            .ok_or(CalcError::Singular)?;
+        Ok(SteinhartHart {
+            a: x[0],
+            b: x[1],
+            c: x[2],
+        })
+    }
+
+    pub fn temperature(&self, ohms: f64) -> Result<f64, CalcError> {
+        if ohms <= 0.0 {
+            return Err(CalcError::OutOfDomain("resistance must be positive"));
+        }
+        let l = ohms.ln();
+        let inv = self.a + self.b * l + self.c * l.powi(3);
+        if inv <= 0.0 {
+            return Err(CalcError::OutOfDomain("outside the fitted curve"));
+        }
+        Ok(1.0 / inv)
+    }
+
+    // Invert the cubic in ln(R)
+    pub fn resistance(&self, kelvin: f64) -> Result<f64, CalcError> {
+        if kelvin <= 0.0 {
+            return Err(CalcError::OutOfDomain("temperature must be above 0 K"));
+        }
+        if self.c == 0.0 {
+            return Ok(((1.0 / kelvin - self.a) / self.b).exp());
+        }
+        let y = (self.a - 1.0 / kelvin) / (2.0 * self.c);
+        let x = ((self.b / (3.0 * self.c)).powi(3) + y * y).sqrt();
+        Ok(((x - y).cbrt() - (x + y).cbrt()).exp())
+    }
+}
+
+impl Beta {
+    // Datasheets quote R0 at 25 °C
+    pub const T0: f64 = 298.15;
+
+    // Least squares of 1/T against ln(R) through two or more (ohm, kelvin) points
+    pub fn fit(points: &[(f64, f64)], w: &[f64]) -> Result<Self, CalcError> {
+        check_points(points, 2)?;
+        let rows: Vec<Vec<f64>> = points.iter().map(|(r, _)| vec![1.0, r.ln()]).collect();
+        let y: Vec<f64> = points.iter().map(|(_, t)| 1.0 / t).collect();
+        let x = linalg::weighted_least_squares(&rows, &y, &inverse_weights(points, w))
+            .ok_or(CalcError::Singular)?;
+        if x[1] == 0.0 {
+            return Err(CalcError::Singular);
+        }
+        let beta = 1.0 / x[1];
+        Ok(Beta {
+            beta,
+            r0: ((1.0 / Self::T0 - x[0]) * beta).exp(),
+            t0: Self::T0,
+        })
+    }
+
+    pub fn temperature(&self, ohms: f64) -> Result<f64, CalcError> {
+        if ohms <= 0.0 {
+            return Err(CalcError::OutOfDomain("resistance must be positive"));
+        }
+        let inv = 1.0 / self.t0 + (ohms / self.r0).ln() / self.beta;
+        if inv <= 0.0 {
+            return Err(CalcError::OutOfDomain("outside the fitted curve"));
+        }
+        Ok(1.0 / inv)
+    }
+
+    pub fn resistance(&self, kelvin: f64) -> Result<f64, CalcError> {
+        if kelvin <= 0.0 {
+            return Err(CalcError::OutOfDomain("temperature must be above 0 K"));
+        }
+        Ok(self.r0 * (self.beta * (1.0 / kelvin - 1.0 / self.t0)).exp())
+    }
+}
+
+impl DividerPosition {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DividerPosition::Low => "low side",
+            DividerPosition::High => "high side",
+        }
+    }
+}
+
+impl Divider {
+    // Thermistor resistance from the measured node voltage
+    pub fn resistance(&self, volts: f64) -> Result<f64, CalcError> {
+        if volts <= 0.0 || volts >= self.supply {
+            return Err(CalcError::OutOfDomain(
+                "divider voltage must be between 0 V and the supply",
+            ));
+        }
+        Ok(match self.position {
+            DividerPosition::Low => self.fixed * volts / (self.supply - volts),
+            DividerPosition::High => self.fixed * (self.supply - volts) / volts,
+        })
+    }
+
+    pub fn voltage(&self, ohms: f64) -> f64 {
+        match self.position {
+            DividerPosition::Low => self.supply * ohms / (self.fixed + ohms),
+            DividerPosition::High => self.supply * self.fixed / (self.fixed + ohms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Beta, Divider, DividerPosition, SteinhartHart};
+    use crate::calculator::CalcError;
+
+    // 10k NTC table generated from the usual a, b, c coefficients (ohm, kelvin)
+    const NTC: [(f64, f64); 5] = [
+        (27950.0, 273.15),
+        (18129.0, 283.15),
+        (9877.0, 298.15),
+        (6759.0, 308.15),
+        (4714.0, 318.15),
+    ];
+
+    #[test]
+    fn steinhart_hart_three_points() {
+        let sh = SteinhartHart::from_three(&[NTC[0], NTC[2], NTC[4]]).unwrap();
+        for (r, t) in [NTC[0], NTC[2], NTC[4]] {
+            assert!((sh.temperature(r).unwrap() - t).abs() < 1e-6);
+            assert!((sh.resistance(t).unwrap() - r).abs() / r < 1e-6);
+        }
+    }
+
+    #[test]
+    fn steinhart_hart_least_squares() {
+        let sh = SteinhartHart::fit(&NTC, &[1.0; NTC.len()]).unwrap();
+        for (r, t) in NTC {
+            assert!((sh.temperature(r).unwrap() - t).abs() < 0.05);
+        }
+        assert_eq!(
+            Err(CalcError::NotEnoughPoints { needed: 3, got: 2 }),
+            SteinhartHart::fit(&NTC[..2], &[1.0; 2])
+        );
+        assert!(SteinhartHart::fit(&[(0.0, 273.0), NTC[1], NTC[2]], &[1.0; 3]).is_err());
+    }
+
+    #[test]
+    fn beta_model() {
+        let beta = Beta::fit(&[NTC[2], NTC[4]], &[1.0; 2]).unwrap();
+        assert!((beta.r0 - 9877.0).abs() < 1e-6);
+        assert!((beta.beta - 3508.0).abs() < 1.0);
+        assert!((beta.temperature(4714.0).unwrap() - 318.15).abs() < 1e-6);
+        assert!((beta.resistance(298.15).unwrap() - 9877.0).abs() < 1e-6);
+        assert!(beta.temperature(-1.0).is_err());
+    }
+
+    #[test]
+    fn divider() {
+        let low = Divider {
+            supply: 3.3,
+            fixed: 10000.0,
+            position: DividerPosition::Low,
+        };
+        assert!((low.resistance(1.65).unwrap() - 10000.0).abs() < 1e-9);
+        assert!((low.resistance(low.voltage(4700.0)).unwrap() - 4700.0).abs() < 1e-9);
+        let high = Divider {
+            position: DividerPosition::High,
+            ..low
+        };
+        assert!((high.resistance(high.voltage(22000.0)).unwrap() - 22000.0).abs() < 1e-9);
+        assert!(high.resistance(3.3).is_err());
+    }
+}
@@ -0,0 +1,614 @@
+use super::linalg;
+use super::CalcError;
+
+// Thermocouples work in mV (relative to a 0 °C reference junction) and °C
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ThermocoupleType {
+    K,
+    J,
+    T,
+    E,
+    N,
+    R,
+    S,
+    B,
+}
+
+// One piece of a NIST ITS-90 reference function: E = sum c_i t^i (mV) for t up to `upper` °C
+pub struct Segment {
+    pub upper: f64,
+    pub coefs: &'static [f64],
+}
+
+/*
+ * NIST ITS-90 reference functions (NIST Monograph 175), temperature to EMF.
+ * Going the other way is done numerically against these so there is one set of tables
+ */
+const K_SEGMENTS: [Segment; 2] = [
+    Segment {
+        upper: 0.0,
+        coefs: &[
+            0.0,
+            0.394501280250E-01,
+            0.236223735980E-04,
+            -0.328589067840E-06,
+            -0.499048287770E-08,
+            -0.675090591730E-10,
+            -0.574103274280E-12,
+            -0.310888728940E-14,
+            -0.104516093650E-16,
+            -0.198892668780E-19,
+            -0.163226974860E-22,
+        ],
+    },
+    Segment {
+        upper: 1372.0,
+        coefs: &[
+            -0.176004136860E-01,
+            0.389212049750E-01,
+            0.185587700320E-04,
+            -0.994575928740E-07,
+            0.318409457190E-09,
+            -0.560728448890E-12,
+            0.560750590590E-15,
+            -0.320207200030E-18,
+            0.971511471520E-22,
+            -0.121047212750E-25,
+        ],
+    },
+];
+// Type K above 0 °C also has a0 exp(a1 (t - a2)^2)
+const K_EXP: [f64; 3] = [0.118597600000E+00, -0.118343200000E-03, 0.126968600000E+03];
+
+const J_SEGMENTS: [Segment; 2] = [
+    Segment {
+        upper: 760.0,
+        coefs: &[
+            0.0,
+            0.503811878150E-01,
+            0.304758369300E-04,
+            -0.856810657200E-07,
+            0.132281952950E-09,
+            -0.170529583370E-12,
+            0.209480906970E-15,
+            -0.125383953360E-18,
+            0.156317256970E-22,
+        ],
+    },
+    Segment {
+        upper: 1200.0,
+        coefs: &[
+            0.296456256810E+03,
+            -0.149761277860E+01,
+            0.317871039240E-02,
+            -0.318476867010E-05,
+            0.157208190040E-08,
+            -0.306913690560E-12,
+        ],
+    },
+];
+
+const T_SEGMENTS: [Segment; 2] = [
+    Segment {
+        upper: 0.0,
+        coefs: &[
+            0.0,
+            0.387481063640E-01,
+            0.441944343470E-04,
+            0.118443231050E-06,
+            0.200329735540E-07,
+            0.901380195590E-09,
+            0.226511565930E-10,
+            0.360711542050E-12,
+            0.384939398830E-14,
+            0.282135219250E-16,
+            0.142515947790E-18,
+            0.487686622860E-21,
+            0.107955392700E-23,
+            0.139450270620E-26,
+            0.797951539270E-30,
+        ],
+    },
+    Segment {
+        upper: 400.0,
+        coefs: &[
+            0.0,
+            0.387481063640E-01,
+            0.332922278800E-04,
+            0.206182434040E-06,
+            -0.218822568460E-08,
+            0.109968809280E-10,
+            -0.308157587720E-13,
+            0.454791352900E-16,
+            -0.275129016730E-19,
+        ],
+    },
+];
+
+const E_SEGMENTS: [Segment; 2] = [
+    Segment {
+        upper: 0.0,
+        coefs: &[
+            0.0,
+            0.586655087080E-01,
+            0.454109771240E-04,
+            -0.779980486860E-06,
+            -0.258001608430E-07,
+            -0.594525830570E-09,
+            -0.932140586670E-11,
+            -0.102876055340E-12,
+            -0.803701236210E-15,
+            -0.439794973910E-17,
+            -0.164147763550E-19,
+            -0.396736195160E-22,
+            -0.558273287210E-25,
+            -0.346578420130E-28,
+        ],
+    },
+    Segment {
+        upper: 1000.0,
+        coefs: &[
+            0.0,
+            0.586655087100E-01,
+            0.450322755820E-04,
+            0.289084072120E-07,
+            -0.330568966520E-09,
+            0.650244032700E-12,
+            -0.191974955040E-15,
+            -0.125366004970E-17,
+            0.214892175690E-20,
+            -0.143880417820E-23,
+            0.359608994810E-27,
+        ],
+    },
+];
+
+const N_SEGMENTS: [Segment; 2] = [
+    Segment {
+        upper: 0.0,
+        coefs: &[
+            0.0,
+            0.261591059620E-01,
+            0.109574842280E-04,
+            -0.938411115540E-07,
+            -0.464120397590E-10,
+            -0.263033577160E-11,
+            -0.226534380030E-13,
+            -0.760893007910E-16,
+            -0.934196678350E-19,
+        ],
+    },
+    Segment {
+        upper: 1300.0,
+        coefs: &[
+            0.0,
+            0.259293946010E-01,
+            0.157101418800E-04,
+            0.438256272370E-07,
+            -0.252611697940E-09,
+            0.643118193390E-12,
+            -0.100634715190E-14,
+            0.997453389920E-18,
+            -0.608632456070E-21,
+            0.208492293390E-24,
+            -0.306821961510E-28,
+        ],
+    },
+];
+
+const R_SEGMENTS: [Segment; 3] = [
+    Segment {
+        upper: 1064.18,
+        coefs: &[
+            0.0,
+            0.528961729765E-02,
+            0.139166589782E-04,
+            -0.238855693017E-07,
+            0.356916001063E-10,
+            -0.462347666298E-13,
+            0.500777441034E-16,
+            -0.373105886191E-19,
+            0.157716482367E-22,
+            -0.281038625251E-26,
+        ],
+    },
+    Segment {
+        upper: 1664.5,
+        coefs: &[
+            0.295157925316E+01,
+            -0.252061251332E-02,
+            0.159564501865E-04,
+            -0.764085947576E-08,
+            0.205305291024E-11,
+            -0.293359668173E-15,
+        ],
+    },
+    Segment {
+        upper: 1768.1,
+        coefs: &[
+            0.152232118209E+03,
+            -0.268819888545E+00,
+            0.171280280471E-03,
+            -0.345895706453E-07,
+            -0.934633971046E-14,
+        ],
+    },
+];
+
+const S_SEGMENTS: [Segment; 3] = [
+    Segment {
+        upper: 1064.18,
+        coefs: &[
+            0.0,
+            0.540313308631E-02,
+            0.125934289740E-04,
+            -0.232477968689E-07,
+            0.322028823036E-10,
+            -0.331465196389E-13,
+            0.255744251786E-16,
+            -0.125068871393E-19,
+            0.271443176145E-23,
+        ],
+    },
+    Segment {
+        upper: 1664.5,
+        coefs: &[
+            0.132900444085E+01,
+            0.334509311344E-02,
+            0.654805192818E-05,
+            -0.164856259209E-08,
+            0.129989605174E-13,
+        ],
+    },
+    Segment {
+        upper: 1768.1,
+        coefs: &[
+            0.146628232636E+03,
+            -0.258430516752E+00,
+            0.163693574641E-03,
+            -0.330439046987E-07,
+            -0.943223690612E-14,
+        ],
+    },
+];
+
+const B_SEGMENTS: [Segment; 2] = [
+    Segment {
+        upper: 630.615,
+        coefs: &[
+            0.0,
+            -0.246508183460E-03,
+            0.590404211710E-05,
+            -0.132579316360E-08,
+            0.156682919010E-11,
+            -0.169445292400E-14,
+            0.629903470940E-18,
+        ],
+    },
+    Segment {
+        upper: 1820.0,
+        coefs: &[
+            -0.389381686210E+01,
+            0.285717474700E-01,
+            -0.848851047850E-04,
+            0.157852801640E-06,
+            -0.168353448640E-09,
+            0.111097940130E-12,
+            -0.445154310330E-16,
+            0.989756408210E-20,
+            -0.937913302180E-24,
+        ],
+    },
+];
+
+impl ThermocoupleType {
+    pub const ALL: [ThermocoupleType; 8] = [
+        ThermocoupleType::K,
+        ThermocoupleType::J,
+        ThermocoupleType::T,
+        ThermocoupleType::E,
+        ThermocoupleType::N,
+        ThermocoupleType::R,
+        ThermocoupleType::S,
+        ThermocoupleType::B,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThermocoupleType::K => "K",
+            ThermocoupleType::J => "J",
+            ThermocoupleType::T => "T",
+            ThermocoupleType::E => "E",
+            ThermocoupleType::N => "N",
+            ThermocoupleType::R => "R",
+            ThermocoupleType::S => "S",
+            ThermocoupleType::B => "B",
+        }
+    }
+
+    // "Type K"
+    pub fn label(&self) -> &'static str {
+        match self {
+            ThermocoupleType::K => "Type K",
+            ThermocoupleType::J => "Type J",
+            ThermocoupleType::T => "Type T",
+            ThermocoupleType::E => "Type E",
+            ThermocoupleType::N => "Type N",
+            ThermocoupleType::R => "Type R",
+            ThermocoupleType::S => "Type S",
+            ThermocoupleType::B => "Type B",
+        }
+    }
+
+    // The next type in ALL, None after the last
+    pub fn next(&self) -> Option<ThermocoupleType> {
+        let i = Self::ALL.iter().position(|t| t == self)?;
+        Self::ALL.get(i + 1).cloned()
+    }
+
+    pub fn parse(s: &str) -> Option<ThermocoupleType> {
+        Self::ALL
+            .iter()
+            .find(|t| t.name().eq_ignore_ascii_case(s))
+            .cloned()
+    }
+
+    fn segments(&self) -> &'static [Segment] {
+        match self {
+            ThermocoupleType::K => &K_SEGMENTS,
+            ThermocoupleType::J => &J_SEGMENTS,
+            ThermocoupleType::T => &T_SEGMENTS,
+            ThermocoupleType::E => &E_SEGMENTS,
+            ThermocoupleType::N => &N_SEGMENTS,
+            ThermocoupleType::R => &R_SEGMENTS,
+            ThermocoupleType::S => &S_SEGMENTS,
+            ThermocoupleType::B => &B_SEGMENTS,
+        }
+    }
+
+    // Temperature range of the reference function in °C
+    pub fn range(&self) -> (f64, f64) {
+        let lower = match self {
+            ThermocoupleType::K
+            | ThermocoupleType::T
+            | ThermocoupleType::E
+            | ThermocoupleType::N => -270.0,
+            ThermocoupleType::J => -210.0,
+            ThermocoupleType::R | ThermocoupleType::S => -50.0,
+            ThermocoupleType::B => 0.0,
+        };
+        let segments = self.segments();
+        (lower, segments[segments.len() - 1].upper)
+    }
+
+    /*
+     * The reference function's segments, coefficients lowest power first, and the Gaussian term
+     * type K adds above 0 °C as (a0, a1, a2)
+     */
+    pub fn reference_function(&self) -> (&'static [Segment], Option<[f64; 3]>) {
+        let segments = self.segments();
+        let gaussian = match self {
+            ThermocoupleType::K => Some(K_EXP),
+            _ => None,
+        };
+        (segments, gaussian)
+    }
+
+    // Range EMF can be turned back into temperature over. Type B is double valued below about 40 °C
+    pub fn inverse_range(&self) -> (f64, f64) {
+        match self {
+            ThermocoupleType::B => (50.0, self.range().1),
+            _ => self.range(),
+        }
+    }
+
+    // dE/dt in mV/°C, one sided at the ends of the table
+    pub fn seebeck(&self, celsius: f64) -> Result<f64, CalcError> {
+        let (lower, upper) = self.range();
+        let (lo, hi) = ((celsius - 0.01).max(lower), (celsius + 0.01).min(upper));
+        Ok((self.emf(hi)? - self.emf(lo)?) / (hi - lo))
+    }
+
+    // Reference EMF in mV with the reference junction at 0 °C
+    pub fn emf(&self, celsius: f64) -> Result<f64, CalcError> {
+        let (lower, upper) = self.range();
+        if celsius < lower || celsius > upper {
+            return Err(CalcError::OutOfDomain(
+                "temperature is outside the thermocouple table",
+            ));
+        }
+        let segment =
+            self.segments()
+                .iter()
+                .find(|s| celsius <= s.upper)
+                .ok_or(CalcError::OutOfDomain(
+                    "temperature is outside the thermocouple table",
+                ))?;
+        let mut e = segment
+            .coefs
+            .iter()
+            .rev()
+            .fold(0.0, |acc, c| acc * celsius + c);
+        if *self == ThermocoupleType::K && celsius > 0.0 {
+            e += K_EXP[0] * (K_EXP[1] * (celsius - K_EXP[2]).powi(2)).exp();
+        }
+        Ok(e)
+    }
+}
+
+// Find the temperature giving an EMF by bisection, the reference functions are monotonic over their inverse range
+fn invert(
+    (mut lo, mut hi): (f64, f64),
+    mv: f64,
+    emf: impl Fn(f64) -> Result<f64, CalcError>,
+) -> Result<f64, CalcError> {
+    let (e_lo, e_hi) = (emf(lo)?, emf(hi)?);
+    if mv < e_lo.min(e_hi) || mv > e_lo.max(e_hi) {
+        return Err(CalcError::OutOfDomain(
+            "EMF is outside the thermocouple table",
+        ));
+    }
+    let rising = e_hi > e_lo;
+    for _ in 0..100 {
+        let mid = (lo + hi) / 2.0;
+        if (emf(mid)? < mv) == rising {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+        if hi - lo < 1e-9 {
+            break;
+        }
+    }
+    Ok((lo + hi) / 2.0)
+}
+
+/*
+ * A thermocouple as calibrated: the reference function plus a deviation polynomial in temperature,
+ * E = E_ref(t) + d0 + d1 t + d2 t^2, fitted to how the user's points sit off the table
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Thermocouple {
+    pub kind: ThermocoupleType,
+    pub deviation: [f64; 3],
+}
+
+impl Thermocouple {
+    pub fn reference(kind: ThermocoupleType) -> Self {
+        Thermocouple {
+            kind,
+            deviation: [0.0; 3],
+        }
+    }
+
+    // Fit the deviation to (mV, °C) points: one point gives an offset, two a linear and three or more a quadratic correction.
+    // `w` weights the temperature errors, they become EMF errors through the Seebeck coefficient
+    pub fn fit(
+        kind: ThermocoupleType,
+        points: &[(f64, f64)],
+        w: &[f64],
+    ) -> Result<Self, CalcError> {
+        let mut tc = Self::reference(kind);
+        if points.is_empty() {
+            return Ok(tc);
+        }
+        let order = points.len().min(3);
+        let rows: Vec<Vec<f64>> = points
+            .iter()
+            // scaled like the RTD fit to keep the normal equations sane
+            .map(|(_, t)| (0..order).map(|i| (t / 100.0).powi(i as i32)).collect())
+            .collect();
+        let y = points
+            .iter()
+            .map(|(e, t)| Ok(e - kind.emf(*t)?))
+            .collect::<Result<Vec<f64>, CalcError>>()?;
+        let w = points
+            .iter()
+            .zip(w)
+            .map(|((_, t), w)| Ok(w / kind.seebeck(*t)?.powi(2)))
+            .collect::<Result<Vec<f64>, CalcError>>()?;
+        let x = linalg::weighted_least_squares(&rows, &y, &w).ok_or(CalcError::Singular)?;
+        for (i, d) in x.iter().enumerate() {
+            tc.deviation[i] = d / 100f64.powi(i as i32);
+        }
+        Ok(tc)
+    }
+
+    pub fn emf(&self, celsius: f64) -> Result<f64, CalcError> {
+        let [d0, d1, d2] = self.deviation;
+        Ok(self.kind.emf(celsius)? + d0 + d1 * celsius + d2 * celsius * celsius)
+    }
+
+    pub fn temperature(&self, mv: f64) -> Result<f64, CalcError> {
+        invert(self.kind.inverse_range(), mv, |t| self.emf(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Thermocouple, ThermocoupleType};
+
+    // (type, °C, mV) from the NIST ITS-90 tables, several per segment
+    const TABLE: [(ThermocoupleType, f64, f64); 24] = [
+        (ThermocoupleType::K, -200.0, -5.891),
+        (ThermocoupleType::K, 100.0, 4.096),
+        (ThermocoupleType::K, 1000.0, 41.276),
+        (ThermocoupleType::J, -100.0, -4.633),
+        (ThermocoupleType::J, 500.0, 27.393),
+        (ThermocoupleType::J, 1000.0, 57.953),
+        (ThermocoupleType::T, -200.0, -5.603),
+        (ThermocoupleType::T, 100.0, 4.279),
+        (ThermocoupleType::T, 400.0, 20.872),
+        (ThermocoupleType::E, -200.0, -8.825),
+        (ThermocoupleType::E, 100.0, 6.319),
+        (ThermocoupleType::E, 900.0, 68.787),
+        (ThermocoupleType::N, -200.0, -3.990),
+        (ThermocoupleType::N, 500.0, 16.748),
+        (ThermocoupleType::N, 1200.0, 43.846),
+        (ThermocoupleType::R, 500.0, 4.471),
+        (ThermocoupleType::R, 1200.0, 13.228),
+        (ThermocoupleType::R, 1700.0, 20.222),
+        (ThermocoupleType::S, 500.0, 4.233),
+        (ThermocoupleType::S, 1200.0, 11.951),
+        (ThermocoupleType::S, 1700.0, 17.947),
+        (ThermocoupleType::B, 500.0, 1.242),
+        (ThermocoupleType::B, 1000.0, 4.834),
+        (ThermocoupleType::B, 1800.0, 13.591),
+    ];
+
+    #[test]
+    fn reference_tables() {
+        for (kind, t, mv) in TABLE {
+            let e = kind.emf(t).unwrap();
+            assert!(
+                (e - mv).abs() < 1e-3,
+                "type {} at {} °C: {}",
+                kind.name(),
+                t,
+                e
+            );
+            let reference = Thermocouple::reference(kind);
+            assert!((reference.temperature(e).unwrap() - t).abs() < 1e-6);
+        }
+        assert!(ThermocoupleType::K.emf(1400.0).is_err());
+        assert!(Thermocouple::reference(ThermocoupleType::T)
+            .temperature(25.0)
+            .is_err());
+    }
+
+    #[test]
+    fn segments_join_up() {
+        for kind in ThermocoupleType::ALL {
+            for s in kind.segments().iter().take(kind.segments().len() - 1) {
+                let below = kind.emf(s.upper - 1e-6).unwrap();
+                let above = kind.emf(s.upper + 1e-6).unwrap();
+                assert!(
+                    (below - above).abs() < 1e-3,
+                    "type {} at {}",
+                    kind.name(),
+                    s.upper
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn deviation_fit() {
+        // a type K reading 20 µV high with a 0.01 % gain error
+        let k = ThermocoupleType::K;
+        let points: Vec<(f64, f64)> = [0.0, 200.0, 400.0, 600.0]
+            .iter()
+            .map(|t| (k.emf(*t).unwrap() * 1.0001 + 0.02, *t))
+            .collect();
+        let tc = Thermocouple::fit(k, &points, &[1.0; 4]).unwrap();
+        for (mv, t) in &points {
+            assert!((tc.temperature(*mv).unwrap() - t).abs() < 0.01);
+        }
+        assert!((tc.deviation[0] - 0.02).abs() < 1e-3);
+        // a single point is just an offset
+        let tc = Thermocouple::fit(k, &points[1..2], &[1.0]).unwrap();
+        assert_eq!(0.0, tc.deviation[1]);
+        assert!((tc.temperature(points[1].0).unwrap() - 200.0).abs() < 1e-6);
+    }
+}
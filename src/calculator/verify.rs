@@ -0,0 +1,129 @@
+use crate::units::{self, Unit};
+
+// Allowed error of an output, the sum of a fixed part and percentages of full scale and of reading
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Tolerance {
+    pub absolute: f64,
+    pub percent_fs: f64,
+    pub percent_reading: f64,
+}
+
+impl Tolerance {
+    // Half width of the band a reading of `nominal` has to fall in, full scale being the output span
+    pub fn limit(&self, nominal: f64, full_scale: f64) -> f64 {
+        self.absolute
+            + self.percent_fs / 100.0 * full_scale.abs()
+            + self.percent_reading / 100.0 * nominal.abs()
+    }
+
+    /*
+     * Parse a spec like "±0.5% FS", "0.2 bar" or "0.1% rdg + 0.05" into `unit`. A bare percentage is
+     * of full scale, a plain number is in `unit` and typed units only scale it like an uncertainty
+     */
+    pub fn parse(s: &str, unit: Unit) -> Option<Tolerance> {
+        let mut tolerance = Tolerance::default();
+        for term in s
+            .split('+')
+            .map(|t| t.trim().trim_start_matches('±').trim())
+        {
+            // "+-0.5%" splits into an empty term and the spec
+            if term.is_empty() || term == "-" {
+                continue;
+            }
+            let term = term.trim_start_matches('-').trim();
+            match term.split_once('%') {
+                Some((value, of)) => {
+                    let value: f64 = value.trim().parse().ok()?;
+                    match of.trim().to_ascii_lowercase().as_str() {
+                        "" | "fs" | "fsd" | "span" | "of span" => tolerance.percent_fs += value,
+                        "rdg" | "rd" | "reading" | "of reading" => {
+                            tolerance.percent_reading += value
+                        }
+                        _ => return None,
+                    }
+                }
+                None => tolerance.absolute += units::parse_uncertainty(term, unit).ok()??.abs(),
+            }
+        }
+        if tolerance == Tolerance::default() {
+            None
+        } else {
+            Some(tolerance)
+        }
+    }
+
+    pub fn describe(&self, unit: Unit) -> String {
+        let mut terms = Vec::new();
+        if self.percent_fs != 0.0 {
+            terms.push(format!("{}% FS", self.percent_fs));
+        }
+        if self.percent_reading != 0.0 {
+            terms.push(format!("{}% rdg", self.percent_reading));
+        }
+        if self.absolute != 0.0 || terms.is_empty() {
+            terms.push(match unit {
+                Unit::Unitless => format!("{}", self.absolute),
+                _ => format!("{} {}", self.absolute, unit),
+            });
+        }
+        format!("±{}", terms.join(" + "))
+    }
+}
+
+// A verification reading compared with its nominal value through the calibration
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Check {
+    pub nominal: f64,
+    // what the calibration makes of the measured input
+    pub indicated: f64,
+    pub limit: f64,
+}
+
+impl Check {
+    pub fn error(&self) -> f64 {
+        self.indicated - self.nominal
+    }
+
+    pub fn passed(&self) -> bool {
+        self.error().abs() <= self.limit
+    }
+
+    // Error as a fraction of the allowed one, over 1 fails
+    pub fn used(&self) -> f64 {
+        self.error().abs() / self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Check, Tolerance};
+    use crate::units::Unit;
+
+    #[test]
+    fn tolerance_specs() {
+        let fs = Tolerance::parse("±0.5% FS", Unit::Bar).unwrap();
+        assert_eq!(0.5, fs.percent_fs);
+        assert!((fs.limit(3.0, 10.0) - 0.05).abs() < 1e-12);
+        let mixed = Tolerance::parse("0.1% rdg + 0.05", Unit::Bar).unwrap();
+        assert!((mixed.limit(-20.0, 100.0) - 0.07).abs() < 1e-12);
+        assert_eq!("±0.1% rdg + 0.05 bar", mixed.describe(Unit::Bar));
+        // typed units scale the fixed part, 0.5 °F is 5/18 °C
+        let t = Tolerance::parse("+-0.5 °F", Unit::Celsius).unwrap();
+        assert!((t.absolute - 5.0 / 18.0).abs() < 1e-9);
+        assert_eq!(None, Tolerance::parse("0.5% of the moon", Unit::Bar));
+        assert_eq!(None, Tolerance::parse("", Unit::Bar));
+    }
+
+    #[test]
+    fn pass_and_fail() {
+        let check = |indicated| Check {
+            nominal: 50.0,
+            indicated,
+            limit: 0.5,
+        };
+        assert!(check(50.4).passed());
+        assert!(check(49.5).passed());
+        assert!(!check(50.6).passed());
+        assert!((check(49.8).used() - 0.4).abs() < 1e-9);
+    }
+}
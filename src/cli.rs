@@ -0,0 +1,483 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::app::App;
+use crate::calculator::adc::{Adc, Polarity};
+use crate::calculator::dac::Dac;
+use crate::calculator::robust::Robust;
+use crate::calculator::thermistor::{Divider, DividerPosition};
+use crate::calculator::verify::Tolerance;
+use crate::calculator::{FitOptions, LoopScaling, ModelKind, PlcScaling, Regression};
+use crate::codegen::Language;
+use crate::lut::{self, LutOptions, Spacing};
+use crate::plot;
+use crate::units::{Quantity, Unit};
+
+// Session settings given on the command line
+pub struct Args {
+    pub input: Quantity,
+    pub output: Quantity,
+    pub loop_scaling: Option<LoopScaling>,
+    // raw range the line is shown over as PLC scaling
+    pub plc_raw: Option<(f64, f64)>,
+    // a PLC's scaling to take the line from, replacing the points
+    pub plc_scaling: Option<PlcScaling>,
+    pub adc: Option<Adc>,
+    // output calibration of a DAC, the input is the code commanded
+    pub dac: Option<Dac>,
+    pub model: ModelKind,
+    pub fit_options: FitOptions,
+    pub divider: Option<Divider>,
+    // thermocouple cold junction in °C
+    pub cold_junction: f64,
+    // level of the confidence and prediction bands, as a fraction
+    pub confidence: f64,
+    pub tolerance: Option<Tolerance>,
+    // session file to start from and to save to
+    pub session: Option<PathBuf>,
+    // directory the calibrations are filed in by serial number
+    pub history: Option<PathBuf>,
+    // session to compare this one against
+    pub compare: Option<PathBuf>,
+    // report templates, the format going by their extension
+    pub templates: Vec<PathBuf>,
+    // report to write instead of starting the terminal
+    pub report: Option<PathBuf>,
+    // chart to export instead of starting the terminal, and the size of exported charts
+    pub chart: Option<PathBuf>,
+    pub chart_size: (u32, u32),
+    // code to write instead of starting the terminal, next to the session unless a file is given
+    pub code: Vec<(Language, Option<PathBuf>)>,
+    // lookup table to write instead of starting the terminal, and how tables are made
+    pub lut: Option<PathBuf>,
+    pub lut_options: LutOptions,
+    // write the files above for every channel of the session rather than the first
+    pub all_channels: bool,
+}
+
+impl Args {
+    // A new session with these settings
+    pub fn into_app(self) -> App {
+        let mut app = App::new(self.input, self.output);
+        let channel = app.active_mut();
+        channel.set_loop_scaling(self.loop_scaling);
+        channel.set_plc_raw(self.plc_raw);
+        channel.set_adc(self.adc);
+        channel.set_dac(self.dac);
+        channel.set_divider(self.divider);
+        channel.set_model_kind(self.model);
+        channel.set_fit_options(self.fit_options);
+        channel.set_cold_junction(self.cold_junction);
+        channel.set_confidence(self.confidence);
+        channel.set_tolerance(self.tolerance);
+        if let Some(dir) = self.history {
+            app.set_history_dir(dir);
+        }
+        app.set_report_templates(self.templates);
+        app.set_chart_size(self.chart_size);
+        app.set_lut_options(self.lut_options);
+        app
+    }
+}
+
+/*
+ * Parse the command line (without the program name)
+ *  --input <name>          what the input measures, e.g. current or "Bridge output"
+ *  --output <name>         what the output measures
+ *  --input-unit <unit>     unit of the input
+ *  --output-unit <unit>    unit of the output
+ *  --loop <lrv>,<urv>      4-20 mA loop scaling with these range values
+ *  --adc <bits>,<vref>[,unipolar|bipolar][,<offset>]
+ *                          ADC front end, the input becomes counts unless a unit is given
+ *  --dac <bits>[,unipolar|bipolar]
+ *                          output calibration of a DAC: the input is the code commanded, the
+ *                          tester gives the code for an output and --code writes that function
+ *  --model <name>          linear, exp, log, power, steinhart-hart, beta, cvd or type-<k|j|t|e|n|r|s|b>
+ *  --refine                refine exp, log and power fits with nonlinear least squares
+ *  --regression <ols|deming[,<ratio>]|tls>
+ *                          how the linear model is fitted, Deming takes the ratio of the output
+ *                          error variance to the input error variance (default 1)
+ *  --robust <off|huber|tukey|ransac>
+ *                          fit that keeps bad readings from dragging the model
+ *  --divider <supply>,<fixed>[,low|high]
+ *                          thermistor read through a divider, the input is the node voltage
+ *  --cold-junction <temp>  thermocouple reference junction temperature, °C unless a unit is given
+ *  --confidence <level>    level of the fit's bands, e.g. 95, 95% or 0.95
+ *  --tolerance <spec>      verification tolerance in the output unit, e.g. "0.5% FS" or "0.1% rdg + 0.05"
+ *  --session <file>        load the session if the file exists, w saves it there. Other flags
+ *                          override the saved settings
+ *  --history <dir>         where saved calibrations are filed by serial number, default history
+ *  --compare <file>        saved session to compare the calibration against
+ *  --template <file>       report template to use instead of the built in one for its format,
+ *                          .md or .html. Give it once per format
+ *  --report <file>         write the report (.md or .html) and exit without starting the terminal
+ *  --chart <file>          export the chart (.svg or .png) and exit without starting the terminal
+ *  --chart-size <w>x<h>    size of exported charts in pixels, default 800x500
+ *  --code <lang>[,<file>]  write the calibration as code and exit without starting the terminal:
+ *                          c, rust, python, arduino, micropython or st. Can be given more than once
+ *  --plc-raw <min>,<max>   raw range of the PLC input card the line is shown over as scaling
+ *  --plc-scaling <raw min>,<raw max>,<eng min>,<eng max>
+ *                          take the line from a PLC's scaling, its ends become the points
+ *  --all-channels          write --report, --chart, --code and --lut for every channel of the
+ *                          session, each file named after its channel, e.g. calibration_tank_2.c
+ *  --lut <file>            write a lookup table (.csv or .h) and exit without starting the terminal
+ *  --lut-size <entries>    entries in lookup tables, default 33
+ *  --lut-spacing <kind>    uniform or adaptive, closer together where the curve bends
+ *  --lut-range <lo>,<hi>   input range lookup tables cover, default the points'
+ *  --lut-scale <factor>    store the outputs as integers of output × factor, e.g. 10 for tenths
+ *  --lut-budget <error>    largest interpolation error allowed in the output unit, picks the
+ *                          smallest table that keeps to it instead of --lut-size
+ */
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, Box<dyn Error>> {
+    let (mut input_name, mut output_name) = (None, None);
+    let (mut input_unit, mut output_unit) = (None, None);
+    let mut loop_scaling = None;
+    let (mut plc_raw, mut plc_scaling) = (None, None);
+    let mut adc = None;
+    let mut dac = None;
+    let mut model = ModelKind::Linear;
+    let mut fit_options = FitOptions::default();
+    let mut divider = None;
+    let mut cold_junction = 0.0;
+    let mut confidence = 0.95;
+    let mut tolerance = None;
+    let mut session = None;
+    let mut history = None;
+    let mut compare = None;
+    let mut templates = Vec::new();
+    let mut report = None;
+    let mut chart = None;
+    let mut chart_size = plot::DEFAULT_SIZE;
+    let mut code = Vec::new();
+    let mut lut = None;
+    let mut lut_options = LutOptions::default();
+    let mut all_channels = false;
+    while let Some(arg) = args.next() {
+        // flags without a value
+        if arg == "--refine" {
+            fit_options.refine = true;
+            continue;
+        }
+        if arg == "--all-channels" {
+            all_channels = true;
+            continue;
+        }
+        let value = args.next().ok_or(format!("{} needs a value", arg))?;
+        match arg.as_str() {
+            "--input" => input_name = Some(value),
+            "--output" => output_name = Some(value),
+            "--input-unit" => input_unit = Some(parse_unit(&value)?),
+            "--output-unit" => output_unit = Some(parse_unit(&value)?),
+            "--loop" => {
+                let [lrv, urv] = parse_list::<2>(&value)?;
+                loop_scaling = Some(LoopScaling::new(lrv, urv));
+                // the loop current is the input unless told otherwise
+                if input_name.is_none() && input_unit.is_none() {
+                    input_unit = Some(Unit::Milliamp);
+                }
+            }
+            "--plc-raw" => {
+                let [lo, hi] = parse_list::<2>(&value)?;
+                if lo == hi {
+                    return Err(format!("expected two different raw values: {}", value).into());
+                }
+                plc_raw = Some((lo, hi));
+            }
+            "--plc-scaling" => {
+                let [raw_min, raw_max, eng_min, eng_max] = parse_list::<4>(&value)?;
+                if raw_min == raw_max {
+                    return Err(format!("expected two different raw values: {}", value).into());
+                }
+                plc_scaling = Some(PlcScaling {
+                    raw_min,
+                    raw_max,
+                    eng_min,
+                    eng_max,
+                });
+            }
+            "--adc" => {
+                adc = Some(parse_adc(&value)?);
+                if input_name.is_none() && input_unit.is_none() {
+                    input_unit = Some(Unit::Count);
+                }
+            }
+            "--dac" => {
+                dac = Some(parse_dac(&value)?);
+                if input_name.is_none() && input_unit.is_none() {
+                    input_unit = Some(Unit::Count);
+                }
+            }
+            "--model" => {
+                model = ModelKind::parse(&value).ok_or(format!("unknown model: {}", value))?
+            }
+            "--regression" => {
+                let (regression, ratio) = parse_regression(&value)?;
+                fit_options.regression = regression;
+                fit_options.variance_ratio = ratio;
+            }
+            "--robust" => {
+                fit_options.robust =
+                    Robust::parse(&value).ok_or(format!("unknown robust fit: {}", value))?
+            }
+            "--divider" => divider = Some(parse_divider(&value)?),
+            "--cold-junction" => {
+                cold_junction = crate::units::parse_value(&value, Unit::Celsius)
+                    .map_err(|_| format!("expected a temperature: {}", value))?;
+            }
+            "--confidence" => confidence = parse_confidence(&value)?,
+            // needs the output unit, which may come later
+            "--tolerance" => tolerance = Some(value),
+            "--session" => session = Some(PathBuf::from(value)),
+            "--history" => history = Some(PathBuf::from(value)),
+            "--compare" => compare = Some(PathBuf::from(value)),
+            "--template" => templates.push(PathBuf::from(value)),
+            "--report" => report = Some(PathBuf::from(value)),
+            "--chart" => chart = Some(PathBuf::from(value)),
+            "--chart-size" => {
+                chart_size = plot::parse_size(&value)
+                    .ok_or(format!("expected a size like 800x500: {}", value))?
+            }
+            "--code" => code.push(parse_code(&value)?),
+            "--lut" => lut = Some(PathBuf::from(value)),
+            "--lut-size" => {
+                lut_options.size = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|n| (2..=lut::MAX_SIZE).contains(n))
+                    .ok_or(format!(
+                        "expected 2 to {} entries: {}",
+                        lut::MAX_SIZE,
+                        value
+                    ))?
+            }
+            "--lut-spacing" => {
+                lut_options.spacing = Spacing::parse(&value)
+                    .ok_or(format!("expected uniform or adaptive: {}", value))?
+            }
+            "--lut-range" => {
+                let [lo, hi] = parse_list::<2>(&value)?;
+                if lo >= hi {
+                    return Err(format!("expected <lo>,<hi> with lo below hi: {}", value).into());
+                }
+                lut_options.range = Some((lo, hi));
+            }
+            "--lut-scale" => lut_options.scale = Some(parse_positive(&value)?),
+            "--lut-budget" => lut_options.budget = Some(parse_positive(&value)?),
+            _ => return Err(format!("unknown argument: {}", arg).into()),
+        }
+    }
+    if dac.is_some() && (adc.is_some() || model.units().is_some()) {
+        return Err("a DAC output calibration takes a linear or curve model and no ADC".into());
+    }
+    let output = Quantity::from_parts(output_name.as_deref(), output_unit, Unit::Unitless);
+    let tolerance = match tolerance {
+        Some(spec) => Some(Tolerance::parse(&spec, output.unit).ok_or(format!(
+            "expected a tolerance like 0.5% FS or 0.2: {}",
+            spec
+        ))?),
+        None => None,
+    };
+    Ok(Args {
+        input: Quantity::from_parts(input_name.as_deref(), input_unit, Unit::Volt),
+        output,
+        loop_scaling,
+        plc_raw,
+        plc_scaling,
+        adc,
+        dac,
+        model,
+        fit_options,
+        divider,
+        cold_junction,
+        confidence,
+        tolerance,
+        session,
+        history,
+        compare,
+        templates,
+        report,
+        chart,
+        chart_size,
+        code,
+        lut,
+        lut_options,
+        all_channels,
+    })
+}
+
+// "rust" or "rust,firmware/level.rs"
+fn parse_code(s: &str) -> Result<(Language, Option<PathBuf>), Box<dyn Error>> {
+    let (lang, file) = match s.split_once(',') {
+        Some((lang, file)) => (lang, Some(PathBuf::from(file.trim()))),
+        None => (s, None),
+    };
+    let lang = Language::parse(lang).ok_or(format!(
+        "unknown language: {}, expected c, rust, python, arduino, micropython or st",
+        lang
+    ))?;
+    Ok((lang, file))
+}
+
+fn parse_positive(s: &str) -> Result<f64, Box<dyn Error>> {
+    match s.trim().parse::<f64>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        _ => Err(format!("expected a number above 0: {}", s).into()),
+    }
+}
+
+// A percentage or a fraction, "95", "95%" and "0.95" are all the same level
+fn parse_confidence(s: &str) -> Result<f64, Box<dyn Error>> {
+    let bad = || format!("expected a confidence level between 0 and 100%: {}", s);
+    let v: f64 = s.trim().trim_end_matches('%').parse().map_err(|_| bad())?;
+    let level = if s.contains('%') || v > 1.0 {
+        v / 100.0
+    } else {
+        v
+    };
+    if level > 0.0 && level < 1.0 {
+        Ok(level)
+    } else {
+        Err(bad().into())
+    }
+}
+
+// "ols", "tls", "deming" or "deming,4"
+fn parse_regression(s: &str) -> Result<(Regression, f64), Box<dyn Error>> {
+    let bad = || format!("expected ols, tls or deming[,<variance ratio>]: {}", s);
+    let mut parts = s.split(',').map(str::trim);
+    let regression = parts.next().and_then(Regression::parse).ok_or_else(bad)?;
+    let ratio: f64 = match (regression, parts.next()) {
+        (_, None) => 1.0,
+        (Regression::Deming, Some(r)) => r.parse().map_err(|_| bad())?,
+        _ => return Err(bad().into()),
+    };
+    if ratio <= 0.0 || !ratio.is_finite() || parts.next().is_some() {
+        return Err(bad().into());
+    }
+    Ok((regression, ratio))
+}
+
+// "3.3,10000" or "5,10 kohm,high". The thermistor sits on the low side unless told otherwise
+fn parse_divider(s: &str) -> Result<Divider, Box<dyn Error>> {
+    let bad = || format!("expected <supply>,<fixed ohms>[,low|high]: {}", s);
+    let mut parts = s.split(',').map(str::trim);
+    let supply = parts
+        .next()
+        .and_then(|v| crate::units::parse_value(v, Unit::Volt).ok())
+        .ok_or_else(bad)?;
+    let fixed = parts
+        .next()
+        .and_then(|v| crate::units::parse_value(v, Unit::Ohm).ok())
+        .ok_or_else(bad)?;
+    let position = match parts.next() {
+        None | Some("low") => DividerPosition::Low,
+        Some("high") => DividerPosition::High,
+        Some(_) => return Err(bad().into()),
+    };
+    if supply <= 0.0 || fixed <= 0.0 {
+        return Err(bad().into());
+    }
+    Ok(Divider {
+        supply,
+        fixed,
+        position,
+    })
+}
+
+// "12,3.3", "16,2.5,bipolar" or "12,3.3,unipolar,-2"
+fn parse_adc(s: &str) -> Result<Adc, Box<dyn Error>> {
+    let bad = || {
+        format!(
+            "expected <bits>,<vref>[,unipolar|bipolar][,<offset>]: {}",
+            s
+        )
+    };
+    let mut parts = s.split(',').map(str::trim);
+    let bits: u32 = parts.next().and_then(|b| b.parse().ok()).ok_or_else(bad)?;
+    let vref: f64 = parts.next().and_then(|v| v.parse().ok()).ok_or_else(bad)?;
+    if bits == 0 || bits > 32 || vref <= 0.0 || !vref.is_finite() {
+        return Err(bad().into());
+    }
+    let mut adc = Adc::new(bits, vref);
+    for part in parts {
+        match part {
+            "unipolar" => adc.polarity = Polarity::Unipolar,
+            "bipolar" => adc.polarity = Polarity::Bipolar,
+            offset => {
+                adc.offset = offset
+                    .parse()
+                    .ok()
+                    .filter(|o: &f64| o.is_finite())
+                    .ok_or_else(bad)?
+            }
+        }
+    }
+    Ok(adc)
+}
+
+// "12" or "16,bipolar"
+fn parse_dac(s: &str) -> Result<Dac, Box<dyn Error>> {
+    let bad = || format!("expected <bits>[,unipolar|bipolar]: {}", s);
+    let mut parts = s.split(',').map(str::trim);
+    let bits: u32 = parts.next().and_then(|b| b.parse().ok()).ok_or_else(bad)?;
+    if bits == 0 || bits > 32 {
+        return Err(bad().into());
+    }
+    let mut dac = Dac::new(bits);
+    match (parts.next(), parts.next()) {
+        (None, _) => {}
+        (Some("unipolar"), None) => dac.polarity = Polarity::Unipolar,
+        (Some("bipolar"), None) => dac.polarity = Polarity::Bipolar,
+        _ => return Err(bad().into()),
+    }
+    Ok(dac)
+}
+
+fn parse_unit(s: &str) -> Result<Unit, Box<dyn Error>> {
+    Ok(Unit::parse(s).ok_or(format!("unknown unit: {}", s))?)
+}
+
+// Parse exactly N comma separated finite numbers, e.g. "0,100"
+fn parse_list<const N: usize>(s: &str) -> Result<[f64; N], Box<dyn Error>> {
+    let vals = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().ok().filter(|v| v.is_finite()))
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(|| format!("expected {} comma separated numbers: {}", N, s))?;
+    vals.try_into()
+        .map_err(|_| format!("expected {} comma separated numbers: {}", N, s).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_adc, parse_list, parse_regression};
+    use crate::calculator::Regression;
+
+    #[test]
+    fn variance_ratio() {
+        assert!(matches!(
+            parse_regression("deming,4"),
+            Ok((Regression::Deming, r)) if r == 4.0
+        ));
+        for bad in ["deming,0", "deming,-1", "deming,nan", "deming,inf", "ols,2"] {
+            assert!(parse_regression(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn adc_reference() {
+        assert!(parse_adc("12,3.3,bipolar,-2").is_ok());
+        for bad in ["12,0", "12,nan", "12,inf", "12,3.3,nan", "0,3.3"] {
+            assert!(parse_adc(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!([4.0, 20.0], parse_list::<2>("4, 20").unwrap());
+        for bad in ["4", "4,20,1", "4,nan", "inf,20"] {
+            assert!(parse_list::<2>(bad).is_err(), "{}", bad);
+        }
+    }
+}
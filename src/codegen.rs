@@ -0,0 +1,1211 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops;
+use std::path::{Path, PathBuf};
+
+use crate::app::Channel;
+use crate::calculator::curve::CurveShape;
+use crate::calculator::dac::Dac;
+use crate::calculator::thermistor::DividerPosition;
+use crate::calculator::{MeasurementType, Model};
+use crate::metadata;
+use crate::units::Unit;
+
+// How many test vectors go with an export, spread over the inputs of the points
+const VECTORS: usize = 9;
+// Fixed iteration counts, the generated code has no early exits
+const NEWTON_STEPS: usize = 20;
+const BISECTION_STEPS: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Language {
+    C,
+    Rust,
+    Python,
+    Arduino,
+    MicroPython,
+    StructuredText,
+}
+
+impl Language {
+    pub const ALL: [Language; 6] = [
+        Language::C,
+        Language::Rust,
+        Language::Python,
+        Language::Arduino,
+        Language::MicroPython,
+        Language::StructuredText,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::C => "C",
+            Language::Rust => "Rust",
+            Language::Python => "Python",
+            Language::Arduino => "Arduino",
+            Language::MicroPython => "MicroPython",
+            Language::StructuredText => "Structured Text",
+        }
+    }
+
+    // Name of the language on the command line
+    pub fn key(&self) -> &'static str {
+        match self {
+            Language::C => "c",
+            Language::Rust => "rust",
+            Language::Python => "python",
+            Language::Arduino => "arduino",
+            Language::MicroPython => "micropython",
+            Language::StructuredText => "st",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Language> {
+        Self::ALL
+            .iter()
+            .find(|l| l.key().eq_ignore_ascii_case(s.trim()))
+            .cloned()
+    }
+
+    // Where the code goes next to a session, e.g. calibration.rs
+    pub fn path_for(&self, session: &Path) -> PathBuf {
+        let ext = match self {
+            Language::C => "c",
+            Language::Rust => "rs",
+            Language::Python => "py",
+            Language::Arduino => "ino",
+            Language::StructuredText => "st",
+            Language::MicroPython => {
+                let stem = session
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("calibration");
+                return session.with_file_name(format!("{}_micropython.py", stem));
+            }
+        };
+        session.with_extension(ext)
+    }
+
+    fn template(&self) -> &'static str {
+        match self {
+            Language::C => include_str!("../templates/code/c.txt"),
+            Language::Rust => include_str!("../templates/code/rust.txt"),
+            Language::Python => include_str!("../templates/code/python.txt"),
+            Language::Arduino => include_str!("../templates/code/arduino.txt"),
+            Language::MicroPython => include_str!("../templates/code/micropython.txt"),
+            Language::StructuredText => include_str!("../templates/code/st.txt"),
+        }
+    }
+
+    // How close the code's results have to be, relative. Boards often only have single precision
+    fn tolerance(&self) -> f64 {
+        match self {
+            Language::Arduino | Language::MicroPython => 1e-4,
+            _ => 1e-9,
+        }
+    }
+
+    fn indent(&self) -> &'static str {
+        match self {
+            Language::Arduino => "  ",
+            _ => "    ",
+        }
+    }
+
+    fn comment(&self, line: &str) -> String {
+        match self {
+            Language::Python | Language::MicroPython => format!("# {}", line).trim_end().to_owned(),
+            Language::StructuredText => format!("(* {} *)", line),
+            _ => format!("// {}", line).trim_end().to_owned(),
+        }
+    }
+}
+
+// Arithmetic on the function's input, the constants and the variables set so far
+#[derive(Clone, PartialEq, Debug)]
+enum Expr {
+    Input,
+    Num(f64),
+    Const(&'static str),
+    Var(&'static str),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Ln(Box<Expr>),
+    Exp(Box<Expr>),
+    Sqrt(Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    // toward zero
+    Trunc(Box<Expr>),
+}
+
+impl From<f64> for Expr {
+    fn from(v: f64) -> Self {
+        Expr::Num(v)
+    }
+}
+
+impl ops::Add for Expr {
+    type Output = Expr;
+    fn add(self, rhs: Expr) -> Expr {
+        Expr::Add(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl ops::Sub for Expr {
+    type Output = Expr;
+    fn sub(self, rhs: Expr) -> Expr {
+        Expr::Sub(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl ops::Mul for Expr {
+    type Output = Expr;
+    fn mul(self, rhs: Expr) -> Expr {
+        Expr::Mul(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl ops::Div for Expr {
+    type Output = Expr;
+    fn div(self, rhs: Expr) -> Expr {
+        Expr::Div(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl Expr {
+    fn ln(self) -> Expr {
+        Expr::Ln(Box::new(self))
+    }
+
+    fn exp(self) -> Expr {
+        Expr::Exp(Box::new(self))
+    }
+
+    fn sqrt(self) -> Expr {
+        Expr::Sqrt(Box::new(self))
+    }
+
+    fn pow(self, power: Expr) -> Expr {
+        Expr::Pow(Box::new(self), Box::new(power))
+    }
+
+    fn trunc(self) -> Expr {
+        Expr::Trunc(Box::new(self))
+    }
+
+    // 1 for sums, 2 for products and 3 for anything that never needs brackets
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Add(..) | Expr::Sub(..) => 1,
+            Expr::Mul(..) | Expr::Div(..) => 2,
+            _ => 3,
+        }
+    }
+
+    fn eval(&self, x: f64, values: &HashMap<&str, f64>) -> f64 {
+        let e = |e: &Expr| e.eval(x, values);
+        match self {
+            Expr::Input => x,
+            Expr::Num(v) => *v,
+            Expr::Const(name) | Expr::Var(name) => values[name],
+            Expr::Add(a, b) => e(a) + e(b),
+            Expr::Sub(a, b) => e(a) - e(b),
+            Expr::Mul(a, b) => e(a) * e(b),
+            Expr::Div(a, b) => e(a) / e(b),
+            Expr::Ln(a) => e(a).ln(),
+            Expr::Exp(a) => e(a).exp(),
+            Expr::Sqrt(a) => e(a).sqrt(),
+            Expr::Pow(a, b) => e(a).powf(e(b)),
+            Expr::Trunc(a) => e(a).trunc(),
+        }
+    }
+
+    // The expression on its own, as a statement's value or a function's argument
+    fn show(&self, lang: Language) -> String {
+        match self {
+            Expr::Num(v) => number(*v),
+            _ => self.operand(lang),
+        }
+    }
+
+    // The expression as part of a bigger one
+    fn operand(&self, lang: Language) -> String {
+        // brackets around a side that binds looser than this, or as loosely on the right of - and /
+        let side = |e: &Expr, right: bool| {
+            let (mine, theirs) = (self.precedence(), e.precedence());
+            let strict = right && matches!(self, Expr::Sub(..) | Expr::Div(..));
+            if theirs < mine || (strict && theirs == mine) {
+                format!("({})", e.operand(lang))
+            } else {
+                e.operand(lang)
+            }
+        };
+        // Rust as f64::exp(x) rather than x.exp(), a literal can't take a method call
+        let call = |f: [&str; 3], args: &[&Expr]| {
+            let [c, rust, st] = f;
+            let shown: Vec<String> = args.iter().map(|a| a.show(lang)).collect();
+            let name = match lang {
+                Language::C | Language::Arduino => c.to_owned(),
+                Language::Python | Language::MicroPython => format!("math.{}", c),
+                Language::StructuredText => st.to_owned(),
+                Language::Rust => format!("f64::{}", rust),
+            };
+            format!("{}({})", name, shown.join(", "))
+        };
+        match self {
+            Expr::Input => "x".to_owned(),
+            Expr::Num(v) if *v < 0.0 => format!("({})", number(*v)),
+            Expr::Num(v) => number(*v),
+            Expr::Const(name) | Expr::Var(name) => name.to_string(),
+            Expr::Add(a, b) => format!("{} + {}", side(a, false), side(b, true)),
+            Expr::Sub(a, b) => format!("{} - {}", side(a, false), side(b, true)),
+            Expr::Mul(a, b) => format!("{} * {}", side(a, false), side(b, true)),
+            Expr::Div(a, b) => format!("{} / {}", side(a, false), side(b, true)),
+            Expr::Ln(a) => call(["log", "ln", "LN"], &[a]),
+            Expr::Exp(a) => call(["exp", "exp", "EXP"], &[a]),
+            Expr::Sqrt(a) => call(["sqrt", "sqrt", "SQRT"], &[a]),
+            Expr::Pow(a, b) => call(["pow", "powf", "EXPT"], &[a, b]),
+            // TRUNC gives an integer in Structured Text
+            Expr::Trunc(a) if lang == Language::StructuredText => {
+                format!("DINT_TO_LREAL(TRUNC({}))", a.show(lang))
+            }
+            Expr::Trunc(a) => call(["trunc", "trunc", "TRUNC"], &[a]),
+        }
+    }
+}
+
+// Only the comparisons the models need, `lhs < rhs` and `lhs <= rhs`
+#[derive(Clone, PartialEq, Debug)]
+enum Stmt {
+    // a new variable
+    Let(&'static str, Expr),
+    // a new variable every branch after it sets
+    Declare(&'static str),
+    Set(&'static str, Expr),
+    If {
+        lhs: Expr,
+        or_equal: bool,
+        rhs: Expr,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>,
+    },
+    Repeat(usize, Vec<Stmt>),
+}
+
+fn if_less(lhs: Expr, rhs: Expr, then: Vec<Stmt>, otherwise: Vec<Stmt>) -> Stmt {
+    Stmt::If {
+        lhs,
+        or_equal: false,
+        rhs,
+        then,
+        otherwise,
+    }
+}
+
+/*
+ * The calibration as a straight run of statements: the session's input is brought into the
+ * model's units, run through the model and the result brought into the session's output units
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Function {
+    pub name: String,
+    constants: Vec<(&'static str, f64)>,
+    body: Vec<Stmt>,
+    result: Expr,
+}
+
+impl Function {
+    // What the generated code works out for x, to check it against the calibration
+    pub fn eval(&self, x: f64) -> f64 {
+        let mut values: HashMap<&str, f64> = self.constants.iter().cloned().collect();
+        run(&self.body, x, &mut values);
+        self.result.eval(x, &values)
+    }
+}
+
+fn run(stmts: &[Stmt], x: f64, values: &mut HashMap<&'static str, f64>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Let(name, e) | Stmt::Set(name, e) => {
+                let v = e.eval(x, values);
+                values.insert(name, v);
+            }
+            Stmt::If {
+                lhs,
+                or_equal,
+                rhs,
+                then,
+                otherwise,
+            } => {
+                let (l, r) = (lhs.eval(x, values), rhs.eval(x, values));
+                let taken = if *or_equal { l <= r } else { l < r };
+                run(if taken { then } else { otherwise }, x, values);
+            }
+            Stmt::Declare(_) => {}
+            Stmt::Repeat(n, body) => {
+                for _ in 0..*n {
+                    run(body, x, values);
+                }
+            }
+        }
+    }
+}
+
+// a x + b as an expression, leaving out what does nothing
+fn affine(e: Expr, a: f64, b: f64) -> Expr {
+    // unit factors are short decimals, without the rounding they pick up on the way
+    let round = |v: f64| format!("{:.12e}", v).parse::<f64>().unwrap_or(v);
+    let (a, b) = (round(a), round(b));
+    let scaled = if a == 1.0 { e } else { Expr::Num(a) * e };
+    if b == 0.0 {
+        scaled
+    } else {
+        scaled + Expr::Num(b)
+    }
+}
+
+// Unit conversions are affine, so two points pin them down
+fn conversion(from: Unit, to: Unit) -> Result<(f64, f64), String> {
+    let bad = |_| format!("can't convert {} to {}", from, to);
+    let b = from.convert(0.0, to).map_err(bad)?;
+    Ok((from.convert(1.0, to).map_err(bad)? - b, b))
+}
+
+// c0 + t (c1 + t (c2 + ...))
+fn horner(coefs: &[f64], t: &Expr) -> Expr {
+    let mut rev = coefs.iter().rev();
+    let first = Expr::Num(*rev.next().unwrap_or(&0.0));
+    rev.fold(first, |acc, c| match c {
+        0.0 => t.clone() * acc,
+        _ => Expr::Num(*c) + t.clone() * acc,
+    })
+}
+
+// "tank_level_from_current", a name any of the languages takes
+pub fn function_name(channel: &Channel) -> String {
+    identifier(&format!(
+        "{} from {}",
+        channel.get_output().name,
+        channel.get_input().name
+    ))
+}
+
+// "code_for_valve_position", the name of an output calibration's function
+pub fn inverse_name(channel: &Channel) -> String {
+    identifier(&format!(
+        "{} for {}",
+        channel.get_input().name,
+        channel.get_output().name
+    ))
+}
+
+// Lower case letters, digits and underscores, for function and file names
+pub fn identifier(raw: &str) -> String {
+    let mut name = String::new();
+    for c in raw.to_ascii_lowercase().chars() {
+        match c {
+            'a'..='z' | '0'..='9' => name.push(c),
+            _ if !name.is_empty() && !name.ends_with('_') => name.push('_'),
+            _ => {}
+        }
+    }
+    let name = name.trim_end_matches('_');
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("f_{}", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+// The session's calibration from input to output as a function
+pub fn function(channel: &Channel) -> Result<Function, String> {
+    let model = channel.get_model().map_err(|e| e.to_string())?;
+    let mut constants = Vec::new();
+    let mut body = Vec::new();
+
+    let (input, output) = (channel.get_input().unit, channel.get_output().unit);
+    let x = match (channel.get_model_kind().units(), channel.get_divider()) {
+        (None, _) => Expr::Input,
+        (Some((Unit::Ohm, _)), Some(divider)) => {
+            let (a, b) = conversion(input, Unit::Volt)?;
+            body.push(Stmt::Let("volts", affine(Expr::Input, a, b)));
+            constants.push(("DIVIDER_SUPPLY", divider.supply));
+            constants.push(("DIVIDER_FIXED", divider.fixed));
+            let (supply, fixed, volts) = (
+                Expr::Const("DIVIDER_SUPPLY"),
+                Expr::Const("DIVIDER_FIXED"),
+                Expr::Var("volts"),
+            );
+            let ohms = match divider.position {
+                DividerPosition::Low => fixed * volts.clone() / (supply - volts),
+                DividerPosition::High => fixed * (supply - volts.clone()) / volts,
+            };
+            body.push(Stmt::Let("ohms", ohms));
+            Expr::Var("ohms")
+        }
+        (Some((unit, _)), _) => {
+            let (a, b) = conversion(input, unit)?;
+            let cj = channel.cold_junction_emf().map_err(|e| e.to_string())?;
+            let mut x = affine(Expr::Input, a, b);
+            if cj != 0.0 {
+                constants.push(("COLD_JUNCTION_EMF", cj));
+                x = x + Expr::Const("COLD_JUNCTION_EMF");
+            }
+            if x == Expr::Input {
+                x
+            } else {
+                body.push(Stmt::Let("model_input", x));
+                Expr::Var("model_input")
+            }
+        }
+    };
+
+    let y = match model {
+        Model::Linear(_) => {
+            let (m, b) = model.get_line().ok_or("the line has no slope")?;
+            constants.extend([("SLOPE", m), ("INTERCEPT", b)]);
+            Expr::Const("SLOPE") * x + Expr::Const("INTERCEPT")
+        }
+        Model::Curve(c) => {
+            constants.extend([("CURVE_A", c.a), ("CURVE_B", c.b)]);
+            let (a, b) = (Expr::Const("CURVE_A"), Expr::Const("CURVE_B"));
+            match c.shape {
+                CurveShape::Exponential => a * (b * x).exp(),
+                CurveShape::Logarithmic => a * x.ln() + b,
+                CurveShape::Power => a * x.pow(b),
+            }
+        }
+        Model::SteinhartHart(sh) => {
+            constants.extend([("SH_A", sh.a), ("SH_B", sh.b), ("SH_C", sh.c)]);
+            body.push(Stmt::Let("ln_r", x.ln()));
+            let l = Expr::Var("ln_r");
+            Expr::Num(1.0)
+                / (Expr::Const("SH_A")
+                    + Expr::Const("SH_B") * l.clone()
+                    + Expr::Const("SH_C") * l.clone() * l.clone() * l)
+        }
+        Model::Beta(b) => {
+            constants.extend([("BETA", b.beta), ("R_NOMINAL", b.r0), ("T_NOMINAL", b.t0)]);
+            Expr::Num(1.0)
+                / (Expr::Num(1.0) / Expr::Const("T_NOMINAL")
+                    + (x / Expr::Const("R_NOMINAL")).ln() / Expr::Const("BETA"))
+        }
+        Model::CallendarVanDusen(cvd) => {
+            constants.extend([
+                ("CVD_R0", cvd.r0),
+                ("CVD_A", cvd.a),
+                ("CVD_B", cvd.b),
+                ("CVD_C", cvd.c),
+            ]);
+            let (a, b, c) = (
+                Expr::Const("CVD_A"),
+                Expr::Const("CVD_B"),
+                Expr::Const("CVD_C"),
+            );
+            let (ratio, t) = (Expr::Var("ratio"), Expr::Var("celsius"));
+            body.push(Stmt::Let("ratio", x / Expr::Const("CVD_R0")));
+            // the quadratic above 0 °C, and the start for Newton below
+            let quadratic = if cvd.b == 0.0 {
+                (ratio.clone() - Expr::Num(1.0)) / a.clone()
+            } else {
+                let disc = a.clone() * a.clone()
+                    - Expr::Num(4.0) * b.clone() * (Expr::Num(1.0) - ratio.clone());
+                (disc.sqrt() - a.clone()) / (Expr::Num(2.0) * b.clone())
+            };
+            body.push(Stmt::Let("celsius", quadratic));
+            if cvd.c != 0.0 {
+                let f = Expr::Num(1.0)
+                    + a.clone() * t.clone()
+                    + b.clone() * t.clone() * t.clone()
+                    + c.clone()
+                        * (t.clone() - Expr::Num(100.0))
+                        * t.clone()
+                        * t.clone()
+                        * t.clone()
+                    - ratio.clone();
+                let df = a
+                    + Expr::Num(2.0) * b * t.clone()
+                    + c * (Expr::Num(4.0) * t.clone() * t.clone() * t.clone()
+                        - Expr::Num(300.0) * t.clone() * t.clone());
+                let newton = vec![Stmt::Set("celsius", t.clone() - f / df)];
+                body.push(if_less(
+                    ratio,
+                    Expr::Num(1.0),
+                    vec![Stmt::Repeat(NEWTON_STEPS, newton)],
+                    vec![],
+                ));
+            }
+            t
+        }
+        Model::Thermocouple(tc) => {
+            let [d0, d1, d2] = tc.deviation;
+            constants.extend([
+                ("DEVIATION_0", d0),
+                ("DEVIATION_1", d1),
+                ("DEVIATION_2", d2),
+            ]);
+            let (lo, hi) = tc.kind.inverse_range();
+            let mid = Expr::Var("mid");
+            body.push(Stmt::Let("mv", x));
+            body.push(Stmt::Let("lo", Expr::Num(lo)));
+            body.push(Stmt::Let("hi", Expr::Num(hi)));
+            // the reference function of the segment mid is in, the last one past them all
+            let (segments, gaussian) = tc.kind.reference_function();
+            let last = &segments[segments.len() - 1];
+            let mut chain = vec![Stmt::Set("emf", horner(last.coefs, &mid))];
+            for segment in segments.iter().rev().skip(1) {
+                chain = vec![Stmt::If {
+                    lhs: mid.clone(),
+                    or_equal: true,
+                    rhs: Expr::Num(segment.upper),
+                    then: vec![Stmt::Set("emf", horner(segment.coefs, &mid))],
+                    otherwise: chain,
+                }];
+            }
+            let mut step = vec![
+                Stmt::Let("mid", (Expr::Var("lo") + Expr::Var("hi")) / Expr::Num(2.0)),
+                Stmt::Declare("emf"),
+            ];
+            step.extend(chain);
+            if let Some([g0, g1, g2]) = gaussian {
+                let bump = Expr::Num(g0)
+                    * (Expr::Num(g1)
+                        * (mid.clone() - Expr::Num(g2))
+                        * (mid.clone() - Expr::Num(g2)))
+                    .exp();
+                step.push(if_less(
+                    Expr::Num(0.0),
+                    mid.clone(),
+                    vec![Stmt::Set("emf", Expr::Var("emf") + bump)],
+                    vec![],
+                ));
+            }
+            let deviation = Expr::Const("DEVIATION_0")
+                + Expr::Const("DEVIATION_1") * mid.clone()
+                + Expr::Const("DEVIATION_2") * mid.clone() * mid.clone();
+            // the reference functions rise with temperature, so below the reading means hotter
+            step.push(if_less(
+                Expr::Var("emf") + deviation,
+                Expr::Var("mv"),
+                vec![Stmt::Set("lo", mid.clone())],
+                vec![Stmt::Set("hi", mid)],
+            ));
+            body.push(Stmt::Repeat(BISECTION_STEPS, step));
+            (Expr::Var("lo") + Expr::Var("hi")) / Expr::Num(2.0)
+        }
+    };
+    let result = match channel.get_model_kind().units() {
+        None => y,
+        Some((_, unit)) => {
+            let (a, b) = conversion(unit, output)?;
+            affine(y, a, b)
+        }
+    };
+    Ok(Function {
+        name: function_name(channel),
+        constants,
+        body,
+        result,
+    })
+}
+
+/*
+ * An output calibration worked back from the output to the DAC code that gives it, rounded to
+ * the nearest code (halves away from zero, like the tester) and kept in the DAC's range
+ */
+pub fn inverse(channel: &Channel, dac: &Dac) -> Result<Function, String> {
+    let model = channel.get_model().map_err(|e| e.to_string())?;
+    let mut constants = Vec::new();
+    let y = Expr::Input;
+    let x = match model {
+        Model::Linear(_) => {
+            let (m, b) = model.get_line().ok_or("the line has no slope")?;
+            constants.extend([("SLOPE", m), ("INTERCEPT", b)]);
+            (y - Expr::Const("INTERCEPT")) / Expr::Const("SLOPE")
+        }
+        Model::Curve(c) => {
+            constants.extend([("CURVE_A", c.a), ("CURVE_B", c.b)]);
+            let (a, b) = (Expr::Const("CURVE_A"), Expr::Const("CURVE_B"));
+            match c.shape {
+                CurveShape::Exponential => (y / a).ln() / b,
+                CurveShape::Logarithmic => ((y - b) / a).exp(),
+                CurveShape::Power => (y / a).pow(Expr::Num(1.0) / b),
+            }
+        }
+        _ => {
+            return Err(format!(
+                "a {} model can't be worked back to a code",
+                channel.get_model_kind().name()
+            ))
+        }
+    };
+    let (min, max) = dac.code_range();
+    constants.extend([("CODE_MIN", min), ("CODE_MAX", max)]);
+    let (code, half) = (Expr::Var("code"), Expr::Num(0.5));
+    let body = vec![
+        Stmt::Let("code", x),
+        if_less(
+            code.clone(),
+            Expr::Num(0.0),
+            vec![Stmt::Set("code", (code.clone() - half.clone()).trunc())],
+            vec![Stmt::Set("code", (code.clone() + half).trunc())],
+        ),
+        if_less(
+            code.clone(),
+            Expr::Const("CODE_MIN"),
+            vec![Stmt::Set("code", Expr::Const("CODE_MIN"))],
+            vec![if_less(
+                Expr::Const("CODE_MAX"),
+                code.clone(),
+                vec![Stmt::Set("code", Expr::Const("CODE_MAX"))],
+                Vec::new(),
+            )],
+        ),
+    ];
+    Ok(Function {
+        name: inverse_name(channel),
+        constants,
+        body,
+        result: code,
+    })
+}
+
+// Codes spread over the points, as the outputs the calibration gives for them and the code back
+pub fn inverse_vectors(channel: &Channel, dac: &Dac) -> Vec<(f64, f64)> {
+    let mut vectors: Vec<(f64, f64)> = test_vectors(channel)
+        .into_iter()
+        .map(|(x, _)| dac.quantize(x))
+        .filter_map(|code| Some((channel.convert(&MeasurementType::Input(code)).ok()?, code)))
+        .filter(|(y, _)| y.is_finite())
+        .collect();
+    vectors.dedup_by(|a, b| a.1 == b.1);
+    vectors
+}
+
+// Inputs spread over the points and what the calibration gives for them, through the model's own
+// get_corresponding_value
+pub fn test_vectors(channel: &Channel) -> Vec<(f64, f64)> {
+    let inputs: Vec<f64> = channel
+        .get_points()
+        .iter()
+        .filter(|p| p.is_valid() && !p.is_excluded())
+        .map(|p| p.get_val()["in"])
+        .collect();
+    let lo = inputs.iter().cloned().fold(f64::INFINITY, f64::min);
+    let hi = inputs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if !lo.is_finite() || !hi.is_finite() {
+        return Vec::new();
+    }
+    (0..VECTORS)
+        .map(|i| lo + (hi - lo) * i as f64 / (VECTORS - 1) as f64)
+        .filter_map(|x| Some((x, channel.convert(&MeasurementType::Input(x)).ok()?)))
+        .filter(|(_, y)| y.is_finite())
+        .collect()
+}
+
+// Numbers that read back exactly and that every language takes as floating point
+pub fn number(v: f64) -> String {
+    let s = format!("{:?}", v);
+    match s.split_once('e') {
+        Some((mantissa, exponent)) if !mantissa.contains('.') => {
+            format!("{}.0e{}", mantissa, exponent)
+        }
+        _ => s,
+    }
+}
+
+// Names given a value anywhere in the statements, in order, and the ones set more than once
+fn variables(stmts: &[Stmt], lets: &mut Vec<&'static str>, sets: &mut Vec<&'static str>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Let(name, _) | Stmt::Declare(name) => lets.push(name),
+            Stmt::Set(name, _) => sets.push(name),
+            Stmt::If {
+                then, otherwise, ..
+            } => {
+                variables(then, lets, sets);
+                variables(otherwise, lets, sets);
+            }
+            Stmt::Repeat(_, body) => variables(body, lets, sets),
+        }
+    }
+}
+
+fn statements(lang: Language, stmts: &[Stmt], depth: usize, mutable: &[&str]) -> String {
+    let pad = lang.indent().repeat(depth);
+    let mut s = String::new();
+    for stmt in stmts {
+        match stmt {
+            Stmt::Let(name, e) => {
+                s += &pad;
+                s += &match lang {
+                    Language::C | Language::Arduino => {
+                        format!("double {} = {};\n", name, e.show(lang))
+                    }
+                    Language::Rust if mutable.contains(name) => {
+                        format!("let mut {} = {};\n", name, e.show(lang))
+                    }
+                    Language::Rust => format!("let {} = {};\n", name, e.show(lang)),
+                    Language::Python | Language::MicroPython => {
+                        format!("{} = {}\n", name, e.show(lang))
+                    }
+                    Language::StructuredText => format!("{} := {};\n", name, e.show(lang)),
+                };
+            }
+            // Python and Structured Text have nothing to say until it is set
+            Stmt::Declare(name) => {
+                s += &match lang {
+                    Language::C | Language::Arduino => format!("{}double {};\n", pad, name),
+                    Language::Rust if mutable.contains(name) => {
+                        format!("{}let mut {};\n", pad, name)
+                    }
+                    Language::Rust => format!("{}let {};\n", pad, name),
+                    _ => String::new(),
+                };
+            }
+            Stmt::Set(name, e) => {
+                s += &pad;
+                s += &match lang {
+                    Language::Python | Language::MicroPython => {
+                        format!("{} = {}\n", name, e.show(lang))
+                    }
+                    Language::StructuredText => format!("{} := {};\n", name, e.show(lang)),
+                    _ => format!("{} = {};\n", name, e.show(lang)),
+                };
+            }
+            Stmt::If {
+                lhs,
+                or_equal,
+                rhs,
+                then,
+                otherwise,
+            } => {
+                let op = if *or_equal { "<=" } else { "<" };
+                let cond = format!("{} {} {}", lhs.show(lang), op, rhs.show(lang));
+                let inner = |body: &[Stmt]| statements(lang, body, depth + 1, mutable);
+                s += &match lang {
+                    Language::C | Language::Arduino | Language::Rust => {
+                        let open = match lang {
+                            Language::Rust => format!("{}if {} {{\n", pad, cond),
+                            _ => format!("{}if ({}) {{\n", pad, cond),
+                        };
+                        match otherwise.as_slice() {
+                            [] => format!("{}{}{}}}\n", open, inner(then), pad),
+                            // an else holding just an if reads as else if
+                            [nested @ Stmt::If { .. }] => {
+                                let chain =
+                                    statements(lang, std::slice::from_ref(nested), depth, mutable);
+                                format!(
+                                    "{}{}{}}} else {}",
+                                    open,
+                                    inner(then),
+                                    pad,
+                                    chain.trim_start()
+                                )
+                            }
+                            _ => format!(
+                                "{}{}{}}} else {{\n{}{}}}\n",
+                                open,
+                                inner(then),
+                                pad,
+                                inner(otherwise),
+                                pad
+                            ),
+                        }
+                    }
+                    Language::Python | Language::MicroPython => match otherwise.as_slice() {
+                        [] => format!("{}if {}:\n{}", pad, cond, inner(then)),
+                        [nested @ Stmt::If { .. }] => {
+                            let chain =
+                                statements(lang, std::slice::from_ref(nested), depth, mutable);
+                            format!(
+                                "{}if {}:\n{}{}el{}",
+                                pad,
+                                cond,
+                                inner(then),
+                                pad,
+                                chain.trim_start()
+                            )
+                        }
+                        _ => format!(
+                            "{}if {}:\n{}{}else:\n{}",
+                            pad,
+                            cond,
+                            inner(then),
+                            pad,
+                            inner(otherwise)
+                        ),
+                    },
+                    Language::StructuredText => {
+                        let mut st = format!("{}IF {} THEN\n{}", pad, cond, inner(then));
+                        let mut rest = otherwise.as_slice();
+                        // ELSIF for else-ifs, so the chain doesn't nest
+                        while let [Stmt::If {
+                            lhs,
+                            or_equal,
+                            rhs,
+                            then,
+                            otherwise,
+                        }] = rest
+                        {
+                            let op = if *or_equal { "<=" } else { "<" };
+                            st += &format!(
+                                "{}ELSIF {} {} {} THEN\n{}",
+                                pad,
+                                lhs.show(lang),
+                                op,
+                                rhs.show(lang),
+                                inner(then)
+                            );
+                            rest = otherwise;
+                        }
+                        if !rest.is_empty() {
+                            st += &format!("{}ELSE\n{}", pad, inner(rest));
+                        }
+                        st + &format!("{}END_IF;\n", pad)
+                    }
+                };
+            }
+            Stmt::Repeat(n, body) => {
+                let inner = statements(lang, body, depth + 1, mutable);
+                s += &match lang {
+                    Language::C | Language::Arduino => format!(
+                        "{}for (int i = 0; i < {}; i++) {{\n{}{}}}\n",
+                        pad, n, inner, pad
+                    ),
+                    Language::Rust => format!("{}for _ in 0..{} {{\n{}{}}}\n", pad, n, inner, pad),
+                    Language::Python | Language::MicroPython => {
+                        format!("{}for _ in range({}):\n{}", pad, n, inner)
+                    }
+                    Language::StructuredText => {
+                        format!("{}FOR i := 1 TO {} DO\n{}{}END_FOR;\n", pad, n, inner, pad)
+                    }
+                };
+            }
+        }
+    }
+    s
+}
+
+impl Function {
+    fn code(&self, lang: Language, header: &[String], vectors: &[(f64, f64)]) -> String {
+        let (mut lets, mut sets) = (Vec::new(), Vec::new());
+        variables(&self.body, &mut lets, &mut sets);
+        let pad = lang.indent();
+
+        let mut body = statements(lang, &self.body, 1, &sets);
+        let result = self.result.show(lang);
+        body += &match lang {
+            Language::C | Language::Arduino => format!("{}return {};", pad, result),
+            Language::Rust => format!("{}{}", pad, result),
+            Language::Python | Language::MicroPython => format!("{}return {}", pad, result),
+            Language::StructuredText => format!("{}{} := {};", pad, self.name, result),
+        };
+        let constants: Vec<String> = self
+            .constants
+            .iter()
+            .map(|(name, v)| match lang {
+                Language::C => format!("static const double {} = {};", name, number(*v)),
+                Language::Arduino => format!("const double {} = {};", name, number(*v)),
+                Language::Rust => format!("pub const {}: f64 = {};", name, number(*v)),
+                Language::Python | Language::MicroPython => format!("{} = {}", name, number(*v)),
+                Language::StructuredText => format!("{}{} : LREAL := {};", pad, name, number(*v)),
+            })
+            .collect();
+        let constants = match lang {
+            Language::StructuredText => constants.join("\n"),
+            _ => constants.join("\n") + "\n",
+        };
+        // Structured Text declares every variable up front, the loop counter too
+        let mut declared: Vec<String> = lets
+            .iter()
+            .map(|name| format!("{}{} : LREAL;", pad, name))
+            .collect();
+        if body.contains("FOR i := ") {
+            declared.push(format!("{}i : INT;", pad));
+        }
+        let rows: Vec<String> = vectors
+            .iter()
+            .map(|(x, y)| match lang {
+                Language::C | Language::Arduino => {
+                    format!("{}{{{}, {}}},", pad, number(*x), number(*y))
+                }
+                Language::Rust => format!("{}({}, {}),", pad.repeat(2), number(*x), number(*y)),
+                _ => format!("{}({}, {}),", pad, number(*x), number(*y)),
+            })
+            .collect();
+        let list = |values: Vec<f64>| {
+            values
+                .into_iter()
+                .map(number)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let comment: Vec<String> = header.iter().map(|l| lang.comment(l)).collect();
+        let values = [
+            ("comment", comment.join("\n")),
+            ("name", self.name.clone()),
+            ("constants", constants),
+            ("variables", declared.join("\n")),
+            ("body", body),
+            ("vectors", rows.join("\n")),
+            ("count", format!("{}", vectors.len())),
+            ("tolerance", number(lang.tolerance())),
+            ("inputs", list(vectors.iter().map(|v| v.0).collect())),
+            ("expected", list(vectors.iter().map(|v| v.1).collect())),
+        ];
+        let mut s = lang.template().to_owned();
+        for (name, value) in values {
+            s = s.replace(&format!("{{{{{}}}}}", name), &value);
+        }
+        s
+    }
+}
+
+/*
+ * The calibration as source code in a language, with its test vectors. The function is checked
+ * against the vectors before it is written, so what goes out does what the calibration does
+ */
+pub fn to_string(channel: &Channel, lang: Language) -> Result<String, String> {
+    let (function, vectors) = match channel.get_dac() {
+        Some(dac) => (inverse(channel, dac)?, inverse_vectors(channel, dac)),
+        None => (function(channel)?, test_vectors(channel)),
+    };
+    if vectors.is_empty() {
+        return Err("the calibration doesn't convert over its points".to_owned());
+    }
+    if let Some((x, _)) = vectors
+        .iter()
+        .find(|(x, y)| (function.eval(*x) - y).abs() > 1e-9 * (1.0 + y.abs()))
+    {
+        return Err(format!(
+            "the generated code doesn't match the calibration at {}",
+            x
+        ));
+    }
+    let (mut input, mut output) = (channel.get_input(), channel.get_output());
+    if channel.get_dac().is_some() {
+        (input, output) = (output, input);
+    }
+    let lo = vectors.iter().map(|v| v.0).fold(f64::INFINITY, f64::min);
+    let hi = vectors
+        .iter()
+        .map(|v| v.0)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let mut header = vec![
+        format!(
+            "{} from {}, generated by Slope-a-Dope {}",
+            output.title(),
+            input.title(),
+            metadata::now()
+        ),
+        format!("Model: {}", channel.get_model_kind().name()),
+    ];
+    header.extend(channel.get_equation().lines().map(str::to_owned));
+    header.extend(channel.get_metadata().describe().lines().map(str::to_owned));
+    header.push(format!(
+        "Calibrated for inputs from {} to {}, nothing checks the range",
+        number(lo),
+        number(hi)
+    ));
+    if let Some(dac) = channel.get_dac() {
+        let (min, max) = dac.code_range();
+        header.push(format!(
+            "Codes for a {} bit DAC, rounded to the nearest and kept from {} to {}",
+            dac.bits, min, max
+        ));
+    }
+    Ok(function.code(lang, &header, &vectors))
+}
+
+pub fn write(channel: &Channel, lang: Language, path: &Path) -> io::Result<()> {
+    let code = to_string(channel, lang).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no {} code: {}", lang.name(), e),
+        )
+    })?;
+    fs::write(path, code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{function, inverse, inverse_vectors, number, test_vectors, to_string, Language};
+    use crate::app::Channel;
+    use crate::calculator::curve::CurveShape;
+    use crate::calculator::dac::Dac;
+    use crate::calculator::thermistor::{Divider, DividerPosition};
+    use crate::calculator::thermocouple::ThermocoupleType;
+    use crate::calculator::{ModelKind, Point};
+    use crate::units::{Quantity, Unit};
+    use std::path::Path;
+
+    fn channel(kind: ModelKind, input: Unit, output: Unit, points: &[(f64, f64)]) -> Channel {
+        let mut channel = Channel::new(
+            Quantity::new("Sensor", input),
+            Quantity::new("Tank level", output),
+        );
+        channel.set_model_kind(kind);
+        channel.set_points(points.iter().map(|p| Point::from(*p)).collect(), None);
+        channel
+    }
+
+    #[test]
+    fn functions_match_models() {
+        let apps = [
+            channel(
+                ModelKind::Linear,
+                Unit::Milliamp,
+                Unit::Percent,
+                &[(4.0, 0.1), (12.0, 50.2), (20.0, 99.9)],
+            ),
+            // a thermistor read in kiloohms, shown in °F
+            channel(
+                ModelKind::SteinhartHart,
+                Unit::Kiloohm,
+                Unit::Fahrenheit,
+                &[(32.65, 32.0), (10.0, 77.0), (3.6, 122.0), (1.5, 167.0)],
+            ),
+            // a PT100 either side of 0 °C, below it needs Newton
+            channel(
+                ModelKind::CallendarVanDusen,
+                Unit::Ohm,
+                Unit::Celsius,
+                &[
+                    (60.26, -100.0),
+                    (100.0, 0.0),
+                    (138.51, 100.0),
+                    (175.86, 200.0),
+                ],
+            ),
+            channel(
+                ModelKind::Thermocouple(ThermocoupleType::K),
+                Unit::Millivolt,
+                Unit::Celsius,
+                &[(-5.891, -200.0), (4.096, 100.0), (41.276, 1000.0)],
+            ),
+        ];
+        // a thermistor at the bottom of a divider, read as the node voltage
+        let mut divided = Channel::new(
+            Quantity::new("Sensor", Unit::Volt),
+            Quantity::new("Tank level", Unit::Celsius),
+        );
+        divided.set_divider(Some(Divider {
+            supply: 3.3,
+            fixed: 10_000.0,
+            position: DividerPosition::Low,
+        }));
+        divided.set_model_kind(ModelKind::Beta);
+        let points = [(2.525, 0.0), (1.65, 25.0), (0.873, 50.0)];
+        divided.set_points(points.iter().map(|p| Point::from(*p)).collect(), None);
+        let c = to_string(&divided, Language::C).unwrap();
+        assert!(c.contains("    double ohms = DIVIDER_FIXED * volts / (DIVIDER_SUPPLY - volts);\n"));
+
+        for channel in apps.iter().chain([&divided]) {
+            let f = function(channel).unwrap();
+            let vectors = test_vectors(channel);
+            assert_eq!(9, vectors.len());
+            for (x, y) in vectors {
+                assert!((f.eval(x) - y).abs() < 1e-9 * (1.0 + y.abs()), "{}", x);
+            }
+        }
+        assert_eq!("tank_level_from_sensor", function(&apps[0]).unwrap().name);
+
+        let rust = to_string(&apps[2], Language::Rust).unwrap();
+        assert!(rust.contains("pub const CVD_R0: f64 = "));
+        assert!(rust.contains("    let mut celsius = "));
+        assert!(rust.contains("pub fn tank_level_from_sensor(x: f64) -> f64 {\n"));
+        let st = to_string(&apps[3], Language::StructuredText).unwrap();
+        assert!(st.contains("    FOR i := 1 TO 64 DO\n"));
+        assert!(st.contains("    i : INT;"));
+        assert!(st.contains("ARRAY[1..9] OF LREAL := [-5.891, "));
+        let python = to_string(&apps[0], Language::Python).unwrap();
+        assert!(python.contains("    return SLOPE * x + INTERCEPT\n"));
+        assert!(!python.contains("{{"));
+    }
+
+    #[test]
+    fn output_calibration() {
+        let dac = Dac::new(12);
+        let mut line = channel(
+            ModelKind::Linear,
+            Unit::Count,
+            Unit::Volt,
+            &[(0.0, 0.012), (2048.0, 5.006), (4095.0, 9.998)],
+        );
+        line.set_dac(Some(dac));
+        let mut curve = channel(
+            ModelKind::Curve(CurveShape::Power),
+            Unit::Count,
+            Unit::Percent,
+            &[(100.0, 2.0), (1000.0, 31.6), (4000.0, 253.0)],
+        );
+        curve.set_dac(Some(dac));
+        for channel in [&line, &curve] {
+            let f = inverse(channel, &dac).unwrap();
+            let vectors = inverse_vectors(channel, &dac);
+            assert_eq!(9, vectors.len());
+            for (y, code) in vectors {
+                assert_eq!(code, f.eval(y));
+                assert_eq!(code, f.eval(y + 1e-6));
+            }
+        }
+        // rounded to the nearest code and kept in range
+        let f = inverse(&line, &dac).unwrap();
+        assert_eq!(0.0, f.eval(-1.0));
+        assert_eq!(4095.0, f.eval(12.0));
+        assert_eq!("sensor_for_tank_level", f.name);
+
+        let c = to_string(&line, Language::C).unwrap();
+        assert!(c.contains(
+            "    double code = (x - INTERCEPT) / SLOPE;
+"
+        ));
+        assert!(c.contains(
+            "        code = trunc(code + 0.5);
+"
+        ));
+        assert!(c.contains(
+            "static const double CODE_MAX = 4095.0;
+"
+        ));
+        let st = to_string(&line, Language::StructuredText).unwrap();
+        assert!(st.contains(
+            "    code := DINT_TO_LREAL(TRUNC(code - 0.5));
+"
+        ));
+        assert!(st.contains(
+            "    ELSIF CODE_MAX < code THEN
+"
+        ));
+        // temperature models aren't worked back
+        let mut cvd = channel(
+            ModelKind::CallendarVanDusen,
+            Unit::Ohm,
+            Unit::Celsius,
+            &[(100.0, 0.0), (138.51, 100.0), (175.86, 200.0)],
+        );
+        cvd.set_dac(Some(dac));
+        assert!(to_string(&cvd, Language::C).is_err());
+    }
+
+    #[test]
+    fn names_and_numbers() {
+        assert_eq!("1.0", number(1.0));
+        assert_eq!("-0.25", number(-0.25));
+        assert_eq!("1.0e-10", number(1e-10));
+        assert_eq!("2.5e20", number(2.5e20));
+        assert_eq!(Some(Language::StructuredText), Language::parse("ST"));
+        assert_eq!(
+            Path::new("out/cal_micropython.py"),
+            Language::MicroPython.path_for(Path::new("out/cal.lsb"))
+        );
+        assert_eq!(
+            Path::new("cal.ino"),
+            Language::Arduino.path_for(Path::new("cal.lsb"))
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::app::Channel;
+use crate::calculator::drift::Calibration;
+use crate::metadata::{self, Field};
+use crate::session;
+use crate::units::Unit;
+
+// Where calibrations are filed when no --history was given
+pub const DEFAULT_DIR: &str = "history";
+
+// A calibration from a sensor's history and the date it was recorded under
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub date: String,
+    pub calibration: Calibration,
+}
+
+/*
+ * The history is a directory per sensor serial number holding a session file per calibration,
+ * named after its date, e.g. history/SN-1042/2026-10-19_06_36_UTC.lsb. Saving the same
+ * calibration again replaces its file. Nothing is filed without a serial number
+ */
+pub fn record(channel: &Channel, dir: &Path) -> io::Result<Option<PathBuf>> {
+    let details = channel.get_metadata();
+    let serial = details.get(Field::Serial);
+    if serial.is_empty() {
+        return Ok(None);
+    }
+    let sensor = dir.join(file_name(serial));
+    fs::create_dir_all(&sensor)?;
+    let date = match details.get(Field::Date) {
+        "" => metadata::now(),
+        date => date.to_owned(),
+    };
+    let path = sensor.join(format!("{}.lsb", file_name(&date)));
+    session::save_channel(channel, &path)?;
+    Ok(Some(path))
+}
+
+/*
+ * Every calibration of a sensor that reads back with a date and a line, oldest first and in the
+ * given units. Files that don't read or are in units that don't convert are left out
+ */
+pub fn load(dir: &Path, serial: &str, input: Unit, output: Unit) -> Vec<Entry> {
+    let Ok(files) = fs::read_dir(dir.join(file_name(serial))) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<Entry> = files
+        .flatten()
+        .map(|f| f.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "lsb"))
+        .filter_map(|p| session::load(&p, &[]).ok())
+        .filter_map(|app| entry(app.into_channel(), input, output))
+        .collect();
+    entries.sort_by(|a, b| a.calibration.day.total_cmp(&b.calibration.day));
+    entries
+}
+
+// The line a session was left with, converted into the given units
+pub fn entry(mut channel: Channel, input: Unit, output: Unit) -> Option<Entry> {
+    let (from_in, from_out) = (channel.get_input().unit, channel.get_output().unit);
+    if from_in.dimension() != input.dimension() || from_out.dimension() != output.dimension() {
+        return None;
+    }
+    if (from_in, from_out) != (input, output) {
+        channel.set_units(input, output);
+    }
+    let date = channel.get_metadata().get(Field::Date).to_owned();
+    let (slope, intercept) = channel.get_line()?;
+    Some(Entry {
+        calibration: Calibration {
+            day: metadata::day_of(&date)?,
+            slope,
+            intercept,
+        },
+        date,
+    })
+}
+
+// Serial numbers and dates made safe to use as file names, dots too so ".." stays put
+fn file_name(s: &str) -> String {
+    s.trim()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, record};
+    use crate::app::Channel;
+    use crate::metadata::Field;
+    use crate::units::{Quantity, Unit};
+
+    #[test]
+    fn file_and_read_back() {
+        let dir = std::env::temp_dir().join(format!("lsb-history-{}", std::process::id()));
+        let mut channel = Channel::new(
+            Quantity::from_unit(Unit::Volt),
+            Quantity::from_unit(Unit::Bar),
+        );
+        // nothing to file it under yet
+        assert_eq!(None, record(&channel, &dir).unwrap());
+        let mut details = channel.get_metadata().clone();
+        details.set(Field::Serial, "../SN 1042");
+        for date in ["2026-04-01 09:00 UTC", "2025-10-01 09:00 UTC"] {
+            details.set(Field::Date, date);
+            channel.set_metadata(details.clone());
+            let path = record(&channel, &dir).unwrap().unwrap();
+            assert!(path.starts_with(dir.join("___SN_1042")));
+        }
+        // read back oldest first, and in other units
+        let entries = load(&dir, "../SN 1042", Unit::Millivolt, Unit::Bar);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(2, entries.len());
+        assert_eq!("2025-10-01 09:00 UTC", entries[0].date);
+        assert!((entries[1].calibration.slope - 0.02).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,442 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::app::Channel;
+use crate::calculator::MeasurementType;
+use crate::codegen;
+use crate::metadata;
+use crate::units::{self, Unit};
+
+// Entries when no --lut-size was given, 2^n + 1 so uniform steps come out round
+pub const DEFAULT_SIZE: usize = 33;
+// Largest table an error budget looks through
+pub const MAX_SIZE: usize = 4096;
+// Places the interpolation is checked at between each pair of entries
+const CHECKS: usize = 16;
+// Resolution the curvature is sampled at for adaptive spacing
+const GRID: usize = 1024;
+
+// Where the table's inputs go
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Spacing {
+    Uniform,
+    // closer together where the curve bends, spreading the interpolation error evenly
+    Adaptive,
+}
+
+impl Spacing {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Spacing::Uniform => "uniform",
+            Spacing::Adaptive => "adaptive",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Spacing> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "uniform" => Some(Spacing::Uniform),
+            "adaptive" => Some(Spacing::Adaptive),
+            _ => None,
+        }
+    }
+}
+
+// How the lookup table is made
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LutOptions {
+    pub size: usize,
+    pub spacing: Spacing,
+    // input range covered, the points' when not given
+    pub range: Option<(f64, f64)>,
+    // store the outputs as whole numbers of 1 / scale, e.g. 10 for tenths
+    pub scale: Option<f64>,
+    // largest interpolation error allowed in output units, picks the smallest table that keeps to it
+    pub budget: Option<f64>,
+}
+
+impl Default for LutOptions {
+    fn default() -> Self {
+        LutOptions {
+            size: DEFAULT_SIZE,
+            spacing: Spacing::Uniform,
+            range: None,
+            scale: None,
+            budget: None,
+        }
+    }
+}
+
+/*
+ * A table of (input, output) entries for linear interpolation, the outputs stored rounded when
+ * the table holds integers, and the largest error interpolating it gives against the calibration
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Lut {
+    pub entries: Vec<(f64, f64)>,
+    pub spacing: Spacing,
+    pub scale: Option<f64>,
+    pub max_error: f64,
+    // input the largest error is at
+    pub at: f64,
+}
+
+impl Lut {
+    // Output at x in output units, held at the ends of the table
+    pub fn interpolate(&self, x: f64) -> f64 {
+        let value = |(_, y): (f64, f64)| match self.scale {
+            Some(scale) => y / scale,
+            None => y,
+        };
+        let n = self.entries.len();
+        let i = self.entries.partition_point(|(xi, _)| *xi <= x);
+        if i == 0 {
+            return value(self.entries[0]);
+        }
+        if i == n {
+            return value(self.entries[n - 1]);
+        }
+        let (a, b) = (self.entries[i - 1], self.entries[i]);
+        value(a) + (value(b) - value(a)) * (x - a.0) / (b.0 - a.0)
+    }
+}
+
+// The calibration from input to output, or why it doesn't go there
+fn model(channel: &Channel, x: f64) -> Result<f64, String> {
+    channel
+        .convert(&MeasurementType::Input(x))
+        .map_err(|e| format!("the calibration doesn't convert {}: {}", x, e))
+}
+
+// Inputs of the valid points that are in the fit
+fn point_range(channel: &Channel) -> Option<(f64, f64)> {
+    let inputs = channel
+        .get_points()
+        .iter()
+        .filter(|p| p.is_valid() && !p.is_excluded())
+        .map(|p| p.get_val()["in"]);
+    let (lo, hi) = inputs.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| {
+        (lo.min(x), hi.max(x))
+    });
+    (lo < hi).then_some((lo, hi))
+}
+
+/*
+ * Inputs spread so the points sit closer where the curve bends. Linear interpolation is off by
+ * about h² |f''| / 8 over a step h, so the steps go as 1 / √|f''| to even that out
+ */
+fn adaptive_inputs(channel: &Channel, (lo, hi): (f64, f64), n: usize) -> Result<Vec<f64>, String> {
+    let xs: Vec<f64> = (0..=GRID)
+        .map(|i| lo + (hi - lo) * i as f64 / GRID as f64)
+        .collect();
+    let ys = xs
+        .iter()
+        .map(|x| model(channel, *x))
+        .collect::<Result<Vec<f64>, String>>()?;
+    let h = (hi - lo) / GRID as f64;
+    let mut density: Vec<f64> = (0..=GRID)
+        .map(|i| {
+            let j = i.clamp(1, GRID - 1);
+            ((ys[j + 1] - 2.0 * ys[j] + ys[j - 1]) / (h * h))
+                .abs()
+                .sqrt()
+        })
+        .collect();
+    // a little everywhere, so straight stretches aren't left with one huge step
+    let floor = density.iter().cloned().fold(0.0, f64::max) * 0.01;
+    if floor == 0.0 || !floor.is_finite() {
+        return Ok(uniform_inputs((lo, hi), n));
+    }
+    density.iter_mut().for_each(|d| *d += floor);
+    let mut cumulative = vec![0.0];
+    for i in 1..=GRID {
+        cumulative.push(cumulative[i - 1] + (density[i - 1] + density[i]) / 2.0);
+    }
+    let total = cumulative[GRID];
+    let mut inputs = vec![lo];
+    for k in 1..n - 1 {
+        let target = total * k as f64 / (n - 1) as f64;
+        let i = cumulative.partition_point(|c| *c < target).clamp(1, GRID);
+        let t = (target - cumulative[i - 1]) / (cumulative[i] - cumulative[i - 1]);
+        inputs.push(xs[i - 1] + t * (xs[i] - xs[i - 1]));
+    }
+    inputs.push(hi);
+    Ok(inputs)
+}
+
+fn uniform_inputs((lo, hi): (f64, f64), n: usize) -> Vec<f64> {
+    (0..n)
+        .map(|i| lo + (hi - lo) * i as f64 / (n - 1) as f64)
+        .collect()
+}
+
+// A table of n entries and how far off it is, checked between the entries and at them
+fn table(
+    channel: &Channel,
+    range: (f64, f64),
+    n: usize,
+    options: &LutOptions,
+) -> Result<Lut, String> {
+    let inputs = match options.spacing {
+        Spacing::Uniform => uniform_inputs(range, n),
+        Spacing::Adaptive => adaptive_inputs(channel, range, n)?,
+    };
+    let entries = inputs
+        .iter()
+        .map(|x| {
+            let y = model(channel, *x)?;
+            Ok((*x, options.scale.map_or(y, |scale| (y * scale).round())))
+        })
+        .collect::<Result<Vec<(f64, f64)>, String>>()?;
+    let mut lut = Lut {
+        entries,
+        spacing: options.spacing,
+        scale: options.scale,
+        max_error: 0.0,
+        at: range.0,
+    };
+    let checks = inputs.windows(2).flat_map(|pair| {
+        (0..CHECKS).map(move |i| pair[0] + (pair[1] - pair[0]) * i as f64 / CHECKS as f64)
+    });
+    for x in checks.chain([range.1]) {
+        let error = (lut.interpolate(x) - model(channel, x)?).abs();
+        if error > lut.max_error {
+            lut.max_error = error;
+            lut.at = x;
+        }
+    }
+    Ok(lut)
+}
+
+// The lookup table the options ask for from the session's calibration
+pub fn build(channel: &Channel, options: &LutOptions) -> Result<Lut, String> {
+    let (lo, hi) = options
+        .range
+        .or_else(|| point_range(channel))
+        .ok_or("no input range, give one or enter points that span one")?;
+    if lo >= hi || !lo.is_finite() || !hi.is_finite() {
+        return Err(format!("the input range {} to {} is empty", lo, hi));
+    }
+    let Some(budget) = options.budget else {
+        return table(channel, (lo, hi), options.size.max(2), options);
+    };
+    let largest = table(channel, (lo, hi), MAX_SIZE, options)?;
+    if largest.max_error > budget {
+        return Err(format!(
+            "even {} entries are off by {}, more than the budget of {}",
+            MAX_SIZE,
+            format_error(largest.max_error, channel.get_output().unit),
+            format_error(budget, channel.get_output().unit)
+        ));
+    }
+    // the error shrinks as the table grows, so halve the way down to the smallest that fits
+    let (mut fits, mut short) = (largest, 1);
+    while fits.entries.len() - short > 1 {
+        let n = (fits.entries.len() + short) / 2;
+        let lut = table(channel, (lo, hi), n, options)?;
+        if lut.max_error <= budget {
+            fits = lut;
+        } else {
+            short = n;
+        }
+    }
+    Ok(fits)
+}
+
+// Errors are small, so with more digits than format_value gives
+pub fn format_error(error: f64, unit: Unit) -> String {
+    match unit {
+        Unit::Unitless => format!("{:.3e}", error),
+        _ => format!("{:.3e} {}", error, unit),
+    }
+}
+
+// Where the tables go next to a session, e.g. calibration_lut.csv
+pub fn path_for(session: &Path, ext: &str) -> PathBuf {
+    let stem = session
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("calibration");
+    session.with_file_name(format!("{}_lut.{}", stem, ext))
+}
+
+// The table as CSV or as a C header, going by the extension
+pub fn to_string(channel: &Channel, lut: &Lut, path: &Path) -> Result<String, String> {
+    let (input, output) = (channel.get_input(), channel.get_output());
+    let mut header = vec![
+        format!(
+            "{} from {}, {} entries with {} spacing, generated by Slope-a-Dope {}",
+            output.title(),
+            input.title(),
+            lut.entries.len(),
+            lut.spacing.name(),
+            metadata::now()
+        ),
+        format!(
+            "Model: {}, interpolating linearly is off by at most {} at {}",
+            channel.get_model_kind().name(),
+            format_error(lut.max_error, output.unit),
+            units::format_value(lut.at, input.unit)
+        ),
+    ];
+    if let Some(scale) = lut.scale {
+        header.push(format!(
+            "Outputs are stored as whole numbers of {}, divide by {}",
+            codegen::number(1.0 / scale),
+            codegen::number(scale)
+        ));
+    }
+    let value = |y: f64| match lut.scale {
+        Some(_) => format!("{}", y as i64),
+        None => codegen::number(y),
+    };
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    match ext.to_ascii_lowercase().as_str() {
+        "csv" => {
+            let mut s: String = header.iter().map(|l| format!("# {}\n", l)).collect();
+            s += "input,output\n";
+            for (x, y) in &lut.entries {
+                s += &format!("{},{}\n", codegen::number(*x), value(*y));
+            }
+            Ok(s)
+        }
+        "h" => {
+            let name = codegen::function_name(channel);
+            let upper = name.to_ascii_uppercase();
+            if lut.scale.is_some() && lut.entries.iter().any(|(_, y)| y.abs() > i32::MAX as f64) {
+                return Err("the outputs don't fit 32 bit integers at this scale".to_owned());
+            }
+            let mut s: String = header.iter().map(|l| format!("// {}\n", l)).collect();
+            s += &format!("#ifndef {u}_LUT_H\n#define {u}_LUT_H\n\n", u = upper);
+            if lut.scale.is_some() {
+                s += "#include <stdint.h>\n\n";
+            }
+            s += &format!("#define {}_LUT_SIZE {}\n", upper, lut.entries.len());
+            let kind = match lut.scale {
+                Some(scale) => {
+                    s += &format!("#define {}_LUT_SCALE {}\n", upper, codegen::number(scale));
+                    "int32_t"
+                }
+                None => "double",
+            };
+            let list = |values: Vec<String>| {
+                values
+                    .chunks(6)
+                    .map(|c| format!("    {},", c.join(", ")))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            let inputs = lut.entries.iter().map(|(x, _)| codegen::number(*x));
+            let outputs = lut.entries.iter().map(|(_, y)| value(*y));
+            s += &format!(
+                "\nstatic const double {}_lut_input[{}] = {{\n{}\n}};\n",
+                name,
+                lut.entries.len(),
+                list(inputs.collect())
+            );
+            s += &format!(
+                "static const {} {}_lut_output[{}] = {{\n{}\n}};\n\n#endif\n",
+                kind,
+                name,
+                lut.entries.len(),
+                list(outputs.collect())
+            );
+            Ok(s)
+        }
+        _ => Err(format!("{}: lookup tables are .csv or .h", path.display())),
+    }
+}
+
+// Build the table and write it, handing it back for its size and error
+pub fn write(channel: &Channel, options: &LutOptions, path: &Path) -> io::Result<Lut> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+    let lut = build(channel, options).map_err(invalid)?;
+    fs::write(path, to_string(channel, &lut, path).map_err(invalid)?)?;
+    Ok(lut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build, to_string, LutOptions, Spacing};
+    use crate::app::Channel;
+    use crate::calculator::thermocouple::ThermocoupleType;
+    use crate::calculator::{ModelKind, Point};
+    use crate::units::{Quantity, Unit};
+    use std::path::Path;
+
+    fn thermocouple() -> Channel {
+        let mut channel = Channel::new(
+            Quantity::from_unit(Unit::Millivolt),
+            Quantity::from_unit(Unit::Celsius),
+        );
+        channel.set_model_kind(ModelKind::Thermocouple(ThermocoupleType::K));
+        let points = [(-5.891, -200.0), (4.096, 100.0), (41.276, 1000.0)];
+        channel.set_points(points.iter().map(|p| Point::from(*p)).collect(), None);
+        channel
+    }
+
+    #[test]
+    fn tables_and_errors() {
+        // a straight line interpolates exactly
+        let line = Channel::new(
+            Quantity::from_unit(Unit::Volt),
+            Quantity::new("Level", Unit::Percent),
+        );
+        let lut = build(&line, &LutOptions::default()).unwrap();
+        assert_eq!(33, lut.entries.len());
+        assert_eq!((0.0, 0.0), lut.entries[0]);
+        assert_eq!((5.0, 100.0), lut.entries[32]);
+        assert!(lut.max_error < 1e-9);
+        assert_eq!(50.0, lut.interpolate(2.5));
+        assert_eq!(100.0, lut.interpolate(7.0));
+
+        // tenths of a percent, so up to half a tenth off
+        let options = LutOptions {
+            size: 3,
+            range: Some((0.0, 0.013)),
+            scale: Some(10.0),
+            ..LutOptions::default()
+        };
+        let lut = build(&line, &options).unwrap();
+        assert_eq!(vec![(0.0, 0.0), (0.0065, 1.0), (0.013, 3.0)], lut.entries);
+        assert!((lut.max_error - 0.04).abs() < 1e-9);
+        let csv = to_string(&line, &lut, Path::new("t.csv")).unwrap();
+        assert!(csv.contains("\ninput,output\n0.0,0\n0.0065,1\n0.013,3\n"));
+        assert!(csv.contains("# Outputs are stored as whole numbers of 0.1, divide by 10.0\n"));
+
+        let channel = thermocouple();
+        let uniform = build(&channel, &LutOptions::default()).unwrap();
+        let adaptive = build(
+            &channel,
+            &LutOptions {
+                spacing: Spacing::Adaptive,
+                ..LutOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(adaptive.max_error < uniform.max_error);
+        let inputs: Vec<f64> = adaptive.entries.iter().map(|e| e.0).collect();
+        assert_eq!((-5.891, 41.276), (inputs[0], inputs[32]));
+        assert!(inputs.windows(2).all(|w| w[0] < w[1]));
+
+        // the smallest table within 0.1 °C, one entry fewer isn't
+        let budget = LutOptions {
+            budget: Some(0.1),
+            ..LutOptions::default()
+        };
+        let lut = build(&channel, &budget).unwrap();
+        assert!(lut.max_error <= 0.1);
+        let smaller = LutOptions {
+            size: lut.entries.len() - 1,
+            ..LutOptions::default()
+        };
+        assert!(build(&channel, &smaller).unwrap().max_error > 0.1);
+        let h = to_string(&channel, &lut, Path::new("t.h")).unwrap();
+        assert!(h.contains(&format!(
+            "#define TEMPERATURE_FROM_VOLTAGE_LUT_SIZE {}\n",
+            lut.entries.len()
+        )));
+        assert!(h.contains("static const double temperature_from_voltage_lut_output["));
+        assert!(to_string(&channel, &lut, Path::new("t.txt")).is_err());
+    }
+}
@@ -1,6 +1,17 @@
 mod app;
 mod calculator;
+mod cli;
+mod codegen;
+mod history;
+mod lut;
+mod metadata;
+mod plot;
+mod png;
+mod report;
+mod session;
+mod svg;
 mod ui;
+mod units;
 use app::App;
 
 use crossterm::{
@@ -13,9 +24,85 @@ use ratatui::{
     Terminal,
 };
 
-use std::{error::Error, io};
+use std::{error::Error, io, path::Path};
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // read the session settings before touching the terminal so bad args print normally
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    let args = cli::parse_args(argv.iter().cloned())?;
+    // a saved session picks up where it was left, with any flags given now on top
+    let (session, compare, plc, report, chart, code, table) = (
+        args.session.clone(),
+        args.compare.clone(),
+        args.plc_scaling,
+        args.report.clone(),
+        args.chart.clone(),
+        args.code.clone(),
+        args.lut.clone(),
+    );
+    let all_channels = args.all_channels;
+    let mut app = match session.as_ref() {
+        Some(path) if path.exists() => session::load(path, &argv)?,
+        _ => args.into_app(),
+    };
+    if let Some(path) = session {
+        app.set_session_path(path);
+    }
+    if let Some(path) = compare {
+        app.active_mut().load_comparison(&path)?;
+    }
+    // after loading, so the PLC's scaling replaces the saved points
+    if let Some(scaling) = plc {
+        app.active_mut().import_plc_scaling(scaling)?;
+    }
+    // files asked for on the command line are written without starting the terminal, one set per
+    // channel with --all-channels
+    let targets: Vec<(usize, Option<String>)> = match all_channels {
+        true => app
+            .get_channel_files()
+            .into_iter()
+            .map(Some)
+            .enumerate()
+            .collect(),
+        false => vec![(app.get_channel(), None)],
+    };
+    for (n, file) in targets {
+        app.select_channel(n);
+        let at = |path: &Path| match file.as_deref() {
+            Some(file) => app::channel_path(path, file),
+            None => path.to_owned(),
+        };
+        if let Some(path) = chart.as_ref().map(|p| at(p)) {
+            plot::write(app.active(), &path, app.get_chart_size())?;
+            println!("Chart written to {}", path.display());
+        }
+        if let Some(path) = report.as_ref().map(|p| at(p)) {
+            report::write(&app, &path)?;
+            println!("Report written to {}", path.display());
+        }
+        for (lang, path) in &code {
+            let path = match path {
+                Some(path) => at(path),
+                None => lang.path_for(&at(app.get_session_path())),
+            };
+            codegen::write(app.active(), *lang, &path)?;
+            println!("{} code written to {}", lang.name(), path.display());
+        }
+        if let Some(path) = table.as_ref().map(|p| at(p)) {
+            let written = lut::write(app.active(), app.get_lut_options(), &path)?;
+            println!(
+                "{} entry lookup table written to {}, off by at most {} at {}",
+                written.entries.len(),
+                path.display(),
+                lut::format_error(written.max_error, app.active().get_output().unit),
+                units::format_value(written.at, app.active().get_input().unit)
+            );
+        }
+    }
+    if chart.is_some() || report.is_some() || !code.is_empty() || table.is_some() {
+        return Ok(());
+    }
+
     //setup terminal
     enable_raw_mode()?;
 
@@ -27,9 +114,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let backend = CrosstermBackend::new(stderr);
     let mut terminal = Terminal::new(backend)?;
 
-    //create app and run it
-    let mut app = App::new();
-
+    //run the app
     let _res: Result<bool, io::Error> = run_app(&mut terminal, &mut app);
 
     // clean up
@@ -37,6 +122,13 @@ fn main() -> Result<(), Box<dyn Error>> {
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
+    // leave the details and coefficients on stdout so they can be copied or piped somewhere
+    let details = app.active().get_metadata().describe();
+    if !details.is_empty() {
+        println!("{}\n", details);
+    }
+    println!("{}", app.active().get_equation());
+
     Ok(())
 }
 
@@ -49,14 +141,13 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
         //thread::sleep(time::Duration::from_millis(5000));
 
         // update app state (waits for keypress)
-        if let Err(_) = app.update_state() {
+        if app.update_state().is_err() {
             break;
         }
 
         // If keypress changed it to quit then break
-        match app.get_mode() {
-            app::Mode::Quit => break,
-            _ => {}
+        if let app::Mode::Quit = app.get_mode() {
+            break;
         }
     }
 
@@ -0,0 +1,222 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// What a calibration record says besides the numbers, for traceability
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Field {
+    SensorModel,
+    Serial,
+    Channel,
+    Technician,
+    Date,
+    ReferenceId,
+    // calibration due date on the reference standard's certificate
+    ReferenceDue,
+    AmbientTemperature,
+    Humidity,
+    Notes,
+}
+
+impl Field {
+    pub const ALL: [Field; 10] = [
+        Field::SensorModel,
+        Field::Serial,
+        Field::Channel,
+        Field::Technician,
+        Field::Date,
+        Field::ReferenceId,
+        Field::ReferenceDue,
+        Field::AmbientTemperature,
+        Field::Humidity,
+        Field::Notes,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Field::SensorModel => "Sensor model",
+            Field::Serial => "Serial number",
+            Field::Channel => "Channel",
+            Field::Technician => "Technician",
+            Field::Date => "Date / time",
+            Field::ReferenceId => "Reference standard",
+            Field::ReferenceDue => "Reference cert due",
+            Field::AmbientTemperature => "Ambient temperature",
+            Field::Humidity => "Humidity",
+            Field::Notes => "Notes",
+        }
+    }
+
+    // Name of the field in a session file
+    pub fn key(&self) -> &'static str {
+        match self {
+            Field::SensorModel => "sensor-model",
+            Field::Serial => "serial",
+            Field::Channel => "channel",
+            Field::Technician => "technician",
+            Field::Date => "date",
+            Field::ReferenceId => "reference",
+            Field::ReferenceDue => "reference-due",
+            Field::AmbientTemperature => "ambient-temperature",
+            Field::Humidity => "humidity",
+            Field::Notes => "notes",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Field> {
+        Self::ALL.iter().find(|f| f.key() == key.trim()).cloned()
+    }
+}
+
+// Free text for each field, blank when not filled in
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Metadata {
+    values: [String; 10],
+}
+
+impl Metadata {
+    pub fn get(&self, field: Field) -> &str {
+        &self.values[field as usize]
+    }
+
+    // Values are single lines, so notes can go into a session file as they are
+    pub fn set(&mut self, field: Field, value: &str) {
+        self.values[field as usize] = value.replace(['\n', '\r'], " ").trim().to_owned();
+    }
+
+    // The filled in fields in form order
+    pub fn filled(&self) -> Vec<(Field, &str)> {
+        Field::ALL
+            .iter()
+            .map(|f| (*f, self.get(*f)))
+            .filter(|(_, v)| !v.is_empty())
+            .collect()
+    }
+
+    // "Label: value" lines for the filled in fields, to head an export
+    pub fn describe(&self) -> String {
+        self.filled()
+            .iter()
+            .map(|(f, v)| format!("{}: {}", f.label(), v))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // Whether the reference's cert due date has passed on `today`, both as YYYY-MM-DD
+    pub fn reference_overdue(&self, today: &str) -> bool {
+        match (date_of(self.get(Field::ReferenceDue)), date_of(today)) {
+            (Some(due), Some(today)) => due < today,
+            _ => false,
+        }
+    }
+}
+
+// (year, month, day) from the start of "2026-03-01" or "2026-03-01 14:30"
+fn date_of(s: &str) -> Option<(i64, u32, u32)> {
+    let mut parts = s.trim().get(..10)?.split('-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Some((year, month, day))
+}
+
+// Days since 1970 of a "YYYY-MM-DD" or "YYYY-MM-DD HH:MM" date, with the time as a fraction
+pub fn day_of(s: &str) -> Option<f64> {
+    let (y, m, d) = date_of(s)?;
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    let time = s.trim().get(10..).map(str::trim).unwrap_or("");
+    let minutes = match time.get(..5).and_then(|t| t.split_once(':')) {
+        Some((h, min)) => h.parse::<f64>().ok()? * 60.0 + min.parse::<f64>().ok()?,
+        None => 0.0,
+    };
+    Some(days_from_civil(y, m, d) as f64 + minutes / 1_440.0)
+}
+
+// "YYYY-MM-DD" of a day since 1970
+pub fn format_day(day: f64) -> String {
+    let (y, m, d) = civil_from_days(day.floor() as i64);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+// Current UTC time as "YYYY-MM-DD HH:MM UTC", there's no date crate to ask
+pub fn now() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    format_timestamp(secs)
+}
+
+fn format_timestamp(secs: i64) -> String {
+    let (y, m, d) = civil_from_days(secs.div_euclid(86_400));
+    let minutes = secs.rem_euclid(86_400) / 60;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        y,
+        m,
+        d,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// The other way round
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = year - i64::from(month <= 2);
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{day_of, format_day, format_timestamp, Field, Metadata};
+
+    #[test]
+    fn timestamps() {
+        assert_eq!("1970-01-01 00:00 UTC", format_timestamp(0));
+        // a leap day and the end of a century
+        assert_eq!("2024-02-29 13:45 UTC", format_timestamp(1_709_214_300));
+        assert_eq!("2000-12-31 23:59 UTC", format_timestamp(978_307_140));
+        // and back to days
+        assert_eq!(Some(19_782.5), day_of("2024-02-29 12:00 UTC"));
+        assert_eq!(Some(0.0), day_of("1970-01-01"));
+        assert_eq!(None, day_of("last Tuesday"));
+        assert_eq!("2024-02-29", format_day(19_782.5));
+    }
+
+    #[test]
+    fn fields() {
+        let mut m = Metadata::default();
+        assert_eq!("", m.describe());
+        m.set(Field::Serial, " SN-1042 ");
+        m.set(Field::Notes, "zeroed\nthen spanned");
+        assert_eq!(
+            "Serial number: SN-1042\nNotes: zeroed then spanned",
+            m.describe()
+        );
+        assert_eq!(Some(Field::ReferenceDue), Field::from_key("reference-due"));
+        // no due date is never overdue
+        assert!(!m.reference_overdue("2026-10-19"));
+        m.set(Field::ReferenceDue, "2026-10-18");
+        assert!(m.reference_overdue("2026-10-19 08:00 UTC"));
+        assert!(!m.reference_overdue("2026-10-18"));
+    }
+}
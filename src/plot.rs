@@ -0,0 +1,320 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::app::{self, Channel};
+use crate::png;
+use crate::svg;
+
+// Size charts are exported at when no --chart-size was given
+pub const DEFAULT_SIZE: (u32, u32) = (800, 500);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl Color {
+    pub const WHITE: Color = Color(255, 255, 255);
+    pub const GRID: Color = Color(221, 221, 221);
+    pub const INK: Color = Color(51, 51, 51);
+    pub const CURVE: Color = Color(0, 128, 0);
+    pub const BAND: Color = Color(70, 130, 180);
+    pub const POINT: Color = Color(139, 0, 139);
+    pub const OUTLIER: Color = Color(220, 20, 60);
+    pub const OTHER: Color = Color(160, 160, 160);
+    pub const TESTER: Color = Color(255, 140, 0);
+}
+
+// How a series is drawn
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Style {
+    Line,
+    Dashed,
+    Dots,
+    // a bigger square, for the tester's point
+    Marker,
+}
+
+pub struct Series {
+    pub data: Vec<(f64, f64)>,
+    pub color: Color,
+    pub style: Style,
+}
+
+// A chart with labelled axes, drawn the way the TUI's charts are but at any resolution
+pub struct Plot {
+    pub x_title: String,
+    pub y_title: String,
+    pub series: Vec<Series>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Anchor {
+    Middle,
+    End,
+}
+
+// What a chart is drawn with, in pixels from the top left. Text sits on its baseline at `at`
+#[derive(Clone, PartialEq, Debug)]
+pub enum Shape {
+    Path {
+        points: Vec<(f64, f64)>,
+        color: Color,
+        width: f64,
+        dashed: bool,
+    },
+    Dot {
+        at: (f64, f64),
+        radius: f64,
+        color: Color,
+    },
+    Square {
+        at: (f64, f64),
+        half: f64,
+        color: Color,
+    },
+    Text {
+        at: (f64, f64),
+        text: String,
+        anchor: Anchor,
+        // reads bottom to top, for the y axis title
+        vertical: bool,
+    },
+}
+
+// Widest a character gets in either output, for leaving room for the labels
+pub const CHAR_WIDTH: f64 = 12.0;
+pub const FONT_SIZE: f64 = 12.0;
+const RIGHT: f64 = 24.0;
+const TOP: f64 = 20.0;
+const BOTTOM: f64 = 56.0;
+
+impl Plot {
+    pub fn layout(&self, width: f64, height: f64) -> Vec<Shape> {
+        let all: Vec<(f64, f64)> = self
+            .series
+            .iter()
+            .flat_map(|s| s.data.iter().cloned())
+            .filter(|(x, y)| x.is_finite() && y.is_finite())
+            .collect();
+        let (x_bounds, y_bounds) = app::bounds_of(&all);
+        let (x0, x1) = x_bounds.bounds;
+        let (y0, y1) = y_bounds.bounds;
+        // room for the y title and the widest y label
+        let widest = y_bounds.labels.iter().map(|l| l.chars().count()).max();
+        let left = 2.0 * FONT_SIZE + CHAR_WIDTH * widest.unwrap_or(0) as f64;
+        let (w, h) = (width - left - RIGHT, height - TOP - BOTTOM);
+        let px = |x: f64| left + (x - x0) / (x1 - x0) * w;
+        let py = |y: f64| TOP + h - (y - y0) / (y1 - y0) * h;
+        let path = |points: Vec<(f64, f64)>, color: Color, width: f64, dashed: bool| Shape::Path {
+            points,
+            color,
+            width,
+            dashed,
+        };
+        let text = |at: (f64, f64), text: &str, anchor: Anchor, vertical: bool| Shape::Text {
+            at,
+            text: text.to_owned(),
+            anchor,
+            vertical,
+        };
+
+        let mut shapes = Vec::new();
+        // grid and tick labels, the labels are evenly spread over the bounds
+        let tick = |n: usize, i: usize, lo: f64, hi: f64| {
+            lo + (hi - lo) * i as f64 / (n - 1).max(1) as f64
+        };
+        let nx = x_bounds.labels.len();
+        for (i, label) in x_bounds.labels.iter().enumerate() {
+            let x = px(tick(nx, i, x0, x1));
+            shapes.push(path(vec![(x, TOP), (x, TOP + h)], Color::GRID, 1.0, false));
+            shapes.push(text((x, TOP + h + 18.0), label, Anchor::Middle, false));
+        }
+        let ny = y_bounds.labels.len();
+        for (i, label) in y_bounds.labels.iter().enumerate() {
+            let y = py(tick(ny, i, y0, y1));
+            shapes.push(path(
+                vec![(left, y), (left + w, y)],
+                Color::GRID,
+                1.0,
+                false,
+            ));
+            shapes.push(text((left - 8.0, y + 5.0), label, Anchor::End, false));
+        }
+        let frame = vec![
+            (left, TOP),
+            (left + w, TOP),
+            (left + w, TOP + h),
+            (left, TOP + h),
+            (left, TOP),
+        ];
+        shapes.push(path(frame, Color::INK, 1.0, false));
+        shapes.push(text(
+            (left + w / 2.0, height - 10.0),
+            &self.x_title,
+            Anchor::Middle,
+            false,
+        ));
+        shapes.push(text(
+            (FONT_SIZE + 6.0, TOP + h / 2.0),
+            &self.y_title,
+            Anchor::Middle,
+            true,
+        ));
+
+        for series in &self.series {
+            let points: Vec<(f64, f64)> = series
+                .data
+                .iter()
+                .filter(|(x, y)| x.is_finite() && y.is_finite())
+                .map(|(x, y)| (px(*x), py(*y)))
+                .collect();
+            match series.style {
+                Style::Line | Style::Dashed if points.len() >= 2 => shapes.push(path(
+                    points,
+                    series.color,
+                    2.0,
+                    series.style == Style::Dashed,
+                )),
+                Style::Line | Style::Dashed => {}
+                Style::Dots => shapes.extend(points.into_iter().map(|at| Shape::Dot {
+                    at,
+                    radius: 4.0,
+                    color: series.color,
+                })),
+                Style::Marker => shapes.extend(points.into_iter().map(|at| Shape::Square {
+                    at,
+                    half: 5.0,
+                    color: series.color,
+                })),
+            }
+        }
+        shapes
+    }
+}
+
+/*
+ * The calibration chart as the points screen draws it: the prediction band, the other stage's
+ * curve, the fitted curve, the points (outliers and excluded ones marked) and the tester's point
+ */
+pub fn calibration(channel: &Channel) -> Plot {
+    let series = |data: Vec<(f64, f64)>, color: Color, style: Style| Series { data, color, style };
+    let mut all: Vec<Series> = channel
+        .get_bands()
+        .prediction
+        .iter()
+        .map(|edge| series(edge.clone(), Color::BAND, Style::Dashed))
+        .collect();
+    all.extend([
+        series(channel.get_other_stage_plot(), Color::OTHER, Style::Line),
+        series(channel.get_plot_data().clone(), Color::CURVE, Style::Line),
+        series(channel.get_point_series(), Color::POINT, Style::Dots),
+        series(channel.get_flagged_series(), Color::OUTLIER, Style::Dots),
+        series(channel.get_excluded_series(), Color::OTHER, Style::Dots),
+    ]);
+    if let Some(test) = channel.get_test_series() {
+        all.push(series(test.to_vec(), Color::TESTER, Style::Marker));
+    }
+    Plot {
+        x_title: channel.get_input().title(),
+        y_title: channel.get_output().title(),
+        series: all,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Image {
+    Svg,
+    Png,
+}
+
+impl Image {
+    // Kind of image file by its extension
+    pub fn from_path(path: &Path) -> Option<Image> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "svg" => Some(Image::Svg),
+            "png" => Some(Image::Png),
+            _ => None,
+        }
+    }
+}
+
+// Export the calibration chart, as SVG or PNG by the file's extension
+pub fn write(channel: &Channel, path: &Path, (width, height): (u32, u32)) -> io::Result<()> {
+    let image = Image::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: charts are .svg or .png", path.display()),
+        )
+    })?;
+    let shapes = calibration(channel).layout(f64::from(width), f64::from(height));
+    match image {
+        Image::Svg => fs::write(path, svg::to_svg(&shapes, width, height)),
+        Image::Png => fs::write(path, png::to_png(&shapes, width, height)),
+    }
+}
+
+// "800x500" and the like, both sides at least 100 pixels so the axes fit
+pub fn parse_size(s: &str) -> Option<(u32, u32)> {
+    let (w, h) = s
+        .trim()
+        .to_ascii_lowercase()
+        .split_once('x')
+        .map(|(w, h)| (w.trim().parse::<u32>(), h.trim().parse::<u32>()))?;
+    match (w.ok()?, h.ok()?) {
+        (w, h) if (100..=10_000).contains(&w) && (100..=10_000).contains(&h) => Some((w, h)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_size, Color, Plot, Series, Shape, Style};
+
+    #[test]
+    fn lays_out() {
+        assert_eq!(Some((640, 400)), parse_size("640x400"));
+        assert_eq!(Some((1200, 800)), parse_size(" 1200 X 800 "));
+        assert_eq!(None, parse_size("10x10"));
+        assert_eq!(None, parse_size("800"));
+
+        let plot = Plot {
+            x_title: "Voltage [V]".to_owned(),
+            y_title: "Level [%]".to_owned(),
+            series: vec![
+                Series {
+                    data: vec![(0.0, 0.0), (10.0, 100.0)],
+                    color: Color::CURVE,
+                    style: Style::Line,
+                },
+                Series {
+                    data: vec![(5.0, 50.0), (f64::NAN, 1.0)],
+                    color: Color::POINT,
+                    style: Style::Dots,
+                },
+            ],
+        };
+        let shapes = plot.layout(600.0, 400.0);
+        // y labels are up to 3 characters, so the plot area starts at 24 + 36 across and runs to
+        // 600 - 24, and from 20 down to 400 - 56
+        let curve = Shape::Path {
+            points: vec![(60.0, 344.0), (576.0, 20.0)],
+            color: Color::CURVE,
+            width: 2.0,
+            dashed: false,
+        };
+        assert!(shapes.contains(&curve));
+        let dots: Vec<&Shape> = shapes
+            .iter()
+            .filter(|s| matches!(s, Shape::Dot { .. }))
+            .collect();
+        assert_eq!(
+            vec![&Shape::Dot {
+                at: (318.0, 182.0),
+                radius: 4.0,
+                color: Color::POINT
+            }],
+            dots
+        );
+    }
+}
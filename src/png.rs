@@ -0,0 +1,441 @@
+use crate::plot::{Anchor, Color, Shape, CHAR_WIDTH};
+
+// Text is the 5x8 font below drawn at twice the size, so a character takes 12 pixels across
+const SCALE: i64 = 2;
+
+/*
+ * A laid out chart as a PNG file. The shapes are rasterized without anti-aliasing into a palette
+ * image, which is then deflated with fixed Huffman codes and runs of the same color as matches
+ */
+pub fn to_png(shapes: &[Shape], width: u32, height: u32) -> Vec<u8> {
+    let mut canvas = Canvas::new(width as usize, height as usize);
+    for shape in shapes {
+        match shape {
+            Shape::Path {
+                points,
+                color,
+                width,
+                dashed,
+            } => canvas.path(points, *color, *width, *dashed),
+            Shape::Dot { at, radius, color } => canvas.disc(*at, *radius, *color),
+            Shape::Square { at, half, color } => {
+                for y in (at.1 - half).round() as i64..(at.1 + half).round() as i64 {
+                    for x in (at.0 - half).round() as i64..(at.0 + half).round() as i64 {
+                        canvas.set(x, y, *color);
+                    }
+                }
+            }
+            Shape::Text {
+                at,
+                text,
+                anchor,
+                vertical,
+            } => canvas.text(*at, text, *anchor, *vertical),
+        }
+    }
+    canvas.encode()
+}
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    // palette indices, a row at a time
+    pixels: Vec<u8>,
+    palette: Vec<Color>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: vec![0; width * height],
+            palette: vec![Color::WHITE],
+        }
+    }
+
+    fn set(&mut self, x: i64, y: i64, color: Color) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let index = match self.palette.iter().position(|c| *c == color) {
+            Some(i) => i,
+            // a chart never gets near 256 colors, but past that they share the last one
+            None if self.palette.len() < 256 => {
+                self.palette.push(color);
+                self.palette.len() - 1
+            }
+            None => 255,
+        };
+        self.pixels[y as usize * self.width + x as usize] = index as u8;
+    }
+
+    // Every pixel whose center is within `radius` of `at`
+    fn disc(&mut self, at: (f64, f64), radius: f64, color: Color) {
+        let r2 = radius * radius;
+        for y in (at.1 - radius).floor() as i64..=(at.1 + radius).ceil() as i64 {
+            for x in (at.0 - radius).floor() as i64..=(at.0 + radius).ceil() as i64 {
+                let (dx, dy) = (x as f64 + 0.5 - at.0, y as f64 + 0.5 - at.1);
+                if dx * dx + dy * dy <= r2 {
+                    self.set(x, y, color);
+                }
+            }
+        }
+    }
+
+    // Segments drawn by stamping a pen along them every half pixel, dashes are 6 on and 4 off
+    fn path(&mut self, points: &[(f64, f64)], color: Color, width: f64, dashed: bool) {
+        let mut travelled = 0.0;
+        for pair in points.windows(2) {
+            let [(x0, y0), (x1, y1)] = [pair[0], pair[1]];
+            let length = (x1 - x0).hypot(y1 - y0);
+            let steps = (length * 2.0).ceil().max(1.0) as usize;
+            for i in 0..=steps {
+                let t = i as f64 / steps as f64;
+                if !dashed || (travelled + t * length) % 10.0 < 6.0 {
+                    self.disc((x0 + t * (x1 - x0), y0 + t * (y1 - y0)), width / 2.0, color);
+                }
+            }
+            travelled += length;
+        }
+    }
+
+    fn text(&mut self, at: (f64, f64), text: &str, anchor: Anchor, vertical: bool) {
+        let advance = CHAR_WIDTH as i64;
+        let length = advance * text.chars().count() as i64 - SCALE;
+        let start = match anchor {
+            Anchor::Middle => -length / 2,
+            Anchor::End => -length,
+        };
+        let (x, y) = (at.0.round() as i64, at.1.round() as i64);
+        for (i, c) in text.chars().enumerate() {
+            let along = start + i as i64 * advance;
+            for (col, bits) in glyph(c).iter().enumerate() {
+                for row in 0..8 {
+                    if bits >> row & 1 == 0 {
+                        continue;
+                    }
+                    // row 7 is the descender, the baseline is under row 6
+                    let (u, v) = (along + col as i64 * SCALE, (row - 7) * SCALE);
+                    for dv in 0..SCALE {
+                        for du in 0..SCALE {
+                            if vertical {
+                                self.set(x + v + dv, y - u - du, Color::INK);
+                            } else {
+                                self.set(x + u + du, y + v + dv, Color::INK);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut header = Vec::new();
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bit palette, deflate, no filtering, not interlaced
+        header.extend([8, 3, 0, 0, 0]);
+        chunk(&mut png, b"IHDR", &header);
+        let palette: Vec<u8> = self.palette.iter().flat_map(|c| [c.0, c.1, c.2]).collect();
+        chunk(&mut png, b"PLTE", &palette);
+        // each row starts with its filter type, none
+        let mut raw = Vec::with_capacity((self.width + 1) * self.height);
+        for row in self.pixels.chunks(self.width) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+        chunk(&mut png, b"IDAT", &zlib(&raw));
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in bytes {
+        crc ^= u32::from(*b);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5_552) {
+        for byte in chunk {
+            a += u32::from(*byte);
+            b += a;
+        }
+        a %= 65_521;
+        b %= 65_521;
+    }
+    (b << 16) | a
+}
+
+// Bits go out least significant first, except Huffman codes which go most significant first
+struct Bits {
+    bytes: Vec<u8>,
+    acc: u32,
+    count: u32,
+}
+
+impl Bits {
+    fn put(&mut self, value: u32, n: u32) {
+        self.acc |= value << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn code(&mut self, code: u32, n: u32) {
+        self.put(code.reverse_bits() >> (32 - n), n);
+    }
+
+    // Fixed Huffman code of a literal/length symbol (RFC 1951 3.2.6)
+    fn symbol(&mut self, s: u32) {
+        match s {
+            0..=143 => self.code(0x30 + s, 8),
+            144..=255 => self.code(0x190 + s - 144, 9),
+            256..=279 => self.code(s - 256, 7),
+            _ => self.code(0xC0 + s - 280, 8),
+        }
+    }
+}
+
+// Length codes from 257 on: the shortest length of each and how many extra bits follow
+const LENGTHS: [(u32, u32); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+
+// A zlib stream of one fixed Huffman block, repeats of the previous byte sent as distance 1 matches
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut bits = Bits {
+        bytes: vec![0x78, 0x01],
+        acc: 0,
+        count: 0,
+    };
+    // last block, fixed codes
+    bits.put(1, 1);
+    bits.put(1, 2);
+    let mut i = 0;
+    while i < data.len() {
+        let run = if i == 0 {
+            0
+        } else {
+            data[i..]
+                .iter()
+                .take(258)
+                .take_while(|b| **b == data[i - 1])
+                .count()
+        };
+        if run >= 3 {
+            let code = LENGTHS
+                .iter()
+                .rposition(|(l, _)| *l as usize <= run)
+                .unwrap();
+            let (base, extra) = LENGTHS[code];
+            bits.symbol(257 + code as u32);
+            bits.put(run as u32 - base, extra);
+            // distance 1 is code 0, five bits
+            bits.code(0, 5);
+            i += run;
+        } else {
+            bits.symbol(u32::from(data[i]));
+            i += 1;
+        }
+    }
+    bits.symbol(256);
+    bits.put(0, 7);
+    let mut bytes = bits.bytes;
+    bytes.extend(adler32(data).to_be_bytes());
+    bytes
+}
+
+// Columns of a character, least significant bit at the top, row 7 for descenders
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        ' '..='~' => FONT[c as usize - 0x20],
+        '°' => [0x00, 0x06, 0x09, 0x09, 0x06],
+        _ => FONT['?' as usize - 0x20],
+    }
+}
+
+// The classic 5x7 LCD font for printable ASCII
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x5F, 0x00, 0x00],
+    [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7F, 0x14, 0x7F, 0x14],
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12],
+    [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x56, 0x20, 0x50],
+    [0x00, 0x08, 0x07, 0x03, 0x00],
+    [0x00, 0x1C, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1C, 0x00],
+    [0x2A, 0x1C, 0x7F, 0x1C, 0x2A],
+    [0x08, 0x08, 0x3E, 0x08, 0x08],
+    [0x00, 0x80, 0x70, 0x30, 0x00],
+    [0x08, 0x08, 0x08, 0x08, 0x08],
+    [0x00, 0x00, 0x60, 0x60, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3E, 0x51, 0x49, 0x45, 0x3E],
+    [0x00, 0x42, 0x7F, 0x40, 0x00],
+    [0x72, 0x49, 0x49, 0x49, 0x46],
+    [0x21, 0x41, 0x49, 0x4D, 0x33],
+    [0x18, 0x14, 0x12, 0x7F, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39],
+    [0x3C, 0x4A, 0x49, 0x49, 0x31],
+    [0x41, 0x21, 0x11, 0x09, 0x07],
+    [0x36, 0x49, 0x49, 0x49, 0x36],
+    [0x46, 0x49, 0x49, 0x29, 0x1E],
+    [0x00, 0x00, 0x14, 0x00, 0x00],
+    [0x00, 0x40, 0x34, 0x00, 0x00],
+    [0x00, 0x08, 0x14, 0x22, 0x41],
+    [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08],
+    [0x02, 0x01, 0x59, 0x09, 0x06],
+    [0x3E, 0x41, 0x5D, 0x59, 0x4E],
+    [0x7C, 0x12, 0x11, 0x12, 0x7C],
+    [0x7F, 0x49, 0x49, 0x49, 0x36],
+    [0x3E, 0x41, 0x41, 0x41, 0x22],
+    [0x7F, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x49, 0x49, 0x49, 0x41],
+    [0x7F, 0x09, 0x09, 0x09, 0x01],
+    [0x3E, 0x41, 0x41, 0x51, 0x73],
+    [0x7F, 0x08, 0x08, 0x08, 0x7F],
+    [0x00, 0x41, 0x7F, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3F, 0x01],
+    [0x7F, 0x08, 0x14, 0x22, 0x41],
+    [0x7F, 0x40, 0x40, 0x40, 0x40],
+    [0x7F, 0x02, 0x1C, 0x02, 0x7F],
+    [0x7F, 0x04, 0x08, 0x10, 0x7F],
+    [0x3E, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x09, 0x09, 0x09, 0x06],
+    [0x3E, 0x41, 0x51, 0x21, 0x5E],
+    [0x7F, 0x09, 0x19, 0x29, 0x46],
+    [0x26, 0x49, 0x49, 0x49, 0x32],
+    [0x03, 0x01, 0x7F, 0x01, 0x03],
+    [0x3F, 0x40, 0x40, 0x40, 0x3F],
+    [0x1F, 0x20, 0x40, 0x20, 0x1F],
+    [0x3F, 0x40, 0x38, 0x40, 0x3F],
+    [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x03, 0x04, 0x78, 0x04, 0x03],
+    [0x61, 0x59, 0x49, 0x4D, 0x43],
+    [0x00, 0x7F, 0x41, 0x41, 0x41],
+    [0x02, 0x04, 0x08, 0x10, 0x20],
+    [0x00, 0x41, 0x41, 0x41, 0x7F],
+    [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40],
+    [0x00, 0x03, 0x07, 0x08, 0x00],
+    [0x20, 0x54, 0x54, 0x78, 0x40],
+    [0x7F, 0x28, 0x44, 0x44, 0x38],
+    [0x38, 0x44, 0x44, 0x44, 0x28],
+    [0x38, 0x44, 0x44, 0x28, 0x7F],
+    [0x38, 0x54, 0x54, 0x54, 0x18],
+    [0x00, 0x08, 0x7E, 0x09, 0x02],
+    [0x18, 0xA4, 0xA4, 0x9C, 0x78],
+    [0x7F, 0x08, 0x04, 0x04, 0x78],
+    [0x00, 0x44, 0x7D, 0x40, 0x00],
+    [0x20, 0x40, 0x40, 0x3D, 0x00],
+    [0x7F, 0x10, 0x28, 0x44, 0x00],
+    [0x00, 0x41, 0x7F, 0x40, 0x00],
+    [0x7C, 0x04, 0x78, 0x04, 0x78],
+    [0x7C, 0x08, 0x04, 0x04, 0x78],
+    [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0xFC, 0x18, 0x24, 0x24, 0x18],
+    [0x18, 0x24, 0x24, 0x18, 0xFC],
+    [0x7C, 0x08, 0x04, 0x04, 0x08],
+    [0x48, 0x54, 0x54, 0x54, 0x24],
+    [0x04, 0x04, 0x3F, 0x44, 0x24],
+    [0x3C, 0x40, 0x40, 0x20, 0x7C],
+    [0x1C, 0x20, 0x40, 0x20, 0x1C],
+    [0x3C, 0x40, 0x30, 0x40, 0x3C],
+    [0x44, 0x28, 0x10, 0x28, 0x44],
+    [0x4C, 0x90, 0x90, 0x90, 0x7C],
+    [0x44, 0x64, 0x54, 0x4C, 0x44],
+    [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x77, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00],
+    [0x02, 0x01, 0x02, 0x04, 0x02],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, crc32, to_png, zlib};
+    use crate::plot::{Color, Shape};
+
+    #[test]
+    fn encodes() {
+        // check values from the PNG and zlib specs
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+        assert_eq!(0x11E6_0398, adler32(b"Wikipedia"));
+        // "aaaaa" is a literal then a match of 4 at distance 1
+        assert_eq!(
+            vec![0x78, 0x01, 0x4B, 0x04, 0x01, 0x00, 0x05, 0xB4, 0x01, 0xE6],
+            zlib(b"aaaaa")
+        );
+
+        let dot = Shape::Dot {
+            at: (5.0, 5.0),
+            radius: 2.0,
+            color: Color::POINT,
+        };
+        let png = to_png(&[dot], 10, 8);
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &png[..8]);
+        assert_eq!(&b"IHDR"[..], &png[12..16]);
+        assert_eq!([0, 0, 0, 10, 0, 0, 0, 8, 8, 3], png[16..26]);
+        // white and the dot's color
+        assert_eq!(&[0, 0, 0, 6][..], &png[33..37]);
+        assert_eq!(&[255, 255, 255, 139, 0, 139][..], &png[41..47]);
+        assert_eq!(&b"IEND"[..], &png[png.len() - 8..png.len() - 4]);
+    }
+}
@@ -0,0 +1,490 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::app::{App, Channel};
+use crate::calculator::MeasurementType;
+use crate::metadata::{self, Field};
+use crate::plot;
+use crate::svg;
+use crate::units;
+
+// Templates the report starts from when the team hasn't given their own with --template
+const MARKDOWN_TEMPLATE: &str = include_str!("../templates/report.md");
+const HTML_TEMPLATE: &str = include_str!("../templates/report.html");
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Markdown,
+    Html,
+}
+
+impl Format {
+    // Format of a report or template file by its extension
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "md" | "markdown" => Some(Format::Markdown),
+            "html" | "htm" => Some(Format::Html),
+            _ => None,
+        }
+    }
+
+    pub fn default_template(&self) -> &'static str {
+        match self {
+            Format::Markdown => MARKDOWN_TEMPLATE,
+            Format::Html => HTML_TEMPLATE,
+        }
+    }
+
+    // Plain text made safe for the format
+    fn text(&self, s: &str) -> String {
+        match self {
+            Format::Markdown => s.to_owned(),
+            Format::Html => svg::escape(s),
+        }
+    }
+
+    // Lines of text as a paragraph
+    fn lines(&self, lines: &[String]) -> String {
+        match self {
+            Format::Markdown => lines.join("  \n"),
+            Format::Html => format!(
+                "<p>{}</p>",
+                lines
+                    .iter()
+                    .map(|l| self.text(l))
+                    .collect::<Vec<_>>()
+                    .join("<br>\n")
+            ),
+        }
+    }
+
+    fn table(&self, header: &[String], rows: &[Vec<String>]) -> String {
+        match self {
+            Format::Markdown => {
+                // pipes would end a cell early
+                let row = |cells: &[String]| {
+                    let cells: Vec<String> = cells.iter().map(|c| c.replace('|', "\\|")).collect();
+                    format!("| {} |\n", cells.join(" | "))
+                };
+                let mut s = row(header);
+                s += &format!("|{}\n", "---|".repeat(header.len()));
+                for r in rows {
+                    s += &row(r);
+                }
+                s
+            }
+            Format::Html => {
+                let row = |cells: &[String], tag: &str| {
+                    let cells: String = cells
+                        .iter()
+                        .map(|c| format!("<{tag}>{}</{tag}>", self.text(c)))
+                        .collect();
+                    format!("<tr>{}</tr>\n", cells)
+                };
+                let mut s = format!("<table>\n{}", row(header, "th"));
+                for r in rows {
+                    s += &row(r, "td");
+                }
+                s + "</table>"
+            }
+        }
+    }
+
+    // The chart inline in HTML, as an SVG data URI image in Markdown so the file stands alone
+    fn chart(&self, svg: &str) -> String {
+        match self {
+            Format::Markdown => format!(
+                "![Calibration curve](data:image/svg+xml;base64,{})",
+                base64(svg.as_bytes())
+            ),
+            Format::Html => format!("<figure>\n{}</figure>", svg),
+        }
+    }
+}
+
+/*
+ * Fill a template in. Each {{name}} is replaced, names it doesn't know are left as they are
+ *  title, generated, model, equation       plain text
+ *  details, coefficients, statistics,
+ *  points, verification, uncertainty       paragraphs and tables
+ *  chart                                   the calibration curve with its points and bands
+ */
+pub fn to_string(app: &App, format: Format, template: &str) -> String {
+    let channel = app.active();
+    let details = channel.get_metadata();
+    let title = match details.get(Field::Serial) {
+        "" => "Calibration report".to_owned(),
+        serial => format!("Calibration report: {}", serial),
+    };
+    let fields: Vec<Vec<String>> = details
+        .filled()
+        .iter()
+        .map(|(f, v)| vec![f.label().to_owned(), v.to_string()])
+        .collect();
+    let details = if fields.is_empty() {
+        format.lines(&["No calibration details were recorded.".to_owned()])
+    } else {
+        format.table(&["Detail".to_owned(), "Value".to_owned()], &fields)
+    };
+    let values = [
+        ("title", format.text(&title)),
+        ("generated", format.text(&metadata::now())),
+        ("model", format.text(channel.get_model_kind().name())),
+        ("equation", format.text(&channel.get_equation())),
+        ("details", details),
+        (
+            "coefficients",
+            format.lines(
+                &channel
+                    .get_line_val()
+                    .lines()
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>(),
+            ),
+        ),
+        ("statistics", format.lines(&statistics(channel))),
+        ("points", points(channel, format)),
+        ("verification", verification(channel, format)),
+        ("uncertainty", uncertainty(channel, format)),
+        ("chart", format.chart(&chart(channel, app.get_chart_size()))),
+    ];
+    let mut s = template.to_owned();
+    for (name, value) in values {
+        s = s.replace(&format!("{{{{{}}}}}", name), &value);
+    }
+    s
+}
+
+/*
+ * Write the report, in the format the file's extension asks for. The team's template for that
+ * format is used when one was given
+ */
+pub fn write(app: &App, path: &Path) -> io::Result<()> {
+    let format = Format::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: reports are .md or .html", path.display()),
+        )
+    })?;
+    let template = match app
+        .get_report_templates()
+        .iter()
+        .find(|t| Format::from_path(t) == Some(format))
+    {
+        Some(t) => fs::read_to_string(t)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", t.display(), e)))?,
+        None => format.default_template().to_owned(),
+    };
+    fs::write(path, to_string(app, format, &template))
+}
+
+// What the calibration makes of a point's input, and the point's output less that
+fn residual(channel: &Channel, x: f64, y: f64) -> Option<(f64, f64)> {
+    let fitted = channel.convert(&MeasurementType::Input(x)).ok()?;
+    Some((fitted, y - fitted))
+}
+
+// How well the model fits the points it was given, and how it was fitted
+fn statistics(channel: &Channel) -> Vec<String> {
+    let unit = channel.get_output().unit;
+    let residuals: Vec<f64> = channel
+        .get_points()
+        .iter()
+        .filter(|p| p.is_valid() && !p.is_excluded())
+        .filter_map(|p| {
+            let vals = p.get_val();
+            residual(channel, vals["in"], vals["out"]).map(|(_, r)| r)
+        })
+        .collect();
+    let options = channel.get_fit_options();
+    let mut lines = vec![format!(
+        "Fit: {} regression, robust {}, {:.0}% bands",
+        options.regression.name(),
+        options.robust.name().to_ascii_lowercase(),
+        100.0 * channel.get_confidence()
+    )];
+    let excluded = channel
+        .get_points()
+        .iter()
+        .filter(|p| p.is_excluded())
+        .count();
+    let outliers = channel
+        .get_points()
+        .iter()
+        .zip(channel.get_outliers())
+        .filter(|(p, o)| **o && !p.is_excluded())
+        .count();
+    lines.push(format!(
+        "Points used: {}, excluded: {}, flagged as outliers: {}",
+        residuals.len(),
+        excluded,
+        outliers
+    ));
+    if !residuals.is_empty() {
+        let rms = (residuals.iter().map(|r| r * r).sum::<f64>() / residuals.len() as f64).sqrt();
+        let max = residuals.iter().fold(
+            0.0,
+            |acc: f64, r| if r.abs() > acc.abs() { *r } else { acc },
+        );
+        lines.push(format!(
+            "RMS residual: {}, largest residual: {}",
+            units::format_value(rms, unit),
+            units::format_value(max, unit)
+        ));
+    }
+    if let Some(ols) = channel.get_ols_comparison() {
+        lines.push(ols);
+    }
+    if let Some((probe, dev)) = channel.get_standard_deviation() {
+        lines.push(format!(
+            "Largest deviation from the {} standard curve: {}",
+            probe,
+            units::format_value(dev, unit)
+        ));
+    }
+    lines
+}
+
+// The point table with the fitted value and residual of each point
+fn points(channel: &Channel, format: Format) -> String {
+    if channel.get_points().iter().all(|p| !p.is_valid()) {
+        return format.lines(&["No calibration points.".to_owned()]);
+    }
+    let header = [
+        "#".to_owned(),
+        channel.get_input().title(),
+        channel.get_output().title(),
+        "Fitted".to_owned(),
+        "Residual".to_owned(),
+        "u(in)".to_owned(),
+        "u(out)".to_owned(),
+        "".to_owned(),
+    ];
+    let number = |v: Option<f64>| v.map(|v| format!("{:.4}", v)).unwrap_or_default();
+    let rows: Vec<Vec<String>> = channel
+        .get_points()
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.is_valid())
+        .map(|(i, p)| {
+            let vals = p.get_val();
+            let (x, y) = (vals["in"], vals["out"]);
+            let fit = residual(channel, x, y);
+            let (u_in, u_out) = p.get_uncertainty();
+            let note = if p.is_excluded() {
+                "excluded"
+            } else if channel.get_outliers().get(i) == Some(&true) {
+                "outlier"
+            } else {
+                ""
+            };
+            vec![
+                format!("{}", i + 1),
+                format!("{:.4}", x),
+                format!("{:.4}", y),
+                number(fit.map(|f| f.0)),
+                fit.map(|f| format!("{:+.4}", f.1)).unwrap_or_default(),
+                number(u_in),
+                number(u_out),
+                note.to_owned(),
+            ]
+        })
+        .collect();
+    format.table(&header, &rows)
+}
+
+// Verification readings against the tolerance, with a pass count under them
+fn verification(channel: &Channel, format: Format) -> String {
+    let checks = channel.get_verification();
+    if checks.is_empty() {
+        return format.lines(&["No verification readings.".to_owned()]);
+    }
+    let unit = channel.get_output().unit;
+    let header = [
+        "#",
+        "Measured",
+        "Nominal",
+        "Indicated",
+        "Error",
+        "Limit",
+        "Result",
+    ]
+    .map(str::to_owned);
+    let rows: Vec<Vec<String>> = channel
+        .get_verification_points()
+        .iter()
+        .zip(&checks)
+        .enumerate()
+        .map(|(i, (p, check))| {
+            let vals = p.get_val();
+            let mut row = vec![format!("{}", i + 1)];
+            row.extend(["in", "out"].map(|k| {
+                vals.get(k)
+                    .filter(|_| p.is_valid())
+                    .map(|v| format!("{:.4}", v))
+                    .unwrap_or_default()
+            }));
+            match check {
+                Some(c) => {
+                    let result = match c.limit.is_finite() {
+                        true if c.passed() => format!("PASS {:.0}%", 100.0 * c.used()),
+                        true => format!("FAIL {:.0}%", 100.0 * c.used()),
+                        false => String::new(),
+                    };
+                    row.extend([
+                        format!("{:.4}", c.indicated),
+                        format!("{:+.4}", c.error()),
+                        if c.limit.is_finite() {
+                            format!("±{:.4}", c.limit)
+                        } else {
+                            String::new()
+                        },
+                        result,
+                    ]);
+                }
+                None => row.extend(vec![String::new(); 4]),
+            }
+            row
+        })
+        .collect();
+    let summary = match channel.get_tolerance() {
+        Some(t) => {
+            let held: Vec<_> = checks.iter().flatten().collect();
+            let passed = held.iter().filter(|c| c.passed()).count();
+            let verdict = if passed == held.len() { "PASS" } else { "FAIL" };
+            format!(
+                "Tolerance {}: {} of {} readings within it, {}",
+                t.describe(unit),
+                passed,
+                held.len(),
+                verdict
+            )
+        }
+        None => "No tolerance was set, readings are not judged".to_owned(),
+    };
+    format!(
+        "{}\n\n{}",
+        format.table(&header, &rows),
+        format.lines(&[summary])
+    )
+}
+
+// The uncertainty budget at the working point, when there's anything in it
+fn uncertainty(channel: &Channel, format: Format) -> String {
+    let budget = match channel.get_budget() {
+        Some(b) if !b.terms.is_empty() || b.fit.is_some() => b,
+        _ => return format.lines(&["No uncertainty budget.".to_owned()]),
+    };
+    let unit = channel.get_output().unit;
+    let header = ["Contributor", "Of", "u(output)", "ν"].map(str::to_owned);
+    let dof = |dof: Option<f64>| dof.map(|d| format!("{:.0}", d)).unwrap_or("∞".to_owned());
+    let mut rows: Vec<Vec<String>> = channel
+        .get_contributors()
+        .iter()
+        .zip(&budget.terms)
+        .map(|(c, t)| {
+            vec![
+                c.name.clone(),
+                c.side.name().to_owned(),
+                format!("{:.4}", t.contribution()),
+                dof(t.dof),
+            ]
+        })
+        .collect();
+    if let Some(fit) = budget.fit {
+        rows.push(vec![
+            "Fit residual".to_owned(),
+            "out".to_owned(),
+            format!("{:.4}", fit.contribution()),
+            dof(fit.dof),
+        ]);
+    }
+    let c = &budget.combined;
+    let summary = format!(
+        "At input {:.4}: combined u = {}, expanded U = {} (k = {:.2})",
+        budget.at,
+        units::format_value(c.standard, unit),
+        units::format_value(c.expanded(), unit),
+        c.k
+    );
+    format!(
+        "{}\n\n{}",
+        format.table(&header, &rows),
+        format.lines(&[summary])
+    )
+}
+
+// The calibration chart as an SVG, at the size charts are exported at
+fn chart(channel: &Channel, (width, height): (u32, u32)) -> String {
+    let shapes = plot::calibration(channel).layout(f64::from(width), f64::from(height));
+    svg::to_svg(&shapes, width, height)
+}
+
+// Standard base64 with padding, for the Markdown chart's data URI
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64, to_string, Format};
+    use crate::app::App;
+    use crate::metadata::Field;
+    use crate::units::{Quantity, Unit};
+    use std::path::Path;
+
+    #[test]
+    fn fills_templates() {
+        assert_eq!("", base64(b""));
+        assert_eq!("TQ==", base64(b"M"));
+        assert_eq!("TWE=", base64(b"Ma"));
+        assert_eq!("TWFu", base64(b"Man"));
+        assert_eq!(
+            Some(Format::Html),
+            Format::from_path(Path::new("r/cert.HTM"))
+        );
+        assert_eq!(None, Format::from_path(Path::new("cert.pdf")));
+
+        let mut app = App::new(
+            Quantity::from_unit(Unit::Volt),
+            Quantity::new("Level", Unit::Percent),
+        );
+        let mut details = app.active().get_metadata().clone();
+        details.set(Field::Serial, "LT<7>");
+        app.active_mut().set_metadata(details);
+        app.active_mut().add_verification();
+
+        let md = to_string(&app, Format::Markdown, Format::Markdown.default_template());
+        assert!(md.starts_with("# Calibration report: LT<7>\n"));
+        assert!(md.contains("| Serial number | LT<7> |"));
+        assert!(md.contains("](data:image/svg+xml;base64,"));
+        assert!(!md.contains("{{"));
+
+        let html = to_string(&app, Format::Html, "<h1>{{title}}</h1>{{chart}}{{custom}}");
+        assert!(html.starts_with("<h1>Calibration report: LT&lt;7&gt;</h1><figure>\n<svg"));
+        // names the report doesn't know are left for whoever wrote the template to spot
+        assert!(html.ends_with("</figure>{{custom}}"));
+        let html = to_string(&app, Format::Html, Format::Html.default_template());
+        assert!(html.contains("<th>Level [%]</th>"));
+        assert!(html.contains("No tolerance was set"));
+    }
+}
@@ -0,0 +1,549 @@
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::app::{App, Channel, Stage};
+use crate::calculator::adc::Polarity;
+use crate::calculator::budget::{Contributor, Distribution, Side};
+use crate::calculator::thermistor::DividerPosition;
+use crate::calculator::{Point, Regression};
+use crate::cli;
+use crate::metadata::{Field, Metadata};
+
+// Where w saves when no --session was given
+pub const DEFAULT_PATH: &str = "calibration.lsb";
+
+/*
+ * A session is a plain text file of [sections], # starts a comment
+ *  [settings]              "flag = value" lines, the command line flags without their dashes
+ *  [metadata]              "key = value" lines of the calibration details
+ *  [points]                input, output, u(in), u(out), excluded - blank when not given
+ *  [as-found] / [as-left]  the shelved stage's points in the same columns
+ *  [verification]          measured input, nominal output
+ *  [budget]                of, value, distribution, ν, name
+ *  [channel]               starts the next channel, its sections following as above
+ * Numbers are written in full so a session reads back exactly
+ */
+pub fn to_string(app: &App) -> String {
+    let channels: Vec<String> = app.get_channels().iter().map(channel).collect();
+    format!("# Slope-a-Dope session\n{}", channels.join("\n[channel]\n"))
+}
+
+fn channel(app: &Channel) -> String {
+    let mut s = String::from("[settings]\n");
+    for (key, value) in settings(app) {
+        s += &format!("{} = {}\n", key, value);
+    }
+
+    s += "\n[metadata]\n";
+    for (field, value) in app.get_metadata().filled() {
+        s += &format!("{} = {}\n", field.key(), value);
+    }
+
+    s += "\n[points]\n# input, output, u(in), u(out), excluded\n";
+    s += &point_lines(app.get_points());
+    if let (Some(live), Some(other)) = (app.get_stage(), app.get_other_stage_points()) {
+        let shelved = match live {
+            Stage::AsFound => Stage::AsLeft,
+            Stage::AsLeft => Stage::AsFound,
+        };
+        s += &format!("\n[{}]\n", shelved.name());
+        s += &point_lines(other);
+    }
+
+    s += "\n[verification]\n# measured input, nominal output\n";
+    for p in app.get_verification_points() {
+        let (input, output) = values(p);
+        s += &format!("{}, {}\n", input, output);
+    }
+
+    s += "\n[budget]\n# of, value, distribution, ν, name\n";
+    for c in app.get_contributors() {
+        let distribution = match c.distribution {
+            Distribution::Normal(k) => format!("k={}", k),
+            Distribution::Rectangular => "rect".to_owned(),
+            Distribution::Triangular => "tri".to_owned(),
+        };
+        s += &format!(
+            "{}, {}, {}, {}, {}\n",
+            c.side.name(),
+            c.value,
+            distribution,
+            number(c.dof),
+            c.name
+        );
+    }
+    s
+}
+
+pub fn save(app: &App, path: &Path) -> io::Result<()> {
+    fs::write(path, to_string(app))
+}
+
+// A session of just this channel
+pub fn save_channel(app: &Channel, path: &Path) -> io::Result<()> {
+    fs::write(path, format!("# Slope-a-Dope session\n{}", channel(app)))
+}
+
+/*
+ * Rebuild a session. The settings go through the command line parser with `overrides` after them,
+ * so flags given when loading a session win over the saved ones. They are for the first channel,
+ * the one the session opens on, the others load as saved
+ */
+pub fn from_str(s: &str, overrides: &[String]) -> Result<App, Box<dyn Error>> {
+    let lines: Vec<(usize, &str)> = s.lines().enumerate().collect();
+    let mut channels = lines
+        .split(|(_, line)| line.trim().eq_ignore_ascii_case("[channel]"))
+        .enumerate()
+        .map(|(n, lines)| channel_from_lines(lines, if n == 0 { overrides } else { &[] }))
+        .collect::<Result<Vec<App>, _>>()?;
+    let mut app = channels.remove(0);
+    app.set_channels(channels.into_iter().map(App::into_channel).collect());
+    Ok(app)
+}
+
+fn channel_from_lines(
+    lines: &[(usize, &str)],
+    overrides: &[String],
+) -> Result<App, Box<dyn Error>> {
+    let mut section = String::new();
+    let mut args = Vec::new();
+    let mut metadata = Metadata::default();
+    let mut points = Vec::new();
+    let mut shelved: Option<(Stage, Vec<Point>)> = None;
+    let mut verification = Vec::new();
+    let mut contributors = Vec::new();
+    for &(n, line) in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_ascii_lowercase();
+            // the shelved stage is the other one from the points being edited
+            match section.as_str() {
+                "as-found" => shelved = Some((Stage::AsFound, Vec::new())),
+                "as-left" => shelved = Some((Stage::AsLeft, Vec::new())),
+                _ => {}
+            }
+            continue;
+        }
+        let bad = || format!("line {}: can't read \"{}\"", n + 1, line);
+        match section.as_str() {
+            "settings" => {
+                let (key, value) = line.split_once('=').ok_or_else(bad)?;
+                push_flag(&mut args, key.trim(), value.trim());
+            }
+            "metadata" => {
+                let (key, value) = line.split_once('=').ok_or_else(bad)?;
+                // fields from a newer version are skipped rather than refused
+                if let Some(field) = Field::from_key(key) {
+                    metadata.set(field, value);
+                }
+            }
+            "points" => points.push(parse_point(line).ok_or_else(bad)?),
+            "as-found" | "as-left" => {
+                if let Some((_, other)) = shelved.as_mut() {
+                    other.push(parse_point(line).ok_or_else(bad)?);
+                }
+            }
+            "verification" => verification.push(parse_point(line).ok_or_else(bad)?),
+            "budget" => contributors.push(parse_contributor(line).ok_or_else(bad)?),
+            _ => return Err(bad().into()),
+        }
+    }
+    // the saved settings alone first, so the readings are read in the units they were saved in
+    let mut app = cli::parse_args(args.iter().cloned())?.into_app();
+    let channel = app.active_mut();
+    channel.set_metadata(metadata);
+    // the points are loaded as saved, fewer than two topped up with empty ones to fill in rather
+    // than left as the example points a new session starts with
+    while points.len() < 2 {
+        points.push(Point::new());
+    }
+    let stages = shelved.map(|(shelved, other)| {
+        let live = match shelved {
+            Stage::AsFound => Stage::AsLeft,
+            Stage::AsLeft => Stage::AsFound,
+        };
+        (live, other)
+    });
+    channel.set_points(points, stages);
+    channel.set_verification_points(verification);
+    channel.set_contributors(contributors);
+    if overrides.is_empty() {
+        return Ok(app);
+    }
+    // then any units the overrides ask for are converted into before their settings are taken
+    let asked = cli::parse_args(args.into_iter().chain(overrides.iter().cloned()))?;
+    let (input, output) = (asked.input.unit, asked.output.unit);
+    let (from_in, from_out) = (channel.get_input().unit, channel.get_output().unit);
+    if from_in.dimension() != input.dimension() || from_out.dimension() != output.dimension() {
+        return Err(format!(
+            "readings saved in {} and {} can't be loaded as {} and {}",
+            from_in, from_out, input, output
+        )
+        .into());
+    }
+    channel.set_units(input, output);
+    let mut args = Vec::new();
+    for (key, value) in settings(channel) {
+        push_flag(&mut args, key, &value);
+    }
+    args.extend(overrides.iter().cloned());
+    let mut overridden = cli::parse_args(args.into_iter())?.into_app();
+    let loaded = overridden.active_mut();
+    loaded.set_metadata(channel.get_metadata().clone());
+    let stages = channel
+        .get_stage()
+        .zip(channel.get_other_stage_points().map(<[Point]>::to_vec));
+    loaded.set_points(channel.get_points().to_vec(), stages);
+    loaded.set_verification_points(channel.get_verification_points().to_vec());
+    loaded.set_contributors(channel.get_contributors().to_vec());
+    Ok(overridden)
+}
+
+// A "key = value" settings line as the command line flags it stands for
+fn push_flag(args: &mut Vec<String>, key: &str, value: &str) {
+    match (key, value) {
+        ("refine", "on") => args.push("--refine".to_owned()),
+        ("refine", _) => {}
+        (key, value) => {
+            args.push(format!("--{}", key));
+            args.push(value.to_owned());
+        }
+    }
+}
+
+pub fn load(path: &Path, overrides: &[String]) -> Result<App, Box<dyn Error>> {
+    let s = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    from_str(&s, overrides).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+// The session's settings as command line flags (without the dashes) and their values
+fn settings(app: &Channel) -> Vec<(&'static str, String)> {
+    let (input, output) = (app.get_input(), app.get_output());
+    let mut settings = vec![
+        ("input", input.name.clone()),
+        ("input-unit", input.unit.symbol().to_owned()),
+        ("output", output.name.clone()),
+        ("output-unit", output.unit.symbol().to_owned()),
+        ("model", app.get_model_kind().key()),
+    ];
+    let options = app.get_fit_options();
+    if options.refine {
+        settings.push(("refine", "on".to_owned()));
+    }
+    settings.push((
+        "regression",
+        match options.regression {
+            Regression::Ordinary => "ols".to_owned(),
+            Regression::Deming => format!("deming,{}", options.variance_ratio),
+            Regression::Orthogonal => "tls".to_owned(),
+        },
+    ));
+    settings.push(("robust", options.robust.name().to_ascii_lowercase()));
+    if let Some(ls) = app.get_loop_scaling() {
+        settings.push(("loop", format!("{},{}", ls.lrv, ls.urv)));
+    }
+    if let Some((lo, hi)) = app.get_plc_raw() {
+        settings.push(("plc-raw", format!("{},{}", lo, hi)));
+    }
+    if let Some(adc) = app.get_adc() {
+        let polarity = match adc.polarity {
+            Polarity::Unipolar => "unipolar",
+            Polarity::Bipolar => "bipolar",
+        };
+        settings.push((
+            "adc",
+            format!("{},{},{},{}", adc.bits, adc.vref, polarity, adc.offset),
+        ));
+    }
+    if let Some(dac) = app.get_dac() {
+        let polarity = match dac.polarity {
+            Polarity::Unipolar => "unipolar",
+            Polarity::Bipolar => "bipolar",
+        };
+        settings.push(("dac", format!("{},{}", dac.bits, polarity)));
+    }
+    if let Some(d) = app.get_divider() {
+        let position = match d.position {
+            DividerPosition::Low => "low",
+            DividerPosition::High => "high",
+        };
+        settings.push(("divider", format!("{},{},{}", d.supply, d.fixed, position)));
+    }
+    if let Some(celsius) = app.get_cold_junction() {
+        settings.push(("cold-junction", format!("{}", celsius)));
+    }
+    settings.push(("confidence", format!("{}", app.get_confidence())));
+    if let Some(t) = app.get_tolerance() {
+        settings.push(("tolerance", t.describe(output.unit)));
+    }
+    settings
+}
+
+fn number(v: Option<f64>) -> String {
+    v.map(|v| format!("{}", v)).unwrap_or_default()
+}
+
+// A point's input and output, blank when not set
+fn values(p: &Point) -> (String, String) {
+    if p.is_valid() {
+        let vals = p.get_val();
+        (format!("{}", vals["in"]), format!("{}", vals["out"]))
+    } else {
+        Default::default()
+    }
+}
+
+fn point_lines(points: &[Point]) -> String {
+    points
+        .iter()
+        .map(|p| {
+            let (input, output) = values(p);
+            let (u_in, u_out) = p.get_uncertainty();
+            let excluded = if p.is_excluded() { "excluded" } else { "" };
+            format!(
+                "{}, {}, {}, {}, {}\n",
+                input,
+                output,
+                number(u_in),
+                number(u_out),
+                excluded
+            )
+        })
+        .collect()
+}
+
+// "1, 2", "1, 2, 0.01, , excluded" and so on, missing columns are blank
+fn parse_point(line: &str) -> Option<Point> {
+    let cols: Vec<&str> = line.split(',').map(str::trim).collect();
+    let col = |i: usize| -> Option<Option<f64>> {
+        match cols.get(i).cloned().unwrap_or("") {
+            "" => Some(None),
+            v => v.parse().ok().filter(|v: &f64| v.is_finite()).map(Some),
+        }
+    };
+    // uncertainties weight the fit, so they have to be above zero
+    let uncertainty = |i: usize| -> Option<Option<f64>> {
+        match col(i)? {
+            Some(u) if u <= 0.0 => None,
+            u => Some(u),
+        }
+    };
+    let mut p = Point::new();
+    if let Some(x) = col(0)? {
+        p.set_input(x);
+    }
+    if let Some(y) = col(1)? {
+        p.set_output(y);
+    }
+    p.set_input_uncertainty(uncertainty(2)?);
+    p.set_output_uncertainty(uncertainty(3)?);
+    match cols.get(4).cloned().unwrap_or("") {
+        "" => {}
+        "excluded" => p.set_excluded(true),
+        _ => return None,
+    }
+    Some(p)
+}
+
+// "out, 0.1, k=2, , Reference standard", the name may have commas of its own
+fn parse_contributor(line: &str) -> Option<Contributor> {
+    let cols: Vec<&str> = line.splitn(5, ',').map(str::trim).collect();
+    let [side, value, distribution, dof, name] = cols[..] else {
+        return None;
+    };
+    let mut c = Contributor::new(name, Side::parse(side)?, Distribution::parse(distribution)?);
+    c.value = value.parse().ok()?;
+    c.dof = match dof {
+        "" => None,
+        dof => Some(dof.parse().ok()?),
+    };
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_str, to_string};
+    use crate::app::{App, Stage};
+    use crate::calculator::verify::Tolerance;
+    use crate::calculator::MeasurementType;
+    use crate::metadata::Field;
+    use crate::units::{Quantity, Unit};
+
+    #[test]
+    fn round_trip() {
+        let mut app = App::new(
+            Quantity::from_unit(Unit::Milliamp),
+            Quantity::new("Tank level", Unit::Percent),
+        );
+        let channel = app.active_mut();
+        let mut metadata = channel.get_metadata().clone();
+        metadata.set(Field::Serial, "LT-101, spare");
+        metadata.set(Field::Notes, "span = 0.1% low");
+        channel.set_metadata(metadata.clone());
+        channel.set_tolerance(Tolerance::parse("0.5% FS", Unit::Percent));
+        channel.set_confidence(0.99);
+        channel.toggle_stage();
+        channel.add_point();
+        channel.add_verification();
+        channel.toggle_excluded();
+
+        let saved = to_string(&app);
+        let back = from_str(&saved, &[]).unwrap();
+        assert_eq!(saved, to_string(&back));
+        assert_eq!(&metadata, back.active().get_metadata());
+        assert_eq!("Tank level", back.active().get_output().name);
+        assert!(back.active().get_stage() == Some(Stage::AsLeft));
+        assert_eq!(3, back.active().get_points().len());
+        assert!(back.active().get_points()[1].is_excluded());
+        assert_eq!(1, back.active().get_verification_points().len());
+        assert_eq!(
+            app.active().get_contributors(),
+            back.active().get_contributors()
+        );
+        let at = MeasurementType::Input(12.0);
+        assert_eq!(app.active().convert(&at), back.active().convert(&at));
+
+        // flags given on the command line win over the saved ones
+        let back = from_str(&saved, &["--confidence".to_owned(), "90".to_owned()]).unwrap();
+        assert_eq!(0.9, back.active().get_confidence());
+        assert!(from_str("[points]\n1, two\n", &[]).is_err());
+    }
+
+    #[test]
+    fn load_in_other_units() {
+        let saved =
+            "[settings]\ninput-unit = mA\noutput-unit = kPa\nloop = 0,100\ntolerance = 0.5\n\
+                     [points]\n4, 0, , 0.2\n20, 100\n[verification]\n12, 50\n";
+        let psi = Unit::Kilopascal.convert(1.0, Unit::Psi).unwrap();
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let flags = |unit: &str| ["--output-unit".to_owned(), unit.to_owned()];
+        let back = from_str(saved, &flags("psi")).unwrap();
+        assert_eq!(Unit::Psi, back.active().get_output().unit);
+        // the kPa readings are converted, not relabelled
+        let points = back.active().get_points();
+        assert!(close(100.0 * psi, points[1].get_val()["out"]));
+        assert!(close(0.2 * psi, points[0].get_uncertainty().1.unwrap()));
+        assert!(close(
+            50.0 * psi,
+            back.active().get_verification_points()[0].get_val()["out"]
+        ));
+        assert!(close(
+            100.0 * psi,
+            back.active().get_loop_scaling().unwrap().urv
+        ));
+        assert!(close(
+            0.5 * psi,
+            back.active().get_tolerance().unwrap().absolute
+        ));
+        // and saved again in psi they read back the same
+        let again = from_str(&to_string(&back), &[]).unwrap();
+        assert_eq!(to_string(&back), to_string(&again));
+        // readings can't become another quantity
+        assert!(from_str(saved, &flags("V")).is_err());
+    }
+
+    #[test]
+    fn short_sessions() {
+        // one point and a shelved as-found stage load as saved, with an empty point to fill in
+        let back = from_str("[points]\n1, 2\n[as-found]\n0, 0\n4, 5\n", &[]).unwrap();
+        assert!(back.active().get_stage() == Some(Stage::AsLeft));
+        assert_eq!(2, back.active().get_other_stage_points().unwrap().len());
+        let points = back.active().get_points();
+        assert_eq!(2, points.len());
+        let first = points[0].get_val();
+        assert_eq!((1.0, 2.0), (first["in"], first["out"]));
+        assert!(!points[1].is_valid());
+        assert_eq!(
+            to_string(&back),
+            to_string(&from_str(&to_string(&back), &[]).unwrap())
+        );
+
+        // no points at all is two empty ones, not the example line
+        let back = from_str("[settings]\nmodel = linear\n", &[]).unwrap();
+        assert!(back.active().get_points().iter().all(|p| !p.is_valid()));
+        assert!(back.active().get_model().is_err());
+    }
+
+    #[test]
+    fn bad_numbers() {
+        let points = from_str("[points]\n0, 0, 0.1, 0.2\n10, 20, , 0.2\n", &[]).unwrap();
+        assert_eq!(
+            (Some(0.1), Some(0.2)),
+            points.active().get_points()[0].get_uncertainty()
+        );
+        // readings and uncertainties that would leave the fit without a number are refused
+        for line in [
+            "nan, 1",
+            "1, inf",
+            "1, 2, 0",
+            "1, 2, , -0.1",
+            "1, 2, inf",
+            "1, 2, , NaN",
+        ] {
+            assert!(
+                from_str(&format!("[points]\n{}\n", line), &[]).is_err(),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn channels() {
+        let mut app = App::new(
+            Quantity::from_unit(Unit::Milliamp),
+            Quantity::new("Tank level", Unit::Percent),
+        );
+        app.add_channel();
+        app.add_channel();
+        assert_eq!(2, app.get_channel());
+        let channel = app.active_mut();
+        let mut metadata = channel.get_metadata().clone();
+        metadata.set(Field::Channel, "Tank 3");
+        channel.set_metadata(metadata);
+        channel.add_point();
+        assert_eq!("Tank level", app.active().get_output().name);
+        assert_eq!(
+            vec!["Channel 1", "Channel 2", "Tank 3"],
+            app.get_channel_names()
+        );
+
+        let saved = to_string(&app);
+        assert_eq!(2, saved.matches("\n[channel]\n").count());
+        let mut back = from_str(&saved, &[]).unwrap();
+        assert_eq!(saved, to_string(&back));
+        assert_eq!(0, back.get_channel());
+        back.step_channel(false);
+        assert_eq!(2, back.get_channel());
+        assert_eq!(3, back.active().get_points().len());
+        assert_eq!(
+            vec!["channel_1", "channel_2", "tank_3"],
+            back.get_channel_files()
+        );
+
+        // flags given when loading are for the channel the session opens on
+        let flags = ["--confidence".to_owned(), "90".to_owned()];
+        let back = from_str(&saved, &flags).unwrap();
+        assert_eq!(0.9, back.active().get_confidence());
+        assert!(back.get_channels()[1..]
+            .iter()
+            .all(|c| c.get_confidence() == 0.95));
+        let mut back = from_str(&saved, &[]).unwrap();
+
+        // the next channel along takes the removed one's place
+        back.select_channel(1);
+        back.remove_channel();
+        assert_eq!(1, back.get_channel());
+        assert_eq!(vec!["Channel 1", "Tank 3"], back.get_channel_names());
+        assert_eq!(3, back.active().get_points().len());
+        back.remove_channel();
+        back.remove_channel();
+        assert_eq!(vec!["Channel 1"], back.get_channel_names());
+        assert_eq!(2, back.active().get_points().len());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::plot::{Anchor, Color, Shape, FONT_SIZE};
+
+// A laid out chart as a standalone SVG document
+pub fn to_svg(shapes: &[Shape], width: u32, height: u32) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"{f}\" fill=\"{ink}\">\n\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\n",
+        w = width,
+        h = height,
+        f = FONT_SIZE,
+        ink = hex(Color::INK)
+    );
+    for shape in shapes {
+        svg += &match shape {
+            Shape::Path {
+                points,
+                color,
+                width,
+                dashed,
+            } => {
+                let points: Vec<String> = points
+                    .iter()
+                    .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+                    .collect();
+                let dash = if *dashed {
+                    " stroke-dasharray=\"6 4\""
+                } else {
+                    ""
+                };
+                format!(
+                    "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"{}/>\n",
+                    points.join(" "),
+                    hex(*color),
+                    width,
+                    dash
+                )
+            }
+            Shape::Dot { at, radius, color } => format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{}\" fill=\"{}\"/>\n",
+                at.0,
+                at.1,
+                radius,
+                hex(*color)
+            ),
+            Shape::Square { at, half, color } => format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                at.0 - half,
+                at.1 - half,
+                2.0 * half,
+                2.0 * half,
+                hex(*color)
+            ),
+            Shape::Text {
+                at,
+                text,
+                anchor,
+                vertical,
+            } => {
+                let anchor = match anchor {
+                    Anchor::Middle => "middle",
+                    Anchor::End => "end",
+                };
+                let place = if *vertical {
+                    format!(
+                        "transform=\"translate({:.1} {:.1}) rotate(-90)\"",
+                        at.0, at.1
+                    )
+                } else {
+                    format!("x=\"{:.1}\" y=\"{:.1}\"", at.0, at.1)
+                };
+                format!(
+                    "<text {} text-anchor=\"{}\">{}</text>\n",
+                    place,
+                    anchor,
+                    escape(text)
+                )
+            }
+        };
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+fn hex(Color(r, g, b): Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// Text made safe to put in SVG or HTML
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, to_svg};
+    use crate::plot::{Anchor, Color, Shape};
+
+    #[test]
+    fn draws_shapes() {
+        let shapes = [
+            Shape::Path {
+                points: vec![(70.0, 350.0), (580.0, 20.0)],
+                color: Color::CURVE,
+                width: 2.0,
+                dashed: true,
+            },
+            Shape::Dot {
+                at: (325.0, 185.0),
+                radius: 4.0,
+                color: Color::POINT,
+            },
+            Shape::Text {
+                at: (18.0, 185.0),
+                text: "Level <%>".to_owned(),
+                anchor: Anchor::Middle,
+                vertical: true,
+            },
+        ];
+        let svg = to_svg(&shapes, 600, 400);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(
+            "points=\"70.0,350.0 580.0,20.0\" fill=\"none\" stroke=\"#008000\" stroke-width=\"2\" \
+             stroke-dasharray"
+        ));
+        assert!(svg.contains("<circle cx=\"325.0\" cy=\"185.0\" r=\"4\" fill=\"#8b008b\"/>"));
+        assert!(svg.contains(
+            "translate(18.0 185.0) rotate(-90)\" text-anchor=\"middle\">Level &lt;%&gt;<"
+        ));
+        assert_eq!("a &amp; &quot;b&quot;", escape("a & \"b\""));
+    }
+}
@@ -1,19 +1,32 @@
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     prelude::Alignment,
     style::{Color, Modifier, Style, Stylize},
     symbols::Marker,
-    text::{Span, Text},
-    widgets::{Axis, Block, BorderType, Borders, Chart, Dataset, GraphType, Paragraph},
+    text::{Line, Span, Text},
+    widgets::{
+        block::{Position, Title},
+        Axis, Block, BorderType, Borders, Cell, Chart, Dataset, GraphType, Paragraph, Row, Table,
+        Tabs,
+    },
     Frame,
 };
 
 use crate::{
-    app::{App, CurrentlyEditing, Mode, ScreenID},
-    calculator::MeasurementType,
+    app::{self, App, Budget, BudgetField, CurrentlyEditing, Mode, ScreenID},
+    calculator::{
+        budget::{self, Distribution, Side, Term},
+        drift::{self, Calibration},
+        robust::Robust,
+        verify::Check,
+        FitOptions, LoopStatus, MeasurementType, ModelKind, Regression,
+    },
+    metadata::{self, Field},
+    units,
 };
 
 pub fn ui(f: &mut Frame, app: &App) {
+    let channel = app.active();
     // Draw all the things
 
     // ----- Break the frame into work spaces ------ ////
@@ -40,11 +53,7 @@ pub fn ui(f: &mut Frame, app: &App) {
     // divide left col
     let left_col = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage(20),
-            Constraint::Percentage(40),
-            Constraint::Percentage(40),
-        ])
+        .constraints([Constraint::Percentage(20), Constraint::Percentage(80)])
         .split(cols[0]);
 
     // [[[[[[[[[ Rects that we will render stuf in ]]]]]]]]]
@@ -52,335 +61,869 @@ pub fn ui(f: &mut Frame, app: &App) {
     let chart_area = right_col[1];
     let sim_area = right_col[0];
     let help_area = left_col[0];
-    let p1_area = left_col[1];
-    let p2_area = left_col[2];
+    let points_area = left_col[1];
     let footer_area = rows[2];
 
+    // the budget, the stage comparison, the details, the history and the comparison with another
+    // session take the whole workspace
+    if let ScreenID::Budget
+    | ScreenID::Stages
+    | ScreenID::Metadata
+    | ScreenID::History
+    | ScreenID::Compare = app.get_current_screen()
+    {
+        let budget_rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Max(6), Constraint::Min(10)])
+            .split(workspace_rect);
+        f.render_widget(make_help(app).block(make_block(" help ")), budget_rows[0]);
+        match app.get_current_screen() {
+            ScreenID::Budget => render_budget(f, app, budget_rows[1]),
+            ScreenID::Metadata => render_metadata(f, app, budget_rows[1]),
+            ScreenID::History => render_history(f, app, budget_rows[1]),
+            ScreenID::Compare => render_compare(f, app, budget_rows[1]),
+            _ => render_stages(f, app, budget_rows[1]),
+        }
+        render_title(f, app, title_area);
+        f.render_widget(make_footer(app), footer_area);
+        return;
+    }
+
     // [][][] Make Blocks [][][]
+    // titles carry the session quantities so they have to be built before the blocks borrow them
+    let (input, output) = (channel.get_input().title(), channel.get_output().title());
+    let test_in_title = format!(" {} ", input);
+    let test_out_title = format!(" {} ", output);
+    // in loop scaling mode the points are the range values and the tester reports NE43 status
+    let mut points_title = match (channel.get_loop_scaling(), channel.get_divider()) {
+        (Some(_), _) => " Range (4-20 mA) ".to_owned(),
+        (None, Some(d)) => format!(
+            " Points - {} | divider {:.3} V, {:.0} ohm {} ",
+            channel.get_model_kind().name(),
+            d.supply,
+            d.fixed,
+            d.position.name()
+        ),
+        (None, None) => match channel.get_model_kind() {
+            ModelKind::Curve(_) if channel.get_fit_options().refine => {
+                format!(" Points - {} (refined) ", channel.get_model_kind().name())
+            }
+            // errors-in-variables lines say how they were fitted
+            ModelKind::Linear => match channel.get_fit_options() {
+                FitOptions {
+                    regression: Regression::Ordinary,
+                    ..
+                } => " Points - Linear ".to_owned(),
+                FitOptions {
+                    regression: Regression::Deming,
+                    variance_ratio,
+                    ..
+                } => format!(" Points - Linear (Deming, ratio {}) ", variance_ratio),
+                FitOptions { regression, .. } => {
+                    format!(" Points - Linear ({}) ", regression.name())
+                }
+            },
+            kind => format!(" Points - {} ", kind.name()),
+        },
+    };
+    let robust = channel.get_fit_options().robust;
+    if robust != Robust::Off && channel.get_loop_scaling().is_none() {
+        points_title += &format!("| {} fit ", robust.name());
+    }
+    if let Some(stage) = channel.get_stage() {
+        points_title += &format!("| {} ", stage.name());
+    }
+    // verification takes over the points panel and the chart
+    let verifying = matches!(app.get_current_screen(), ScreenID::Verify);
+    let checks = channel.get_verification();
+    if verifying {
+        let passed = checks.iter().flatten().filter(|c| c.passed()).count();
+        points_title = match channel.get_tolerance() {
+            Some(t) => format!(
+                " Verification - {} | {} / {} pass ",
+                t.describe(channel.get_output().unit),
+                passed,
+                checks.iter().flatten().count()
+            ),
+            None => " Verification - no tolerance set ".to_owned(),
+        };
+    }
+    // ADC resolution and the band the tester's code stands for go along the bottom of the tester
+    let adc_title = channel.get_quantization().map(|q| {
+        let out_unit = channel.get_output().unit;
+        let mut t = format!(" 1 LSB = {} ", units::format_value(q.resolution, out_unit));
+        if let (Some(code), Some((lo, hi))) = (q.code, q.band) {
+            t += &format!(
+                "| code {} | {:.4} .. {} ",
+                code,
+                lo,
+                units::format_value(hi, out_unit)
+            );
+        }
+        t
+    });
+    // in an output calibration, the code to command for the output and what it really gives
+    let dac_title = channel.get_dac_command().map(|c| {
+        let out_unit = channel.get_output().unit;
+        format!(
+            " command code {} ({:.2}) | gives {} | off by {:+.4} ",
+            c.code,
+            c.ideal,
+            units::format_value(c.output, out_unit),
+            c.error
+        )
+    });
+    let sim_title = match channel.get_loop_status() {
+        Some(status) => format!(" Test function - {} ", loop_status_text(status)),
+        None => " Test function ".to_owned(),
+    };
 
-    let title_block = make_block("");
     let help_block = make_block(" help ");
-    let mut p1_block = make_block(" Point 1 ");
-    let mut p2_block = make_block(" Point 2 ");
-    let chart_block = make_block(" Results ");
-    let mut sim_block = make_block(" Test function ");
-    let footer_block = make_block(" Current Mode ");
-
-    // get inner blocks for P1, P2, and sim
-    // [P1]
-    let p1_inner = p1_block.inner(p1_area);
-    let p1_contents = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-        .split(p1_inner);
-    let mut p1_v_block = make_block(" p1 Voltage ");
-    let mut p1_p_block = make_block(" p1 Physical ");
-    // [P2]
-    let p2_inner = p2_block.inner(p2_area);
-    let p2_contents = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-        .split(p2_inner);
-    let mut p2_v_block = make_block(" p2 Voltage ");
-    let mut p2_p_block = make_block(" p2 Physical ");
+    let mut points_block = make_block(&points_title);
+    // the tolerance is typed along the bottom of the verification
+    let tolerance_title = match (app.get_mode(), app.get_currently_editing()) {
+        (Mode::EditingValue, Some(CurrentlyEditing::Tolerance)) => {
+            Some(format!(" Tolerance: {}_ ", app.get_temp_point()))
+        }
+        _ => None,
+    };
+    if let Some(t) = tolerance_title.as_deref() {
+        points_block = points_block.title(
+            Title::from(Span::styled(t, Style::default().fg(Color::Green)))
+                .position(Position::Bottom),
+        );
+    }
+    // fitted RTDs report how far they are from the standard curve
+    let results_title = match channel.get_standard_deviation() {
+        Some((probe, dev)) => format!(
+            " Results - max {} from IEC 60751 {} ",
+            units::format_value(dev, channel.get_output().unit),
+            probe
+        ),
+        None if verifying => " Results - error against tolerance ".to_owned(),
+        None => " Results ".to_owned(),
+    };
+    let mut chart_block = make_block(&results_title);
+    // the bands' level along the bottom, in their colors
+    if !verifying && !channel.get_bands().confidence[0].is_empty() {
+        chart_block = chart_block.title(
+            Title::from(Line::from(vec![
+                Span::raw(format!(" {:.0}% ", channel.get_confidence() * 100.0)),
+                Span::styled("confidence", Style::default().fg(Color::Cyan)),
+                Span::raw(" / "),
+                Span::styled("prediction", Style::default().fg(Color::Blue)),
+                Span::raw(" bands "),
+            ]))
+            .position(Position::Bottom),
+        );
+    }
+    let mut sim_block = make_block(&sim_title);
+    if let Some(t) = adc_title.as_deref().or(dac_title.as_deref()) {
+        sim_block = sim_block.title(Title::from(t).position(Position::Bottom));
+    }
 
-    // Get inner blocks for test section
+    // Get inner blocks for test section, thermocouples get a third cell for the cold junction
+    let cold_junction = channel.get_cold_junction();
+    let cj_unit = channel.cold_junction_unit();
+    let cj_title = format!(" CJC [{}] ", cj_unit);
     let sim_inner = sim_block.inner(sim_area);
     let test_values = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .constraints(match cold_junction {
+            Some(_) => vec![
+                Constraint::Percentage(35),
+                Constraint::Percentage(35),
+                Constraint::Percentage(30),
+            ],
+            None => vec![Constraint::Percentage(50), Constraint::Percentage(50)],
+        })
         .split(sim_inner);
 
     // Blocks to render the sim values in
     // will be rendered to the rects in test_values
-    let mut test_v_block = make_block(" Voltage ");
-    let mut test_p_block = make_block(" Pysical ");
+    let mut test_in_block = make_block(&test_in_title);
+    let mut test_out_block = make_block(&test_out_title);
+    let mut cj_block = make_block(&cj_title);
+    if let Some(status) = channel.get_loop_status() {
+        let style = Style::default().fg(loop_status_color(status));
+        test_in_block = test_in_block.style(style);
+        test_out_block = test_out_block.style(style);
+    }
 
     // ------ DYNAMIC RENDERED --------
-    // Color blocks for slector
-    match app.get_mode() {
-        Mode::Select => match app.get_current_screen() {
-            ScreenID::P1 => {
-                p1_block = p1_block.style(Style::default().fg(Color::LightMagenta));
-            }
-            ScreenID::P2 => {
-                p2_block = p2_block.style(Style::default().fg(Color::LightMagenta));
-            }
-            ScreenID::Tester => {
-                sim_block = sim_block.style(Style::default().fg(Color::LightMagenta));
-            }
-        },
-
+    // Color blocks for slector, the point cells are colored when the table is built
+    match (app.get_mode(), app.get_current_screen()) {
+        (Mode::Select, ScreenID::Points | ScreenID::Verify) => {
+            points_block = points_block.style(Style::default().fg(Color::LightMagenta));
+        }
+        (Mode::Select, ScreenID::Tester) => {
+            sim_block = sim_block.style(Style::default().fg(Color::LightMagenta));
+        }
+        (Mode::Edit | Mode::EditingValue, ScreenID::Points | ScreenID::Verify) => {
+            points_block = points_block.style(Style::default().fg(Color::Green));
+        }
         // Color the highlighted cell will paint cells in edit mode and persist the outer coloring through editing value mode
-        Mode::Edit => match app.get_current_screen() {
-            ScreenID::P1 => {
-                p1_block = p1_block.style(Style::default().fg(Color::Green));
-                // Color volt/phys selector
-                if let Some(point_value) = app.get_currently_editing() {
-                    match point_value {
-                        crate::app::CurrentlyEditing::Voltage => {
-                            //currently editing voltage need to color it yellow
-                            p1_v_block = p1_v_block.style(Style::default().fg(Color::LightMagenta))
-                        }
-                        crate::app::CurrentlyEditing::Physical => {
-                            //currently editing voltage need to color it yellow
-                            p1_p_block = p1_p_block.style(Style::default().fg(Color::LightMagenta))
-                        }
+        (Mode::Edit, ScreenID::Tester) => {
+            sim_block = sim_block.style(Style::default().fg(Color::Green));
+            if let Some(point_value) = app.get_currently_editing() {
+                match point_value {
+                    CurrentlyEditing::Input => {
+                        //currently editing input need to color it yellow
+                        test_in_block =
+                            test_in_block.style(Style::default().fg(Color::LightMagenta))
                     }
-                }
-            }
-            ScreenID::P2 => {
-                p2_block = p2_block.style(Style::default().fg(Color::Green));
-                if let Some(point_value) = app.get_currently_editing() {
-                    match point_value {
-                        crate::app::CurrentlyEditing::Voltage => {
-                            //currently editing voltage need to color it yellow
-                            p2_v_block = p2_v_block.style(Style::default().fg(Color::LightMagenta))
-                        }
-                        crate::app::CurrentlyEditing::Physical => {
-                            //currently editing voltage need to color it yellow
-                            p2_p_block = p2_p_block.style(Style::default().fg(Color::LightMagenta))
-                        }
+                    CurrentlyEditing::Output => {
+                        //currently editing output need to color it yellow
+                        test_out_block =
+                            test_out_block.style(Style::default().fg(Color::LightMagenta))
                     }
-                }
-            }
-            ScreenID::Tester => {
-                sim_block = sim_block.style(Style::default().fg(Color::Green));
-                if let Some(point_value) = app.get_currently_editing() {
-                    match point_value {
-                        crate::app::CurrentlyEditing::Voltage => {
-                            //currently editing voltage need to color it yellow
-                            test_v_block =
-                                test_v_block.style(Style::default().fg(Color::LightMagenta))
-                        }
-                        crate::app::CurrentlyEditing::Physical => {
-                            //currently editing voltage need to color it yellow
-                            test_p_block =
-                                test_p_block.style(Style::default().fg(Color::LightMagenta))
-                        }
+                    CurrentlyEditing::ColdJunction => {
+                        cj_block = cj_block.style(Style::default().fg(Color::LightMagenta))
                     }
-                } else {
-                    test_p_block = test_p_block.style(Style::default().fg(Color::Red));
+                    // only the points have uncertainties
+                    CurrentlyEditing::InputUncertainty
+                    | CurrentlyEditing::OutputUncertainty
+                    | CurrentlyEditing::Budget(_)
+                    | CurrentlyEditing::Tolerance
+                    | CurrentlyEditing::Metadata
+                    | CurrentlyEditing::ComparePath
+                    | CurrentlyEditing::PlcScaling => {}
                 }
+            } else {
+                test_out_block = test_out_block.style(Style::default().fg(Color::Red));
             }
-        },
-        // Handle coloring and editng values in EditValue mode
-        // We need to repaint the currently_editing value cell
-        Mode::EditingValue => {
-            if let Some(x) = app.get_currently_editing() {
-                match app.get_current_screen() {
-                    ScreenID::P1 => match x {
-                        CurrentlyEditing::Physical => {
-                            p1_block = p1_block.style(Style::default().fg(Color::Green));
-                            p1_p_block = p1_p_block.style(Style::default().fg(Color::Green));
-                        }
-                        CurrentlyEditing::Voltage => {
-                            p1_block = p1_block.style(Style::default().fg(Color::Green));
-                            p1_v_block = p1_v_block.style(Style::default().fg(Color::Green));
-                        }
-                    },
-                    ScreenID::P2 => match x {
-                        CurrentlyEditing::Physical => {
-                            p2_block = p2_block.style(Style::default().fg(Color::Green));
-                            p2_p_block = p2_p_block.style(Style::default().fg(Color::Green))
-                        }
-                        CurrentlyEditing::Voltage => {
-                            p2_block = p2_block.style(Style::default().fg(Color::Green));
-                            p2_v_block = p2_v_block.style(Style::default().fg(Color::Green))
-                        }
-                    },
-                    ScreenID::Tester => match x {
-                        CurrentlyEditing::Physical => {
-                            sim_block = sim_block.style(Style::default().fg(Color::Green));
-                            test_p_block = test_p_block.style(Style::default().fg(Color::Green))
-                        }
-                        CurrentlyEditing::Voltage => {
-                            sim_block = sim_block.style(Style::default().fg(Color::Green));
-                            test_v_block = test_v_block.style(Style::default().fg(Color::Green))
-                        }
-                    },
+        }
+        (Mode::EditingValue, ScreenID::Tester) => {
+            sim_block = sim_block.style(Style::default().fg(Color::Green));
+            match app.get_currently_editing() {
+                Some(CurrentlyEditing::Output) => {
+                    test_out_block = test_out_block.style(Style::default().fg(Color::Green))
+                }
+                Some(CurrentlyEditing::Input) => {
+                    test_in_block = test_in_block.style(Style::default().fg(Color::Green))
                 }
+                Some(CurrentlyEditing::ColdJunction) => {
+                    cj_block = cj_block.style(Style::default().fg(Color::Green))
+                }
+                _ => {}
             }
         }
-        // Color for editingValue
         _ => {}
     }
     // Render Outer Blocks here to not overwrite inner colors and stuff
     f.render_widget(sim_block, sim_area);
-    f.render_widget(p1_block, p1_area);
-    f.render_widget(p2_block, p2_area);
-
-    let mut test_v_text = make_paragraph("", test_v_block.clone());
-    let mut test_p_text = make_paragraph("", test_p_block.clone());
-
-    // Make paragraphs for [P1] [P2] [Tester]
-    if let Some(points) = app.get_points() {
-        // Determine if we should use the temp_point or the stored p1 and p2 values
-        // First set the point values
-        let mut p1_v_str = format!("{:.4}", points.0.get("v").cloned().unwrap_or(0.0));
-        let mut p1_p_str = format!("{:.4}", points.0.get("p").cloned().unwrap_or(0.0));
-        let mut p2_v_str = format!("{:.4}", points.1.get("v").cloned().unwrap_or(990.0));
-        let mut p2_p_str = format!("{:.4}", points.1.get("p").cloned().unwrap_or(990.0));
-
-        // Then overwrite as needed for editingvalue mode
-        if let Mode::EditingValue = app.get_mode() {
-            if let Some(x) = app.get_currently_editing() {
-                match app.get_current_screen() {
-                    ScreenID::P1 => match x {
-                        CurrentlyEditing::Physical => {
-                            p1_p_str = app.get_temp_point().into();
-                        }
-                        CurrentlyEditing::Voltage => {
-                            p1_v_str = app.get_temp_point().into();
-                        }
-                    },
-                    ScreenID::P2 => match x {
-                        CurrentlyEditing::Physical => {
-                            p2_p_str = app.get_temp_point().into();
-                        }
-                        CurrentlyEditing::Voltage => {
-                            p2_v_str = app.get_temp_point().into();
-                        }
-                    },
-                    ScreenID::Tester => match x {
-                        CurrentlyEditing::Physical => {
-                            test_p_text = Paragraph::new(app.get_temp_point())
-                                .alignment(Alignment::Center)
-                                .block(test_p_block.clone());
-                        }
-                        CurrentlyEditing::Voltage => {
-                            test_v_text = Paragraph::new(app.get_temp_point())
-                                .alignment(Alignment::Center)
-                                .block(test_v_block.clone());
-                        }
-                    },
-                }
+
+    // [POINTS]
+    let points_inner = points_block.inner(points_area);
+    f.render_widget(points_block, points_area);
+    if verifying {
+        f.render_widget(make_verification_table(app, &checks), points_inner);
+    } else {
+        f.render_widget(make_point_table(app, &input, &output), points_inner);
+    }
+
+    // [TESTER]
+    let mut test_in_text = make_paragraph("", test_in_block.clone());
+    let mut test_out_text = make_paragraph("", test_out_block.clone());
+    let cj_str = cold_junction
+        .and_then(|t| units::Unit::Celsius.convert(t, cj_unit).ok())
+        .map(|t| format!("{:.2}", t))
+        .unwrap_or_default();
+    let mut cj_text = make_paragraph(&cj_str, cj_block.clone());
+
+    // Show the typed value while editing the tester
+    if let (Mode::EditingValue, ScreenID::Tester) = (app.get_mode(), app.get_current_screen()) {
+        match app.get_currently_editing() {
+            Some(CurrentlyEditing::Output) => {
+                test_out_text = make_paragraph(app.get_temp_point(), test_out_block.clone());
+            }
+            Some(CurrentlyEditing::Input) => {
+                test_in_text = make_paragraph(app.get_temp_point(), test_in_block.clone());
             }
+            Some(CurrentlyEditing::ColdJunction) => {
+                cj_text = make_paragraph(app.get_temp_point(), cj_block);
+            }
+            _ => {}
         }
+    }
 
-        // Make paragraphs for tester if were holidng a testing value
-        if let Some(testing_value) = app.testing_value.as_ref() {
-            if let Some(line) = app.line.as_ref() {
-                if let Some((_, _)) = line.get_val() {
-                    if let Ok(calculated_value) = line.get_corresponding_value(&testing_value) {
-                        match testing_value {
-                            MeasurementType::Physical(phys) => {
-                                // were using a physicaly input so we need to calc the other one
-                                test_p_text = Paragraph::new(format!("{:.4}", phys.clone()))
-                                    .alignment(Alignment::Center)
-                                    .block(test_p_block);
-                                test_v_text = Paragraph::new(format!("{:.4}", calculated_value))
-                                    .alignment(Alignment::Center)
-                                    .block(test_v_block);
-                            }
-                            MeasurementType::Voltage(volt) => {
-                                test_v_text = Paragraph::new(format!("{:.4}", volt.clone()))
-                                    .alignment(Alignment::Center)
-                                    .block(test_v_block);
-                                test_p_text = Paragraph::new(format!("{:.4}", calculated_value))
-                                    .alignment(Alignment::Center)
-                                    .block(test_p_block);
-                            }
-                        }
-                    }
+    // Make paragraphs for tester if were holidng a testing value
+    if let Some(testing_value) = channel.testing_value.as_ref() {
+        if let Ok(calculated_value) = channel.convert(testing_value) {
+            let (in_val, out_val) = match testing_value {
+                // were given an output value so we need to calc the input
+                MeasurementType::Output(out) => (calculated_value, *out),
+                MeasurementType::Input(inp) => (*inp, calculated_value),
+            };
+            let mut in_str = format!("{:.4}", in_val);
+            let mut out_str = format!("{:.4}", out_val);
+            // the converted side says how far it can be trusted
+            if let Some(u) = channel.get_test_uncertainty() {
+                match testing_value {
+                    MeasurementType::Output(_) => in_str += &format!(" ± {:.4} (k=2)", u),
+                    MeasurementType::Input(_) => out_str += &format!(" ± {:.4} (k=2)", u),
                 }
             }
+            test_in_text = Paragraph::new(in_str)
+                .alignment(Alignment::Center)
+                .block(test_in_block);
+            test_out_text = Paragraph::new(out_str)
+                .alignment(Alignment::Center)
+                .block(test_out_block);
         }
-        // make the paragraphs
-        // [P1]
-        let p1_v_text = make_paragraph(&p1_v_str, p1_v_block);
-        let p1_p_text = make_paragraph(&p1_p_str, p1_p_block);
-
-        // [P2]
-        let p2_v_text = make_paragraph(&p2_v_str, p2_v_block);
-        let p2_p_text = make_paragraph(&p2_p_str, p2_p_block);
-
-        // [TESTER]
-        f.render_widget(test_v_text, test_values[0]);
-        f.render_widget(test_p_text, test_values[1]);
-
-        // render
-        f.render_widget(p1_v_text, p1_contents[0]);
-        f.render_widget(p1_p_text, p1_contents[1]);
-        f.render_widget(p2_v_text, p2_contents[0]);
-        f.render_widget(p2_p_text, p2_contents[1]);
-    } else {
-        f.render_widget(p1_p_block, p1_contents[0]);
-        f.render_widget(p1_v_block, p1_contents[1]);
-        f.render_widget(p2_p_block, p2_contents[0]);
-        f.render_widget(p2_v_block, p2_contents[1]);
     }
+    f.render_widget(test_in_text, test_values[0]);
+    f.render_widget(test_out_text, test_values[1]);
+    if cold_junction.is_some() {
+        f.render_widget(cj_text, test_values[2]);
+    }
+
     // --------Results Render -----
     // get tester inner block before rendering it
     let temp = chart_block.inner(chart_area);
     let calc_contents = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Max(2), Constraint::Min(10)])
+        .constraints([Constraint::Max(4), Constraint::Min(10)])
         .split(temp);
 
     // Render the border to not color everything
     f.render_widget(chart_block, chart_area);
 
-    // Equation def
+    // Equation def, with the OLS line under it for errors-in-variables fits
+    let mut line_val = channel.get_line_val();
+    if let Some(ols) = channel.get_ols_comparison() {
+        line_val = format!("{}\n{}", line_val, ols);
+    }
+    // and the line as PLC scaling, or the scaling being typed
+    let plc = match (app.get_mode(), app.get_currently_editing()) {
+        (Mode::EditingValue, Some(CurrentlyEditing::PlcScaling)) => {
+            Some(format!("PLC scaling: {}_", app.get_temp_point()))
+        }
+        _ => channel.get_plc_val(),
+    };
+    if let Some(plc) = plc {
+        line_val = format!("{}\n{}", line_val, plc);
+    }
     f.render_widget(
-        Paragraph::new(app.get_line_val()).alignment(Alignment::Center),
+        Paragraph::new(line_val).alignment(Alignment::Center),
         calc_contents[0],
     );
     // [Chart]
-    f.render_widget(make_chart(app), calc_contents[1]);
+    if verifying {
+        let (bars, envelope) = (channel.get_error_bars(), channel.get_tolerance_envelope());
+        f.render_widget(make_error_chart(app, &bars, &envelope), calc_contents[1]);
+    } else {
+        let point_series = channel.get_point_series();
+        let (flagged, excluded) = (channel.get_flagged_series(), channel.get_excluded_series());
+        let other_stage = channel.get_other_stage_plot();
+        f.render_widget(
+            make_chart(app, &point_series, &flagged, &excluded, &other_stage),
+            calc_contents[1],
+        );
+    }
+
+    //  ---- ----- Render things --- ----- -----
+    render_title(f, app, title_area);
+    f.render_widget(make_help(app).block(help_block), help_area);
 
-    // ---- STATIC Colors -----
+    f.render_widget(make_footer(app), footer_area);
+}
+
+// ------- Helper Functions -------
+// The title with the session's channels as tabs beside it
+fn render_title(f: &mut Frame, app: &App, area: Rect) {
+    let cols = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(16), Constraint::Min(10)])
+        .split(area);
+    f.render_widget(make_title(), cols[0]);
+    f.render_widget(make_tabs(app), cols[1]);
+}
 
-    // Title
-    let title_paragrah = Paragraph::new(Text::styled(
+pub fn make_tabs<'a>(app: &App) -> Tabs<'a> {
+    let names: Vec<Line> = app
+        .get_channel_names()
+        .into_iter()
+        .map(Line::from)
+        .collect();
+    Tabs::new(names)
+        .block(make_block(" Channels "))
+        .select(app.get_channel())
+        .highlight_style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+}
+
+pub fn make_title<'a>() -> Paragraph<'a> {
+    Paragraph::new(Text::styled(
         "Slope-a-Dope",
         Style::default().fg(Color::Yellow),
     ))
-    .block(title_block)
+    .block(make_block(""))
     .alignment(Alignment::Center)
-    .add_modifier(Modifier::BOLD);
+    .add_modifier(Modifier::BOLD)
+}
 
-    // Footer
+pub fn make_footer<'a>(app: &App) -> Paragraph<'a> {
     let s = match app.get_mode() {
         Mode::Select => "Mode: Select Point",
         Mode::Edit => "Mode: Value Selection",
         Mode::Quit => "Bye Bye!",
         Mode::EditingValue => "Editing Value",
+        Mode::ConfirmRemove => "Remove this channel with its points and details? y / n",
     };
-    let footer_text = Paragraph::new(s)
-        .block(footer_block)
-        .alignment(Alignment::Center);
-
-    //  ---- ----- Render things --- ----- -----
-    f.render_widget(title_paragrah, title_area);
-    f.render_widget(help_block, help_area);
-
-    f.render_widget(footer_text, footer_area);
+    // the last save says how it went
+    let s = match app.get_message() {
+        Some(message) => format!("{} | {}", s, message),
+        None => s.to_owned(),
+    };
+    Paragraph::new(s)
+        .block(make_block(" Current Mode "))
+        .alignment(Alignment::Center)
 }
 
-// ------- Helper Functions -------
 pub fn make_block<'a>(s: &'a str) -> Block<'a> {
     let block = Block::default()
         .borders(Borders::ALL)
         .style(Style::default().fg(Color::White))
         .border_type(BorderType::Rounded)
         .title(s);
-    return block;
+    block
 }
 
 pub fn make_paragraph<'a>(s: &'a str, b: Block<'a>) -> Paragraph<'a> {
     Paragraph::new(s).block(b).alignment(Alignment::Center)
 }
 
+// NAMUR NE43 status colours for the tester
+pub fn loop_status_color(status: LoopStatus) -> Color {
+    match status {
+        LoopStatus::Normal => Color::Green,
+        LoopStatus::UnderRange => Color::LightBlue,
+        LoopStatus::OverRange => Color::Yellow,
+        LoopStatus::FaultLow => Color::Red,
+        LoopStatus::FaultHigh => Color::LightRed,
+    }
+}
+
+pub fn loop_status_text(status: LoopStatus) -> &'static str {
+    match status {
+        LoopStatus::Normal => "in range",
+        LoopStatus::UnderRange => "UNDER RANGE",
+        LoopStatus::OverRange => "OVER RANGE",
+        LoopStatus::FaultLow => "SENSOR FAULT (<= 3.6 mA)",
+        LoopStatus::FaultHigh => "SENSOR FAULT (>= 21 mA)",
+    }
+}
+
+// Key hints for the current mode
+pub fn make_help<'a>(app: &App) -> Paragraph<'a> {
+    let channel = app.active();
+    let budget = matches!(app.get_current_screen(), ScreenID::Budget);
+    let verifying = matches!(app.get_current_screen(), ScreenID::Verify);
+    let mut s = match app.get_mode() {
+        Mode::Select if budget => String::from(
+            "Arrows: move  Enter: edit  Esc: quit\n\
+             n / x: add / remove contributor\n\
+             Input contributors reach the output through the model's slope",
+        ),
+        Mode::Edit if budget => String::from(
+            "Arrows: choose column  Enter: type it  Esc: back\n\
+             Value: expanded for normal, half width otherwise",
+        ),
+        Mode::EditingValue if budget => String::from(
+            "Enter: save  Esc: cancel\n\
+             of: in / out  Distribution: normal, k=2, rect, tri\n\
+             Leave ν blank for infinite",
+        ),
+        Mode::Select if verifying => String::from(
+            "Arrows: move  Enter: edit  Esc: quit\n\
+             n / x: add / remove reading  t: tolerance\n\
+             Readings are a measured input and the nominal output",
+        ),
+        Mode::EditingValue if app.get_currently_editing() == &Some(CurrentlyEditing::Tolerance) => {
+            String::from(
+                "Enter: save  Esc: cancel  Blank clears it\n\
+                 e.g. 0.5% FS, 0.2 bar or 0.1% rdg + 0.05",
+            )
+        }
+        Mode::Select if matches!(app.get_current_screen(), ScreenID::Metadata) => String::from(
+            "Arrows: move  Enter: edit  Esc: quit  w: save session\n\
+             Details go into the saved session and every export\n\
+             Dates as YYYY-MM-DD so an overdue reference can be flagged",
+        ),
+        Mode::EditingValue if app.get_currently_editing() == &Some(CurrentlyEditing::Metadata) => {
+            String::from("Enter: save  Esc: cancel  Blank clears it")
+        }
+        Mode::Select if matches!(app.get_current_screen(), ScreenID::History) => String::from(
+            "Arrows: move  Esc: quit  w: save and file this calibration\n\
+             Calibrations are filed by serial number when saved\n\
+             The forecast needs a tolerance from the verification screen",
+        ),
+        Mode::Select if matches!(app.get_current_screen(), ScreenID::Compare) => String::from(
+            "Arrows: move  Enter: load a session to compare  Esc: quit\n\
+             Difference is this calibration less the loaded one\n\
+             across the inputs of the points",
+        ),
+        Mode::EditingValue
+            if app.get_currently_editing() == &Some(CurrentlyEditing::PlcScaling) =>
+        {
+            String::from(
+                "Enter: apply  Esc: cancel  Blank: the points' range\n\
+                 <raw min>,<raw max> shows the line over that raw range\n\
+                 <raw min>,<raw max>,<eng min>,<eng max> replaces the points with the PLC's scaling",
+            )
+        }
+        Mode::EditingValue
+            if app.get_currently_editing() == &Some(CurrentlyEditing::ComparePath) =>
+        {
+            String::from(
+                "Enter: load  Esc: cancel\nPath of a saved session, e.g. history/SN-1042/...",
+            )
+        }
+        Mode::Select if matches!(app.get_current_screen(), ScreenID::Stages) => String::from(
+            "Arrows: move  Esc: quit\n\
+             f: record as-found, then switch between as-found and as-left\n\
+             Errors are each stage's calibration less the nominal",
+        ),
+        Mode::Select => String::from(
+            "Arrows: move  Enter: edit  Esc: quit  f: stage  w: save  p: report\n\
+             n / x: add / remove point  m: cycle model  r: refine fit  s: export chart\n\
+             k: export code  u: lookup table  z: PLC scaling  g: line fit\n\
+             i / o: cycle input / output units  b: robust fit  e: exclude / include point\n\
+             c: band level  q: change input quantity  l: 4-20 mA loop scaling\n\
+             Tab: next channel  d / D: add / remove channel  E: export every channel",
+        ),
+        Mode::Edit if verifying => String::from(
+            "Arrows: choose value  Enter: type it  Esc: back\n\
+             Units may be typed, e.g. 14.7 psi",
+        ),
+        Mode::Edit => String::from(
+            "Arrows: choose value  Enter: type it  Esc: back\n\
+             u(in) / u(out): standard uncertainties that weight the fit",
+        ),
+        Mode::EditingValue => String::from(
+            "Enter: save  Esc: cancel\nUnits may be typed, e.g. 14.7 psi\n\
+             Leave an uncertainty blank to clear it",
+        ),
+        Mode::ConfirmRemove => String::from("y: remove the channel  any other key: keep it"),
+        Mode::Quit => String::new(),
+    };
+    if channel.get_adc().is_some() {
+        match app.get_mode() {
+            Mode::Select => s += "\na: switch input between counts and volts",
+            Mode::EditingValue => s += "\nCounts and volts are interchangeable",
+            _ => {}
+        }
+    }
+    if let (Some(dac), Mode::Select) = (channel.get_dac(), app.get_mode()) {
+        s += &format!(
+            "\nTest an output for the {} bit DAC code to command",
+            dac.bits
+        );
+    }
+    Paragraph::new(s).alignment(Alignment::Center)
+}
+
+// Table of calibration points, the selected row is highlighted and the edited cell colored like the tester.
+// Uncertainties are blank when not given and w is the point's relative weight in the fit
+pub fn make_point_table<'a>(app: &App, input: &str, output: &str) -> Table<'a> {
+    let channel = app.active();
+    let loop_mode = channel.get_loop_scaling().is_some();
+    let header = Row::new(vec![
+        Cell::from(""),
+        Cell::from(input.to_owned()),
+        Cell::from(output.to_owned()),
+        Cell::from("u(in)"),
+        Cell::from("u(out)"),
+        Cell::from("w"),
+    ])
+    .style(Style::default().fg(Color::Yellow));
+
+    let on_points = matches!(app.get_current_screen(), ScreenID::Points);
+    let rows = channel.get_points().iter().enumerate().map(|(i, p)| {
+        let label = match (loop_mode, i) {
+            (true, 0) => "LRV".to_owned(),
+            (true, _) => "URV".to_owned(),
+            (false, _) => format!("{}", i + 1),
+        };
+        // outliers get a mark as well as the color
+        let flagged = !p.is_excluded() && channel.get_outliers().get(i) == Some(&true);
+        let label = if flagged { label + "!" } else { label };
+        let vals = p.get_val();
+        let (u_in, u_out) = p.get_uncertainty();
+        let uncertainty = |u: Option<f64>| u.map(|u| format!("{:.4}", u)).unwrap_or_default();
+        let mut cells = [
+            format!("{:.4}", vals.get("in").cloned().unwrap_or(0.0)),
+            format!("{:.4}", vals.get("out").cloned().unwrap_or(0.0)),
+            uncertainty(u_in),
+            uncertainty(u_out),
+        ];
+        let mut styles = [Style::default(); 4];
+        let selected = on_points && i == channel.get_selected();
+        if selected {
+            if let Some(editing) = app.get_currently_editing() {
+                let col = match editing {
+                    CurrentlyEditing::Input => Some(0),
+                    CurrentlyEditing::Output => Some(1),
+                    CurrentlyEditing::InputUncertainty => Some(2),
+                    CurrentlyEditing::OutputUncertainty => Some(3),
+                    CurrentlyEditing::ColdJunction
+                    | CurrentlyEditing::Budget(_)
+                    | CurrentlyEditing::Tolerance
+                    | CurrentlyEditing::Metadata
+                    | CurrentlyEditing::ComparePath
+                    | CurrentlyEditing::PlcScaling => None,
+                };
+                match (app.get_mode(), col) {
+                    (Mode::Edit, Some(col)) => {
+                        styles[col] = Style::default().fg(Color::LightMagenta)
+                    }
+                    (Mode::EditingValue, Some(col)) => {
+                        cells[col] = app.get_temp_point().to_owned();
+                        styles[col] = Style::default().fg(Color::Green);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let weight = channel
+            .get_weights()
+            .get(i)
+            .cloned()
+            .flatten()
+            .map(|w| format!("{:.2}", w))
+            .unwrap_or_default();
+        let mut row_cells = vec![Cell::from(label)];
+        row_cells.extend(
+            cells
+                .into_iter()
+                .zip(styles)
+                .map(|(c, style)| Cell::from(c).style(style)),
+        );
+        row_cells.push(Cell::from(weight));
+        let mut row = Row::new(row_cells);
+        if p.is_excluded() {
+            row = row.style(
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::CROSSED_OUT),
+            );
+        } else if flagged {
+            row = row.style(Style::default().fg(Color::Red));
+        }
+        if selected {
+            row.add_modifier(Modifier::BOLD | Modifier::REVERSED)
+        } else {
+            row
+        }
+    });
+    Table::new(rows.collect::<Vec<_>>())
+        .header(header)
+        .widths(&[
+            Constraint::Length(4),
+            Constraint::Percentage(24),
+            Constraint::Percentage(24),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+            Constraint::Percentage(10),
+        ])
+        .column_spacing(1)
+}
+
+// Verification readings with what the calibration makes of them, green when they pass and red when
+// they don't. The result column says how much of the tolerance the error uses
+pub fn make_verification_table<'a>(app: &App, checks: &[Option<Check>]) -> Table<'a> {
+    let channel = app.active();
+    let header = Row::new(vec![
+        Cell::from(""),
+        Cell::from("Measured"),
+        Cell::from("Nominal"),
+        Cell::from("Indicated"),
+        Cell::from("Error"),
+        Cell::from("Result"),
+    ])
+    .style(Style::default().fg(Color::Yellow));
+    let rows = channel
+        .get_verification_points()
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let vals = p.get_val();
+            let mut cells = [
+                format!("{:.4}", vals.get("in").cloned().unwrap_or(0.0)),
+                format!("{:.4}", vals.get("out").cloned().unwrap_or(0.0)),
+            ];
+            let mut styles = [Style::default(); 2];
+            let selected = i == channel.get_verify_selected();
+            let col = match app.get_currently_editing() {
+                Some(CurrentlyEditing::Input) => Some(0),
+                Some(CurrentlyEditing::Output) => Some(1),
+                _ => None,
+            };
+            match (selected, app.get_mode(), col) {
+                (true, Mode::Edit, Some(col)) => {
+                    styles[col] = Style::default().fg(Color::LightMagenta)
+                }
+                (true, Mode::EditingValue, Some(col)) => {
+                    cells[col] = app.get_temp_point().to_owned();
+                    styles[col] = Style::default().fg(Color::Green);
+                }
+                _ => {}
+            }
+            let check = checks.get(i).cloned().flatten();
+            let (indicated, error) = match check {
+                Some(c) => (format!("{:.4}", c.indicated), format!("{:+.4}", c.error())),
+                None => Default::default(),
+            };
+            let (result, color) = match check.filter(|c| c.limit.is_finite()) {
+                Some(c) if c.passed() => (format!("PASS {:.0}%", 100.0 * c.used()), Color::Green),
+                Some(c) => (format!("FAIL {:.0}%", 100.0 * c.used()), Color::Red),
+                None => (String::new(), Color::White),
+            };
+            let mut row_cells = vec![Cell::from(format!("{}", i + 1))];
+            row_cells.extend(
+                cells
+                    .into_iter()
+                    .zip(styles)
+                    .map(|(c, style)| Cell::from(c).style(style)),
+            );
+            row_cells.extend([Cell::from(indicated), Cell::from(error), Cell::from(result)]);
+            let row = Row::new(row_cells).style(Style::default().fg(color));
+            if selected {
+                row.add_modifier(Modifier::BOLD | Modifier::REVERSED)
+            } else {
+                row
+            }
+        });
+    Table::new(rows.collect::<Vec<_>>())
+        .header(header)
+        .widths(&[
+            Constraint::Length(3),
+            Constraint::Percentage(18),
+            Constraint::Percentage(18),
+            Constraint::Percentage(18),
+            Constraint::Percentage(15),
+            Constraint::Length(9),
+        ])
+        .column_spacing(1)
+}
+
+// Error of each verification reading as a bar from zero, inside the tolerance band
+pub fn make_error_chart<'a>(
+    app: &App,
+    bars: &'a [([(f64, f64); 2], bool)],
+    envelope: &'a [Vec<(f64, f64)>; 2],
+) -> Chart<'a> {
+    let channel = app.active();
+    let mut datasets: Vec<Dataset> = envelope
+        .iter()
+        .map(|edge| {
+            Dataset::default()
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Yellow))
+                .marker(Marker::Braille)
+                .data(edge)
+        })
+        .collect();
+    for (bar, passed) in bars {
+        let color = if *passed { Color::Green } else { Color::Red };
+        datasets.push(
+            Dataset::default()
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(color))
+                .marker(Marker::Braille)
+                .data(bar),
+        );
+        datasets.push(
+            Dataset::default()
+                .graph_type(GraphType::Scatter)
+                .style(Style::default().fg(color))
+                .marker(Marker::Dot)
+                .data(&bar[1..]),
+        );
+    }
+    let (x_bounds, y_bounds) = channel.get_error_bounds();
+    let output = channel.get_output();
+    Chart::new(datasets)
+        .x_axis(
+            Axis::default()
+                .title(Span::styled(
+                    format!("Nominal {}", output.title()),
+                    Style::default().fg(Color::Red),
+                ))
+                .bounds([x_bounds.bounds.0, x_bounds.bounds.1])
+                .labels(x_bounds.labels.into_iter().map(Span::from).collect()),
+        )
+        .y_axis(
+            Axis::default()
+                .title(Span::styled(
+                    format!("Error [{}]", output.unit),
+                    Style::default().fg(Color::Red),
+                ))
+                .bounds([y_bounds.bounds.0, y_bounds.bounds.1])
+                .labels(y_bounds.labels.into_iter().map(Span::from).collect()),
+        )
+}
+
 // make chart
-pub fn make_chart<'a>(app: &'a App) -> Chart<'a> {
-    let mut datasets = vec![Dataset::default()
-        .graph_type(GraphType::Line)
-        .style(Style::default().fg(Color::Green))
-        .marker(Marker::Dot)
-        .data(app.get_plot_data().as_slice())];
-
-    if let Some(test_point) = app.get_test_series() {
+// Outliers are drawn over the points in red and excluded points in gray, the bands go underneath.
+// In the as-found / as-left workflow the other stage's curve is drawn in gray behind the fit
+pub fn make_chart<'a>(
+    app: &'a App,
+    points: &'a [(f64, f64)],
+    flagged: &'a [(f64, f64)],
+    excluded: &'a [(f64, f64)],
+    other_stage: &'a [(f64, f64)],
+) -> Chart<'a> {
+    let channel = app.active();
+    let bands = channel.get_bands();
+    let mut datasets = Vec::new();
+    for (edges, color) in [
+        (&bands.prediction, Color::Blue),
+        (&bands.confidence, Color::Cyan),
+    ] {
+        for edge in edges {
+            datasets.push(
+                Dataset::default()
+                    .graph_type(GraphType::Line)
+                    .style(Style::default().fg(color))
+                    .marker(Marker::Braille)
+                    .data(edge),
+            );
+        }
+    }
+    datasets.extend([
+        Dataset::default()
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::DarkGray))
+            .marker(Marker::Braille)
+            .data(other_stage),
+        Dataset::default()
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Green))
+            .marker(Marker::Dot)
+            .data(channel.get_plot_data().as_slice()),
+        Dataset::default()
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::LightMagenta))
+            .marker(Marker::Dot)
+            .data(points),
+        Dataset::default()
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::Red))
+            .marker(Marker::Dot)
+            .data(flagged),
+        Dataset::default()
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::DarkGray))
+            .marker(Marker::Dot)
+            .data(excluded),
+    ]);
+
+    if let Some(test_point) = channel.get_test_series() {
         datasets.push(
             Dataset::default()
                 .graph_type(GraphType::Scatter)
@@ -390,21 +933,679 @@ pub fn make_chart<'a>(app: &'a App) -> Chart<'a> {
         );
     }
 
-    let bounds = app.get_bounds();
+    let (x_bounds, y_bounds) = channel.get_bounds();
 
     Chart::new(datasets)
         .x_axis(
             Axis::default()
-                .title(Span::styled("Voltage", Style::default().fg(Color::Red)))
+                .title(Span::styled(
+                    channel.get_input().title(),
+                    Style::default().fg(Color::Red),
+                ))
                 .style(Style::default())
-                .bounds([bounds.bounds.0.clone(), bounds.bounds.1.clone()])
-                .labels(bounds.labels[..].iter().cloned().map(Span::from).collect()),
+                .bounds([x_bounds.bounds.0, x_bounds.bounds.1])
+                .labels(x_bounds.labels.into_iter().map(Span::from).collect()),
         )
         .y_axis(
             Axis::default()
-                .title(Span::styled("Physical", Style::default().fg(Color::Red)))
+                .title(Span::styled(
+                    channel.get_output().title(),
+                    Style::default().fg(Color::Red),
+                ))
                 .style(Style::default())
-                .bounds([bounds.bounds.0.clone(), bounds.bounds.1.clone()])
-                .labels(bounds.labels[..].iter().cloned().map(Span::from).collect()),
+                .bounds([y_bounds.bounds.0, y_bounds.bounds.1])
+                .labels(y_bounds.labels.into_iter().map(Span::from).collect()),
         )
 }
+
+// Uncertainty budget screen: a line per contributor, then the fit's, with the GUM summary under them
+pub fn render_budget(f: &mut Frame, app: &App, area: Rect) {
+    let channel = app.active();
+    let budget = channel.get_budget();
+    let title = match budget.as_ref() {
+        Some(b) => format!(
+            " Uncertainty budget - at {} = {} ",
+            channel.get_input().name,
+            units::format_value(b.at, channel.get_input().unit)
+        ),
+        None => " Uncertainty budget ".to_owned(),
+    };
+    let mut block = make_block(&title);
+    match app.get_mode() {
+        Mode::Select | Mode::ConfirmRemove => {
+            block = block.style(Style::default().fg(Color::LightMagenta))
+        }
+        Mode::Edit | Mode::EditingValue => block = block.style(Style::default().fg(Color::Green)),
+        Mode::Quit => {}
+    }
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .split(inner);
+    f.render_widget(make_budget_table(app, budget.as_ref()), parts[0]);
+
+    let out_unit = channel.get_output().unit;
+    let summary = match budget {
+        Some(b) => {
+            let c = b.combined;
+            let dof = c.dof.map(|d| format!("{:.1}", d)).unwrap_or("∞".to_owned());
+            format!(
+                "Combined standard uncertainty u_c = {}\n\
+                 Effective degrees of freedom {} | k = {:.2} for {:.2}% coverage\n\
+                 Expanded uncertainty U = k·u_c = {}",
+                units::format_value(c.standard, out_unit),
+                dof,
+                c.k,
+                budget::COVERAGE * 100.0,
+                units::format_value(c.expanded(), out_unit)
+            )
+        }
+        None => "No model to take the sensitivity coefficients from".to_owned(),
+    };
+    f.render_widget(
+        Paragraph::new(summary).alignment(Alignment::Center),
+        parts[1],
+    );
+}
+
+// Budget lines with their standard uncertainty, sensitivity, contribution to the output and share
+// of the combined variance. Values are in the unit of the side they are on
+pub fn make_budget_table<'a>(app: &App, budget: Option<&Budget>) -> Table<'a> {
+    let channel = app.active();
+    let header = Row::new(vec![
+        "",
+        "Contributor",
+        "of",
+        "Value",
+        "Distribution",
+        "u",
+        "c",
+        "|c·u|",
+        "ν",
+        "%",
+    ])
+    .style(Style::default().fg(Color::Yellow));
+    let unit = |side: Side| match side {
+        Side::Input => channel.get_input().unit,
+        Side::Output => channel.get_output().unit,
+    };
+    // the numbers from a term, blank without a model
+    let numbers = |term: Option<&Term>| match (term, budget) {
+        (Some(t), Some(b)) => {
+            let share = match b.combined.standard {
+                s if s > 0.0 => format!("{:.1}", 100.0 * (t.contribution() / s).powi(2)),
+                _ => String::new(),
+            };
+            [
+                format!("{:.4}", t.standard),
+                format!("{:.4}", t.sensitivity),
+                format!("{:.4}", t.contribution()),
+                t.dof.map(|d| format!("{:.0}", d)).unwrap_or("∞".to_owned()),
+                share,
+            ]
+        }
+        _ => Default::default(),
+    };
+
+    let mut rows: Vec<Row> = channel
+        .get_contributors()
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let [u, sens, contribution, dof, share] = numbers(budget.and_then(|b| b.terms.get(i)));
+            let mut cells = [
+                format!("{}", i + 1),
+                c.name.clone(),
+                c.side.name().to_owned(),
+                units::format_value(c.value, unit(c.side)),
+                c.distribution.name(),
+                u,
+                sens,
+                contribution,
+                // the typed degrees of freedom stand even without a model
+                c.dof.map(|d| format!("{:.0}", d)).unwrap_or(dof),
+                share,
+            ];
+            let mut styles = [Style::default(); 10];
+            let selected = i == channel.get_budget_selected();
+            if let (true, Some(CurrentlyEditing::Budget(field))) =
+                (selected, app.get_currently_editing())
+            {
+                let col = match field {
+                    BudgetField::Name => 1,
+                    BudgetField::Side => 2,
+                    BudgetField::Value => 3,
+                    BudgetField::Distribution => 4,
+                    BudgetField::Dof => 8,
+                };
+                match app.get_mode() {
+                    Mode::Edit => styles[col] = Style::default().fg(Color::LightMagenta),
+                    Mode::EditingValue => {
+                        cells[col] = app.get_temp_point().to_owned();
+                        styles[col] = Style::default().fg(Color::Green);
+                    }
+                    _ => {}
+                }
+            }
+            let row = Row::new(
+                cells
+                    .into_iter()
+                    .zip(styles)
+                    .map(|(c, style)| Cell::from(c).style(style)),
+            );
+            if selected {
+                row.add_modifier(Modifier::BOLD | Modifier::REVERSED)
+            } else {
+                row
+            }
+        })
+        .collect();
+    // the fit's own line can't be edited
+    if let Some(fit) = budget.and_then(|b| b.fit.as_ref()) {
+        let [u, sens, contribution, dof, share] = numbers(Some(fit));
+        let out = channel.get_output().unit;
+        rows.push(
+            Row::new(vec![
+                "fit".to_owned(),
+                "Fit residual".to_owned(),
+                Side::Output.name().to_owned(),
+                units::format_value(fit.standard, out),
+                Distribution::Normal(1.0).name(),
+                u,
+                sens,
+                contribution,
+                dof,
+                share,
+            ])
+            .style(Style::default().fg(Color::Cyan)),
+        );
+    }
+    Table::new(rows)
+        .header(header)
+        .widths(&[
+            Constraint::Length(4),
+            Constraint::Percentage(20),
+            Constraint::Length(4),
+            Constraint::Percentage(14),
+            Constraint::Percentage(14),
+            Constraint::Percentage(10),
+            Constraint::Percentage(10),
+            Constraint::Percentage(10),
+            Constraint::Length(5),
+            Constraint::Length(5),
+        ])
+        .column_spacing(1)
+}
+
+// As-found against as-left: each point's reading and error in both stages, and how the fit moved
+pub fn render_stages(f: &mut Frame, app: &App, area: Rect) {
+    let channel = app.active();
+    let title = match channel.get_stage() {
+        Some(stage) => format!(" As-found / as-left - editing {} ", stage.name()),
+        None => " As-found / as-left ".to_owned(),
+    };
+    let block = make_block(&title).style(Style::default().fg(Color::LightMagenta));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let Some(comparison) = channel.get_stage_comparison() else {
+        f.render_widget(
+            Paragraph::new(
+                "Press f to record the points as found. They are copied to start the as-left stage,\n\
+                 enter the readings taken after adjustment there and f switches between the two",
+            )
+            .alignment(Alignment::Center),
+            inner,
+        );
+        return;
+    };
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(2)])
+        .split(inner);
+
+    let header = Row::new(vec![
+        "",
+        "Nominal",
+        "As-found reading",
+        "As-found error",
+        "As-left reading",
+        "As-left error",
+        "Δ error",
+    ])
+    .style(Style::default().fg(Color::Yellow));
+    let value = |v: Option<f64>, sign: bool| match (v, sign) {
+        (Some(v), true) => format!("{:+.4}", v),
+        (Some(v), false) => format!("{:.4}", v),
+        (None, _) => String::new(),
+    };
+    let rows: Vec<Row> = comparison
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            Row::new(vec![
+                format!("{}", i + 1),
+                value(Some(r.nominal).filter(|n| n.is_finite()), false),
+                value(r.found.map(|f| f.0), false),
+                value(r.found.map(|f| f.1), true),
+                value(r.left.map(|l| l.0), false),
+                value(r.left.map(|l| l.1), true),
+                value(r.delta(), true),
+            ])
+        })
+        .collect();
+    let table = Table::new(rows)
+        .header(header)
+        .widths(&[
+            Constraint::Length(4),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+        ])
+        .column_spacing(1);
+    f.render_widget(table, parts[0]);
+
+    // how the fit moved, coefficients for lines and the behaviour at the working point otherwise
+    let (input, output) = (channel.get_input().unit, channel.get_output().unit);
+    let output_unit = match output {
+        units::Unit::Unitless => String::new(),
+        unit => format!(" {}", unit),
+    };
+    let change = |what: &str, [a, b]: [Option<f64>; 2], unit: String| match (a, b) {
+        (Some(a), Some(b)) => format!(
+            "{}: {:.4} -> {:.4}{} (change {:+.4})",
+            what,
+            a,
+            b,
+            unit,
+            b - a
+        ),
+        _ => format!("{}: no fit to compare", what),
+    };
+    let summary = match comparison.lines {
+        Some([(m0, b0), (m1, b1)]) => format!(
+            "{}\n{}",
+            change(
+                "Slope",
+                [Some(m0), Some(m1)],
+                app::slope_unit(input, output)
+            ),
+            change("Offset", [Some(b0), Some(b1)], output_unit.clone())
+        ),
+        None => {
+            let at = units::format_value(comparison.at, input);
+            format!(
+                "{}\n{}",
+                change(
+                    &format!("Slope at {}", at),
+                    comparison.slopes,
+                    app::slope_unit(input, output)
+                ),
+                change(
+                    &format!("Output at {}", at),
+                    comparison.outputs,
+                    output_unit.clone()
+                )
+            )
+        }
+    };
+    f.render_widget(
+        Paragraph::new(summary).alignment(Alignment::Center),
+        parts[1],
+    );
+}
+
+// Form of the calibration details, the selected field is typed over in place
+pub fn render_metadata(f: &mut Frame, app: &App, area: Rect) {
+    let channel = app.active();
+    let mut block = make_block(" Calibration details ");
+    match app.get_mode() {
+        Mode::EditingValue => block = block.style(Style::default().fg(Color::Green)),
+        _ => block = block.style(Style::default().fg(Color::LightMagenta)),
+    }
+    let details = channel.get_metadata();
+    let overdue = details.reference_overdue(&metadata::now());
+    let rows: Vec<Row> = Field::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let selected = i == channel.get_metadata_selected();
+            let (value, style) = match (selected, app.get_mode()) {
+                (true, Mode::EditingValue) => (
+                    app.get_temp_point().to_owned(),
+                    Style::default().fg(Color::Green),
+                ),
+                _ if *field == Field::ReferenceDue && overdue => (
+                    format!("{} (overdue)", details.get(*field)),
+                    Style::default().fg(Color::Red),
+                ),
+                _ => (details.get(*field).to_owned(), Style::default()),
+            };
+            let row = Row::new(vec![
+                Cell::from(field.label()).style(Style::default().fg(Color::Yellow)),
+                Cell::from(value).style(style),
+            ]);
+            if selected {
+                row.add_modifier(Modifier::BOLD | Modifier::REVERSED)
+            } else {
+                row
+            }
+        })
+        .collect();
+    let table = Table::new(rows)
+        .block(block)
+        .widths(&[Constraint::Length(22), Constraint::Percentage(100)])
+        .column_spacing(1);
+    f.render_widget(table, area);
+}
+
+// Slope and intercept of each of the sensor's calibrations over time with their trends, and when
+// the drift is expected to use up the tolerance
+pub fn render_history(f: &mut Frame, app: &App, area: Rect) {
+    let channel = app.active();
+    let serial = channel.get_metadata().get(Field::Serial);
+    let history = channel.get_history();
+    let title = match serial {
+        "" => " History ".to_owned(),
+        serial => format!(
+            " History - {} | {} calibration{} ",
+            serial,
+            history.len(),
+            if history.len() == 1 { "" } else { "s" }
+        ),
+    };
+    let block = make_block(&title).style(Style::default().fg(Color::LightMagenta));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    if serial.is_empty() {
+        f.render_widget(
+            Paragraph::new(
+                "Fill in the serial number on the details screen and save with w to start a history",
+            )
+            .alignment(Alignment::Center),
+            inner,
+        );
+        return;
+    }
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(8), Constraint::Length(2)])
+        .split(inner);
+    // a gap keeps the date labels of the two charts apart
+    let charts = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(50),
+            Constraint::Length(3),
+            Constraint::Percentage(50),
+        ])
+        .split(parts[0]);
+
+    let (input, output) = (channel.get_input().unit, channel.get_output().unit);
+    let drift = channel.get_drift();
+    let calibrations: Vec<Calibration> = history.iter().map(|e| e.calibration).collect();
+    let current = channel.get_metadata().get(Field::Date);
+    let this_day = history
+        .iter()
+        .find(|e| e.date == current)
+        .map(|e| e.calibration.day);
+    let series = |value: fn(&Calibration) -> f64, rate: Option<f64>| {
+        let points: Vec<(f64, f64)> = calibrations.iter().map(|c| (c.day, value(c))).collect();
+        // the trend runs through the mean of the points
+        let trend = match (rate, calibrations.first(), calibrations.last()) {
+            (Some(rate), Some(first), Some(last)) => {
+                let n = points.len() as f64;
+                let mean_day = points.iter().map(|p| p.0).sum::<f64>() / n;
+                let mean = points.iter().map(|p| p.1).sum::<f64>() / n;
+                [first.day, last.day]
+                    .iter()
+                    .map(|d| (*d, mean + rate * (d - mean_day)))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        let this: Vec<(f64, f64)> = points
+            .iter()
+            .filter(|p| Some(p.0) == this_day)
+            .cloned()
+            .collect();
+        (points, trend, this)
+    };
+    let slopes = series(|c| c.slope, drift.map(|d| d.slope));
+    let intercepts = series(|c| c.intercept, drift.map(|d| d.intercept));
+    let slope_title = format!("Slope [{}]", app::slope_unit(input, output).trim());
+    let intercept_title = format!("Intercept [{}]", output);
+    for ((points, trend, this), title, area) in [
+        (&slopes, slope_title, charts[0]),
+        (&intercepts, intercept_title, charts[2]),
+    ] {
+        f.render_widget(make_history_chart(points, trend, this, &title), area);
+    }
+
+    let per_year = |rate: f64, unit: String| format!("{:+.4}{} per year", rate * drift::YEAR, unit);
+    let output_unit = match output {
+        units::Unit::Unitless => String::new(),
+        unit => format!(" {}", unit),
+    };
+    let rates = match drift {
+        Some(d) => format!(
+            "Slope drift {} | intercept drift {}",
+            per_year(d.slope, app::slope_unit(input, output)),
+            per_year(d.intercept, output_unit)
+        ),
+        None => "Needs calibrations on two different days to trend".to_owned(),
+    };
+    let forecast = match (channel.get_drift_forecast(), channel.get_tolerance(), drift) {
+        (Some(fc), _, _) => {
+            let last = calibrations.last().map(|c| c.day).unwrap_or(fc.day);
+            format!(
+                "At {} the drift uses up ±{} around {} ({:.0} days after the last calibration)",
+                units::format_value(fc.at, input),
+                units::format_value(fc.limit, output),
+                metadata::format_day(fc.day),
+                fc.day - last
+            )
+        }
+        (None, None, Some(_)) => {
+            "Set a tolerance on the verification screen to forecast".to_owned()
+        }
+        (None, Some(_), Some(_)) => "Not drifting".to_owned(),
+        (None, _, None) => String::new(),
+    };
+    f.render_widget(
+        Paragraph::new(format!("{}\n{}", rates, forecast)).alignment(Alignment::Center),
+        parts[1],
+    );
+}
+
+// One coefficient over time: the calibrations, their trend and this session's in yellow
+pub fn make_history_chart<'a>(
+    points: &'a [(f64, f64)],
+    trend: &'a [(f64, f64)],
+    this: &'a [(f64, f64)],
+    title: &str,
+) -> Chart<'a> {
+    let datasets = vec![
+        Dataset::default()
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Green))
+            .marker(Marker::Braille)
+            .data(trend),
+        Dataset::default()
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::LightMagenta))
+            .marker(Marker::Dot)
+            .data(points),
+        Dataset::default()
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::LightYellow))
+            .marker(Marker::Block)
+            .data(this),
+    ];
+    // dates along the bottom, at least a month wide so a single calibration sits in the middle
+    let first = points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let last = points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+    let (mut from, mut to) = if first.is_finite() {
+        (first, last)
+    } else {
+        (0.0, 0.0)
+    };
+    if to - from < 30.0 {
+        let middle = (from + to) / 2.0;
+        (from, to) = (middle - 15.0, middle + 15.0);
+    }
+    let pad = (to - from) * 0.05;
+    (from, to) = (from - pad, to + pad);
+    let dates = [from, (from + to) / 2.0, to]
+        .iter()
+        .map(|d| Span::from(metadata::format_day(*d)))
+        .collect();
+    let values = points.iter().map(|p| p.1);
+    let (lo, hi) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+        (lo.min(v), hi.max(v))
+    });
+    let y = if lo.is_finite() {
+        app::make_bounds(lo, hi)
+    } else {
+        app::make_bounds(0.0, 1.0)
+    };
+    Chart::new(datasets)
+        .x_axis(Axis::default().bounds([from, to]).labels(dates))
+        .y_axis(
+            Axis::default()
+                .title(Span::styled(
+                    title.to_owned(),
+                    Style::default().fg(Color::Red),
+                ))
+                .bounds([y.bounds.0, y.bounds.1])
+                .labels(y.labels.into_iter().map(Span::from).collect()),
+        )
+}
+
+// This calibration over the one loaded from another session, and the difference between them
+pub fn render_compare(f: &mut Frame, app: &App, area: Rect) {
+    let channel = app.active();
+    let comparison = channel.get_comparison();
+    let title = match comparison.as_ref() {
+        Some(c) => format!(" Compare - this session against {} ", c.label),
+        None => " Compare ".to_owned(),
+    };
+    let mut block = make_block(&title);
+    match app.get_mode() {
+        Mode::EditingValue => block = block.style(Style::default().fg(Color::Green)),
+        _ => block = block.style(Style::default().fg(Color::LightMagenta)),
+    }
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let typing = app.get_currently_editing() == &Some(CurrentlyEditing::ComparePath);
+    let Some(comparison) = comparison.filter(|_| !typing) else {
+        let text = if typing {
+            format!("Session to compare against: {}", app.get_temp_point())
+        } else {
+            "Press Enter and type the path of a saved session, or start with --compare <file>"
+                .to_owned()
+        };
+        f.render_widget(Paragraph::new(text).alignment(Alignment::Center), inner);
+        return;
+    };
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(8), Constraint::Length(1)])
+        .split(inner);
+    let charts = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(50),
+            Constraint::Length(3),
+            Constraint::Percentage(50),
+        ])
+        .split(parts[0]);
+
+    // both curves, the loaded one in gray behind this one
+    let points = channel.get_point_series();
+    let (input, output) = (channel.get_input(), channel.get_output());
+    let curves = vec![
+        Dataset::default()
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::DarkGray))
+            .marker(Marker::Braille)
+            .data(&comparison.other_plot),
+        Dataset::default()
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Green))
+            .marker(Marker::Braille)
+            .data(channel.get_plot_data()),
+        Dataset::default()
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::LightMagenta))
+            .marker(Marker::Dot)
+            .data(&points),
+    ];
+    let all: Vec<(f64, f64)> = comparison
+        .other_plot
+        .iter()
+        .chain(channel.get_plot_data())
+        .cloned()
+        .collect();
+    let (x, y) = app::bounds_of(&all);
+    f.render_widget(
+        Chart::new(curves)
+            .x_axis(axis(input.title(), x))
+            .y_axis(axis(output.title(), y)),
+        charts[0],
+    );
+
+    // the difference has zero in view so its size can be judged
+    let max = comparison.max;
+    let max_point: Vec<(f64, f64)> = max.into_iter().collect();
+    let difference = vec![
+        Dataset::default()
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Yellow))
+            .marker(Marker::Braille)
+            .data(&comparison.difference),
+        Dataset::default()
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(Color::Red))
+            .marker(Marker::Block)
+            .data(&max_point),
+    ];
+    let mut with_zero = comparison.difference.clone();
+    if let Some((x0, _)) = with_zero.first().cloned() {
+        with_zero.push((x0, 0.0));
+    }
+    let (x, y) = app::bounds_of(&with_zero);
+    f.render_widget(
+        Chart::new(difference)
+            .x_axis(axis(input.title(), x))
+            .y_axis(axis(format!("Difference [{}]", output.unit), y)),
+        charts[2],
+    );
+
+    let summary = match max {
+        Some((at, d)) => format!(
+            "Largest difference {} at {} = {}",
+            units::format_value(d, output.unit),
+            input.name,
+            units::format_value(at, input.unit)
+        ),
+        None => "The calibrations don't overlap over the points".to_owned(),
+    };
+    f.render_widget(
+        Paragraph::new(summary).alignment(Alignment::Center),
+        parts[1],
+    );
+}
+
+// A chart axis with a red title and round bounds
+fn axis<'a>(title: String, bounds: app::Bounds) -> Axis<'a> {
+    Axis::default()
+        .title(Span::styled(title, Style::default().fg(Color::Red)))
+        .bounds([bounds.bounds.0, bounds.bounds.1])
+        .labels(bounds.labels.into_iter().map(Span::from).collect())
+}
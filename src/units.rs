@@ -0,0 +1,474 @@
+use std::fmt;
+
+// What kind of thing a unit measures. Only units with the same dimension can be converted
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dimension {
+    Voltage,
+    Current,
+    Counts,
+    Resistance,
+    Temperature,
+    Pressure,
+    Dimensionless,
+}
+
+impl Dimension {
+    // What we call a quantity of this dimension unless the session names it
+    pub fn default_name(&self) -> &'static str {
+        match self {
+            Dimension::Voltage => "Voltage",
+            Dimension::Current => "Current",
+            Dimension::Counts => "Counts",
+            Dimension::Resistance => "Resistance",
+            Dimension::Temperature => "Temperature",
+            Dimension::Pressure => "Pressure",
+            Dimension::Dimensionless => "Physical",
+        }
+    }
+
+    pub fn default_unit(&self) -> Unit {
+        match self {
+            Dimension::Voltage => Unit::Volt,
+            Dimension::Current => Unit::Milliamp,
+            Dimension::Counts => Unit::Count,
+            Dimension::Resistance => Unit::Ohm,
+            Dimension::Temperature => Unit::Celsius,
+            Dimension::Pressure => Unit::Bar,
+            Dimension::Dimensionless => Unit::Unitless,
+        }
+    }
+
+    // Match a dimension by its default name, e.g. "current"
+    pub fn from_name(s: &str) -> Option<Dimension> {
+        DIMENSIONS
+            .iter()
+            .find(|d| d.default_name().eq_ignore_ascii_case(s.trim()))
+            .cloned()
+    }
+
+    // Cycle through the quantities a sensor usually produces as its raw signal
+    pub fn next_input(&self) -> Dimension {
+        match self {
+            Dimension::Voltage => Dimension::Current,
+            Dimension::Current => Dimension::Counts,
+            Dimension::Counts => Dimension::Resistance,
+            _ => Dimension::Voltage,
+        }
+    }
+}
+
+const DIMENSIONS: [Dimension; 7] = [
+    Dimension::Voltage,
+    Dimension::Current,
+    Dimension::Counts,
+    Dimension::Resistance,
+    Dimension::Temperature,
+    Dimension::Pressure,
+    Dimension::Dimensionless,
+];
+
+// A named quantity on one axis of the calibration, e.g. "Loop current" in mA
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantity {
+    pub name: String,
+    pub unit: Unit,
+}
+
+impl Quantity {
+    pub fn new(name: &str, unit: Unit) -> Self {
+        Quantity {
+            name: name.to_owned(),
+            unit,
+        }
+    }
+
+    // Quantity named after the unit's dimension, e.g. mA -> "Current"
+    pub fn from_unit(unit: Unit) -> Self {
+        Quantity::new(unit.dimension().default_name(), unit)
+    }
+
+    // Build a quantity from an optional name and unit. A name like "current" picks the
+    // dimension's default unit when no unit is given; anything else is used as a label
+    pub fn from_parts(name: Option<&str>, unit: Option<Unit>, fallback: Unit) -> Self {
+        let dimension = name.and_then(Dimension::from_name);
+        let unit = unit
+            .or(dimension.map(|d| d.default_unit()))
+            .unwrap_or(fallback);
+        match (name, dimension) {
+            (Some(_), Some(d)) if d == unit.dimension() => Quantity::from_unit(unit),
+            (Some(n), _) => Quantity::new(n, unit),
+            (None, _) => Quantity::from_unit(unit),
+        }
+    }
+
+    // "Pressure [bar]", or just the name when there is no unit
+    pub fn title(&self) -> String {
+        match self.unit {
+            Unit::Unitless => self.name.clone(),
+            _ => format!("{} [{}]", self.name, self.unit),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Unit {
+    Volt,
+    Millivolt,
+    Microvolt,
+    Ampere,
+    Milliamp,
+    Microamp,
+    Count,
+    Ohm,
+    Kiloohm,
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+    Pascal,
+    Kilopascal,
+    Megapascal,
+    Millibar,
+    Bar,
+    Psi,
+    InchH2O,
+    Percent,
+    Unitless,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UnitError {
+    Parse(String),
+    UnknownUnit(String),
+    Incompatible(Unit, Unit),
+}
+
+// Every unit we know about, in the order they are cycled through in the UI
+const ALL: [Unit; 21] = [
+    Unit::Volt,
+    Unit::Millivolt,
+    Unit::Microvolt,
+    Unit::Ampere,
+    Unit::Milliamp,
+    Unit::Microamp,
+    Unit::Count,
+    Unit::Ohm,
+    Unit::Kiloohm,
+    Unit::Celsius,
+    Unit::Fahrenheit,
+    Unit::Kelvin,
+    Unit::Pascal,
+    Unit::Kilopascal,
+    Unit::Megapascal,
+    Unit::Millibar,
+    Unit::Bar,
+    Unit::Psi,
+    Unit::InchH2O,
+    Unit::Percent,
+    Unit::Unitless,
+];
+
+impl Unit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Volt => "V",
+            Unit::Millivolt => "mV",
+            Unit::Microvolt => "uV",
+            Unit::Ampere => "A",
+            Unit::Milliamp => "mA",
+            Unit::Microamp => "uA",
+            Unit::Count => "counts",
+            Unit::Ohm => "ohm",
+            Unit::Kiloohm => "kohm",
+            Unit::Celsius => "°C",
+            Unit::Fahrenheit => "°F",
+            Unit::Kelvin => "K",
+            Unit::Pascal => "Pa",
+            Unit::Kilopascal => "kPa",
+            Unit::Megapascal => "MPa",
+            Unit::Millibar => "mbar",
+            Unit::Bar => "bar",
+            Unit::Psi => "psi",
+            Unit::InchH2O => "inH2O",
+            Unit::Percent => "%",
+            Unit::Unitless => "",
+        }
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            Unit::Volt | Unit::Millivolt | Unit::Microvolt => Dimension::Voltage,
+            Unit::Ampere | Unit::Milliamp | Unit::Microamp => Dimension::Current,
+            Unit::Count => Dimension::Counts,
+            Unit::Ohm | Unit::Kiloohm => Dimension::Resistance,
+            Unit::Celsius | Unit::Fahrenheit | Unit::Kelvin => Dimension::Temperature,
+            Unit::Pascal
+            | Unit::Kilopascal
+            | Unit::Megapascal
+            | Unit::Millibar
+            | Unit::Bar
+            | Unit::Psi
+            | Unit::InchH2O => Dimension::Pressure,
+            Unit::Percent | Unit::Unitless => Dimension::Dimensionless,
+        }
+    }
+
+    // (scale, offset) so that base = value * scale + offset
+    // base units are V, A, counts, ohm, K, Pa and plain numbers
+    fn to_base_factors(self) -> (f64, f64) {
+        match self {
+            Unit::Volt => (1.0, 0.0),
+            Unit::Millivolt => (1e-3, 0.0),
+            Unit::Microvolt => (1e-6, 0.0),
+            Unit::Ampere => (1.0, 0.0),
+            Unit::Milliamp => (1e-3, 0.0),
+            Unit::Microamp => (1e-6, 0.0),
+            Unit::Count => (1.0, 0.0),
+            Unit::Ohm => (1.0, 0.0),
+            Unit::Kiloohm => (1e3, 0.0),
+            Unit::Celsius => (1.0, 273.15),
+            Unit::Fahrenheit => (5.0 / 9.0, 459.67 * 5.0 / 9.0),
+            Unit::Kelvin => (1.0, 0.0),
+            Unit::Pascal => (1.0, 0.0),
+            Unit::Kilopascal => (1e3, 0.0),
+            Unit::Megapascal => (1e6, 0.0),
+            Unit::Millibar => (1e2, 0.0),
+            Unit::Bar => (1e5, 0.0),
+            Unit::Psi => (6894.757293168, 0.0),
+            Unit::InchH2O => (249.08891, 0.0),
+            // generic outputs are plain numbers anyway, so % is just a label on them
+            Unit::Percent => (1.0, 0.0),
+            Unit::Unitless => (1.0, 0.0),
+        }
+    }
+
+    // Convert a value in this unit to another unit of the same dimension
+    pub fn convert(&self, value: f64, to: Unit) -> Result<f64, UnitError> {
+        if self.dimension() != to.dimension() {
+            return Err(UnitError::Incompatible(*self, to));
+        }
+        let (scale, offset) = self.to_base_factors();
+        let (to_scale, to_offset) = to.to_base_factors();
+        Ok((value * scale + offset - to_offset) / to_scale)
+    }
+
+    // The next unit with the same dimension, wrapping around. Used to cycle units from the keyboard
+    pub fn next_compatible(&self) -> Unit {
+        let same: Vec<Unit> = ALL
+            .iter()
+            .cloned()
+            .filter(|u| u.dimension() == self.dimension())
+            .collect();
+        let i = same.iter().position(|u| u == self).unwrap_or(0);
+        same[(i + 1) % same.len()]
+    }
+
+    // Look up a unit by symbol. Exact matches win so "K" is kelvin, then we try ignoring case
+    pub fn parse(s: &str) -> Option<Unit> {
+        let s = s.trim();
+        if let Some(u) = ALIASES.iter().find(|(alias, _)| *alias == s) {
+            return Some(u.1);
+        }
+        ALIASES
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(s))
+            .map(|(_, u)| *u)
+    }
+}
+
+const ALIASES: [(&str, Unit); 37] = [
+    ("V", Unit::Volt),
+    ("mV", Unit::Millivolt),
+    ("uV", Unit::Microvolt),
+    ("µV", Unit::Microvolt),
+    ("A", Unit::Ampere),
+    ("mA", Unit::Milliamp),
+    ("uA", Unit::Microamp),
+    ("µA", Unit::Microamp),
+    ("counts", Unit::Count),
+    ("count", Unit::Count),
+    ("cnt", Unit::Count),
+    ("LSB", Unit::Count),
+    ("ohm", Unit::Ohm),
+    ("ohms", Unit::Ohm),
+    ("Ω", Unit::Ohm),
+    ("kohm", Unit::Kiloohm),
+    ("kΩ", Unit::Kiloohm),
+    ("°C", Unit::Celsius),
+    ("degC", Unit::Celsius),
+    ("C", Unit::Celsius),
+    ("°F", Unit::Fahrenheit),
+    ("degF", Unit::Fahrenheit),
+    ("F", Unit::Fahrenheit),
+    ("K", Unit::Kelvin),
+    ("Pa", Unit::Pascal),
+    ("kPa", Unit::Kilopascal),
+    ("MPa", Unit::Megapascal),
+    ("mbar", Unit::Millibar),
+    ("bar", Unit::Bar),
+    ("psi", Unit::Psi),
+    ("inH2O", Unit::InchH2O),
+    ("%", Unit::Percent),
+    ("pct", Unit::Percent),
+    ("none", Unit::Unitless),
+    ("-", Unit::Unitless),
+    ("unitless", Unit::Unitless),
+    ("", Unit::Unitless),
+];
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+// Split user input like "14.7", "14.7 psi" or "14.7psi" into the number and the unit typed after it.
+// "inf" and "nan" parse as floats but are no reading, so they are refused
+pub fn split_value(s: &str) -> Result<(f64, Option<Unit>), UnitError> {
+    let s = s.trim();
+    // find the longest prefix that is a finite number, the rest is the unit
+    let mut split = None;
+    for (i, _) in s.char_indices().chain([(s.len(), ' ')]) {
+        if s[..i].trim().parse::<f64>().is_ok_and(f64::is_finite) {
+            split = Some(i);
+        }
+    }
+    let split = split.ok_or_else(|| UnitError::Parse(s.to_owned()))?;
+    let value: f64 = s[..split].trim().parse().unwrap();
+    let rest = s[split..].trim();
+    if rest.is_empty() {
+        return Ok((value, None));
+    }
+    let unit = Unit::parse(rest).ok_or_else(|| UnitError::UnknownUnit(rest.to_owned()))?;
+    Ok((value, Some(unit)))
+}
+
+// Parse user input into a value expressed in `target`. A bare number is taken to already be in `target`
+pub fn parse_value(s: &str, target: Unit) -> Result<f64, UnitError> {
+    match split_value(s)? {
+        (value, Some(unit)) => unit.convert(value, target),
+        (value, None) => Ok(value),
+    }
+}
+
+// Parse a typed uncertainty into `target`, blank for none. Being a difference, a typed unit
+// only scales it: 1 °F of uncertainty is 5/9 °C, not -17.2 °C
+pub fn parse_uncertainty(s: &str, target: Unit) -> Result<Option<f64>, UnitError> {
+    if s.trim().is_empty() {
+        return Ok(None);
+    }
+    match split_value(s)? {
+        (value, Some(unit)) => Ok(Some(
+            unit.convert(value, target)? - unit.convert(0.0, target)?,
+        )),
+        (value, None) => Ok(Some(value)),
+    }
+}
+
+// Format a value with its unit symbol, leaving out the space for unitless values
+pub fn format_value(value: f64, unit: Unit) -> String {
+    match unit {
+        Unit::Unitless => format!("{:.4}", value),
+        _ => format!("{:.4} {}", value, unit),
+    }
+}
+
+// -------TESTS --------
+#[cfg(test)]
+mod tests {
+    use super::{parse_uncertainty, parse_value, Quantity, Unit, UnitError};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn convert_pressure() {
+        let bar = Unit::Psi.convert(14.7, Unit::Bar).unwrap();
+        assert!(close(bar, 1.0135287));
+        assert!(close(
+            Unit::Bar.convert(1.0, Unit::Kilopascal).unwrap(),
+            100.0
+        ));
+    }
+
+    #[test]
+    fn convert_temperature() {
+        assert!(close(
+            Unit::Celsius.convert(100.0, Unit::Fahrenheit).unwrap(),
+            212.0
+        ));
+        assert!(close(
+            Unit::Fahrenheit.convert(32.0, Unit::Kelvin).unwrap(),
+            273.15
+        ));
+        assert!(close(
+            Unit::Kelvin.convert(0.0, Unit::Celsius).unwrap(),
+            -273.15
+        ));
+    }
+
+    #[test]
+    fn incompatible_units() {
+        assert_eq!(
+            Err(UnitError::Incompatible(Unit::Volt, Unit::Bar)),
+            Unit::Volt.convert(1.0, Unit::Bar)
+        );
+    }
+
+    #[test]
+    fn parse_with_units() {
+        assert!(close(
+            parse_value("14.7 psi", Unit::Bar).unwrap(),
+            1.0135287
+        ));
+        assert!(close(parse_value("250mV", Unit::Volt).unwrap(), 0.25));
+        assert!(close(parse_value("-1.5e1", Unit::Celsius).unwrap(), -15.0));
+        assert!(close(parse_value("1 K", Unit::Kelvin).unwrap(), 1.0));
+        assert_eq!(
+            Err(UnitError::UnknownUnit("furlongs".to_owned())),
+            parse_value("3 furlongs", Unit::Volt)
+        );
+        assert!(parse_value("12 V", Unit::Bar).is_err());
+        assert!(parse_value("abc", Unit::Bar).is_err());
+        assert!(parse_value("nan V", Unit::Volt).is_err());
+        assert!(parse_value("inf", Unit::Volt).is_err());
+        assert!(parse_value("-infinity", Unit::Volt).is_err());
+        assert!(parse_value("1e999", Unit::Volt).is_err());
+    }
+
+    #[test]
+    fn parse_uncertainties() {
+        assert_eq!(Ok(None), parse_uncertainty(" ", Unit::Celsius));
+        assert!(close(
+            parse_uncertainty("0.9 F", Unit::Celsius).unwrap().unwrap(),
+            0.5
+        ));
+        assert!(close(
+            parse_uncertainty("2 mV", Unit::Volt).unwrap().unwrap(),
+            0.002
+        ));
+        assert!(parse_uncertainty("1 bar", Unit::Volt).is_err());
+        assert!(parse_uncertainty("NaN", Unit::Volt).is_err());
+    }
+
+    #[test]
+    fn cycle_units() {
+        assert_eq!(Unit::Fahrenheit, Unit::Celsius.next_compatible());
+        assert_eq!(Unit::Celsius, Unit::Kelvin.next_compatible());
+        assert_eq!(
+            Unit::Unitless,
+            Unit::Unitless.next_compatible().next_compatible()
+        );
+    }
+
+    #[test]
+    fn quantities_from_args() {
+        let q = Quantity::from_parts(Some("current"), None, Unit::Volt);
+        assert_eq!(Quantity::new("Current", Unit::Milliamp), q);
+        let q = Quantity::from_parts(Some("Tank level"), Some(Unit::Percent), Unit::Unitless);
+        assert_eq!("Tank level [%]", q.title());
+        let q = Quantity::from_parts(None, Some(Unit::Psi), Unit::Unitless);
+        assert_eq!("Pressure [psi]", q.title());
+        assert_eq!("Physical", Quantity::from_unit(Unit::Unitless).title());
+    }
+}